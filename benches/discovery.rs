@@ -0,0 +1,106 @@
+//! Times finding the targets on a port, so that slow discovery and the
+//! differences between backends and platforms can be measured rather than
+//! guessed.
+//!
+//! Run with `cargo bench --bench discovery`. The port is held by a listener
+//! of the benchmark itself, which is found but never killed.
+
+use clap::Parser;
+use clap::ValueEnum;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use killport::cli::{Backend, KillPortArgs, Port};
+use killport::killport::{Diagnostics, KillOptions, Killport, KillportOperations};
+use std::hint::black_box;
+use std::net::TcpListener;
+
+/// Returns the port and the killport options parsed from `args` for `port`.
+///
+/// # Arguments
+///
+/// * `port` - The port to search.
+/// * `args` - The killport options of the case.
+fn options(port: u16, args: &[&str]) -> (Port, KillOptions) {
+    let port = port.to_string();
+    let args = KillPortArgs::parse_from(["killport", &port].iter().chain(args));
+    (args.ports[0], KillOptions::from(&args))
+}
+
+/// Times finding the targets on `port` with `options`.
+///
+/// # Arguments
+///
+/// * `c` - The benchmark group of the case.
+/// * `id` - The name of the case.
+/// * `port` - The port to search.
+/// * `options` - The killport options of the case.
+fn bench<M: criterion::measurement::Measurement>(
+    c: &mut criterion::BenchmarkGroup<M>,
+    id: BenchmarkId,
+    port: Port,
+    options: &KillOptions,
+) {
+    let killport = Killport::new();
+    c.bench_with_input(id, options, |b, options| {
+        b.iter(|| {
+            let found = killport.find_target_killables(port, options, &mut Diagnostics::default());
+            black_box(found.map(|killables| killables.len()).ok())
+        })
+    });
+}
+
+fn discovery(c: &mut Criterion) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind a port");
+    let port = listener
+        .local_addr()
+        .expect("Failed to read the port")
+        .port();
+    let free_port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to find a free port")
+        .port();
+
+    let mut group = c.benchmark_group("discovery");
+    for (name, port, args) in [
+        ("processes", port, &["--mode", "process"][..]),
+        (
+            "processes and docker",
+            port,
+            &["--mode", "auto", "--internal-port"][..],
+        ),
+        ("docker", port, &["--mode", "container"][..]),
+        ("free port", free_port, &["--mode", "process"][..]),
+    ] {
+        let (port, options) = options(port, args);
+        bench(
+            &mut group,
+            BenchmarkId::from_parameter(name),
+            port,
+            &options,
+        );
+    }
+    group.finish();
+
+    // Each backend available here, compared on the same port
+    let (port, options) = options(port, &["--mode", "process"]);
+    let mut group = c.benchmark_group("backend");
+    for backend in Backend::value_variants() {
+        if backend.is_available() {
+            let options = KillOptions {
+                backend: *backend,
+                ..options.clone()
+            };
+            bench(
+                &mut group,
+                BenchmarkId::from_parameter(backend),
+                port,
+                &options,
+            );
+        }
+    }
+    group.finish();
+
+    drop(listener);
+}
+
+criterion_group!(benches, discovery);
+criterion_main!(benches);
@@ -0,0 +1,567 @@
+use crate::cli::Port;
+use crate::killport::KillableType;
+use log::warn;
+use std::env;
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+#[cfg(unix)]
+use std::io::ErrorKind;
+use std::io::{Error, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File the actions are appended to, if any.
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// System log the actions are sent to, if any.
+#[cfg(unix)]
+static SYSTEM_LOG: Mutex<Option<SystemLog>> = Mutex::new(None);
+
+/// Socket of the systemd journal taking entries of fields.
+#[cfg(unix)]
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Sockets of the syslog daemon, on Linux and on macOS.
+#[cfg(unix)]
+const SYSLOG_SOCKETS: [&str; 2] = ["/dev/log", "/var/run/syslog"];
+
+/// Audit log the actions are appended to, if any.
+static AUDIT_LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+
+/// Identifier the actions are logged under in the system log.
+const IDENTIFIER: &str = "killport";
+
+/// A system log the actions are sent to.
+#[cfg(unix)]
+enum SystemLog {
+    /// The systemd journal, which keeps the fields of each action.
+    Journal(UnixDatagram),
+    /// A syslog daemon, which is sent the fields as a line.
+    Syslog(UnixDatagram),
+}
+
+/// An audit log, with the context of the run its entries share.
+struct AuditLog {
+    file: File,
+    /// The user running killport, if known.
+    user: Option<String>,
+    /// The user who ran killport through sudo, if any.
+    sudo_user: Option<String>,
+    /// The command line killport was run with.
+    args: Vec<String>,
+}
+
+/// How an action on a target turned out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The action was carried out.
+    Done,
+    /// The target was already gone.
+    Gone,
+    /// The action failed with this error.
+    Failed(String),
+}
+
+impl Outcome {
+    /// Returns the outcome of an action from its result.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The result of the action, false if the target was already gone.
+    pub fn of(result: &Result<bool, Error>) -> Self {
+        match result {
+            Ok(true) => Outcome::Done,
+            Ok(false) => Outcome::Gone,
+            Err(e) => Outcome::Failed(e.to_string()),
+        }
+    }
+}
+
+/// An action killport took on a target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionRecord {
+    pub timestamp: SystemTime,
+    /// Port the target was found on.
+    pub port: Port,
+    /// Process ID of the target, if it is a process.
+    pub pid: Option<u32>,
+    pub target_type: KillableType,
+    pub name: String,
+    /// The action taken, e.g. "kill" or "stop".
+    pub action: &'static str,
+    /// Signal sent to the target, if any.
+    pub signal: Option<String>,
+    pub outcome: Outcome,
+}
+
+impl ActionRecord {
+    /// Formats the record as a line of `key=value` fields, quoting the
+    /// values holding spaces, e.g. `2026-10-16T09:30:00Z port=8080 pid=4242
+    /// type=process name="node server.js" action=kill signal=SIGKILL
+    /// result=ok`.
+    pub fn to_line(&self) -> String {
+        let mut line = format_timestamp(self.timestamp);
+        for (key, value) in self.fields() {
+            let _ = write!(line, " {}={}", key, quote(&value));
+        }
+
+        line
+    }
+
+    /// Returns the fields of the record, without its timestamp.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("port", self.port.to_string())];
+        if let Some(pid) = self.pid {
+            fields.push(("pid", pid.to_string()));
+        }
+        fields.push(("type", self.target_type.to_string()));
+        fields.push(("name", self.name.to_string()));
+        fields.push(("action", self.action.to_string()));
+        if let Some(signal) = &self.signal {
+            fields.push(("signal", signal.to_string()));
+        }
+        match &self.outcome {
+            Outcome::Done => fields.push(("result", "ok".to_string())),
+            Outcome::Gone => fields.push(("result", "gone".to_string())),
+            Outcome::Failed(error) => {
+                fields.push(("result", "error".to_string()));
+                fields.push(("error", error.to_string()));
+            }
+        }
+
+        fields
+    }
+
+    /// Describes the action in a sentence, e.g. "kill of process 'node
+    /// server.js' (PID 4242) on port 8080 with SIGKILL succeeded".
+    pub fn message(&self) -> String {
+        let mut message = format!("{} of {} '{}'", self.action, self.target_type, self.name);
+        if let Some(pid) = self.pid {
+            let _ = write!(message, " (PID {})", pid);
+        }
+        let _ = write!(message, " on port {}", self.port);
+        if let Some(signal) = &self.signal {
+            let _ = write!(message, " with {}", signal);
+        }
+        match &self.outcome {
+            Outcome::Done => message.push_str(" succeeded"),
+            Outcome::Gone => message.push_str(" found it already gone"),
+            Outcome::Failed(error) => {
+                let _ = write!(message, " failed: {}", error);
+            }
+        }
+
+        message
+    }
+
+    /// Encodes the record as an entry of the systemd journal's native
+    /// protocol, with its fields named `KILLPORT_<FIELD>` next to the
+    /// message, priority and identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user running killport, if known.
+    pub fn to_journal_entry(&self, user: Option<&str>) -> Vec<u8> {
+        let priority = match self.outcome {
+            Outcome::Failed(_) => "3",
+            _ => "5",
+        };
+        let mut fields = vec![
+            ("MESSAGE".to_string(), self.message()),
+            ("PRIORITY".to_string(), priority.to_string()),
+            ("SYSLOG_IDENTIFIER".to_string(), IDENTIFIER.to_string()),
+        ];
+        if let Some(user) = user {
+            fields.push(("KILLPORT_USER".to_string(), user.to_string()));
+        }
+        fields.extend(
+            self.fields()
+                .into_iter()
+                .map(|(key, value)| (format!("KILLPORT_{}", key.to_uppercase()), value)),
+        );
+
+        let mut entry = vec![];
+        for (key, value) in fields {
+            entry.extend_from_slice(key.as_bytes());
+            // Values spanning lines are given with their length instead
+            if value.contains('\n') {
+                entry.push(b'\n');
+                entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            } else {
+                entry.push(b'=');
+            }
+            entry.extend_from_slice(value.as_bytes());
+            entry.push(b'\n');
+        }
+
+        entry
+    }
+
+    /// Returns the entry of the record in the audit log.
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user running killport, if known.
+    /// * `sudo_user` - The user who ran killport through sudo, if any.
+    /// * `args` - The command line killport was run with.
+    pub fn to_audit_entry(
+        &self,
+        user: Option<&str>,
+        sudo_user: Option<&str>,
+        args: &[String],
+    ) -> serde_json::Value {
+        let mut entry = serde_json::json!({
+            "time": format_timestamp(self.timestamp),
+            "user": user,
+            "args": args,
+            "message": self.message(),
+        });
+        if let Some(sudo_user) = sudo_user {
+            entry["sudo_user"] = sudo_user.into();
+        }
+        for (key, value) in self.fields() {
+            entry[key] = match key {
+                "port" => self.port.number().into(),
+                "pid" => self.pid.into(),
+                _ => value.into(),
+            };
+        }
+
+        entry
+    }
+}
+
+/// Returns the audit log of the user, `killport/audit.jsonl` in the user
+/// data directory: `XDG_DATA_HOME` or `~/.local/share` on Linux,
+/// `~/Library/Application Support` on macOS and `%LOCALAPPDATA%` on Windows.
+///
+/// Run through sudo, it is the audit log of the user behind sudo, in their
+/// home directory, so that the actions they took as root show up in their
+/// own `--show-audit`.
+pub fn audit_log_path() -> Option<PathBuf> {
+    let data_dir = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if let Some(home) = sudo_home() {
+        // The environment under sudo is that of root, so only the home
+        // directory of the user behind it is known
+        Some(home.join(if cfg!(target_os = "macos") {
+            "Library/Application Support"
+        } else {
+            ".local/share"
+        }))
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    }?;
+
+    Some(data_dir.join("killport").join("audit.jsonl"))
+}
+
+/// Returns the home directory of the user who ran killport through sudo, if
+/// any.
+fn sudo_home() -> Option<PathBuf> {
+    #[cfg(unix)]
+    return sudo_account().map(|user| user.dir);
+
+    #[cfg(not(unix))]
+    None
+}
+
+/// Returns the account of the user who ran killport through sudo, if any.
+#[cfg(unix)]
+fn sudo_account() -> Option<nix::unistd::User> {
+    if !nix::unistd::getuid().is_root() {
+        return None;
+    }
+    let name = env::var("SUDO_USER").ok().filter(|user| !user.is_empty())?;
+    nix::unistd::User::from_name(&name).ok().flatten()
+}
+
+/// Opens the audit log the actions taken from now on are appended to,
+/// creating it if needed.
+///
+/// # Arguments
+///
+/// * `path` - The audit log.
+/// * `args` - The command line killport was run with.
+pub fn set_audit_log(path: &Path, args: Vec<String>) -> Result<(), Error> {
+    let open = || {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new().create(true).append(true).open(path)
+    };
+    let file = open().map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Failed to open audit log {}: {}", path.display(), e),
+        )
+    })?;
+
+    // A log created through sudo is handed to the user behind it, who could
+    // not append to it otherwise
+    #[cfg(unix)]
+    if let Some(user) = sudo_account().filter(|user| path.starts_with(&user.dir)) {
+        for owned in path.parent().into_iter().chain([path]) {
+            if let Err(e) =
+                std::os::unix::fs::chown(owned, Some(user.uid.as_raw()), Some(user.gid.as_raw()))
+            {
+                warn!("Failed to hand {} to {}: {}", owned.display(), user.name, e);
+            }
+        }
+    }
+
+    *AUDIT_LOG.lock().unwrap_or_else(PoisonError::into_inner) = Some(AuditLog {
+        file,
+        user: current_user(),
+        sudo_user: env::var("SUDO_USER").ok().filter(|user| !user.is_empty()),
+        args,
+    });
+    Ok(())
+}
+
+/// Reads the entries of an audit log, keeping those of the given ports, or
+/// all of them when no port is given. Lines that are not entries are
+/// skipped.
+///
+/// # Arguments
+///
+/// * `path` - The audit log.
+/// * `ports` - The ports to keep the entries of.
+pub fn read_audit_log(path: &Path, ports: &[Port]) -> Result<Vec<serde_json::Value>, Error> {
+    let log = fs::read_to_string(path).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Failed to read audit log {}: {}", path.display(), e),
+        )
+    })?;
+
+    Ok(log
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|entry| {
+            ports.is_empty()
+                || entry["port"].as_u64().is_some_and(|port| {
+                    ports
+                        .iter()
+                        .any(|wanted| u64::from(wanted.number()) == port)
+                })
+        })
+        .collect())
+}
+
+/// Describes an audit entry in a line, e.g. "2026-10-16T09:30:00Z alice:
+/// kill of process 'node' (PID 4242) on port 8080 with SIGKILL succeeded
+/// (killport 8080)".
+///
+/// # Arguments
+///
+/// * `entry` - The audit entry.
+pub fn describe_audit_entry(entry: &serde_json::Value) -> String {
+    let mut user = entry["user"].as_str().unwrap_or("unknown user").to_string();
+    if let Some(sudo_user) = entry["sudo_user"].as_str() {
+        let _ = write!(user, " (sudo by {})", sudo_user);
+    }
+    let args: Vec<&str> = entry["args"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|arg| arg.as_str())
+        .collect();
+
+    format!(
+        "{} {}: {} ({})",
+        entry["time"].as_str().unwrap_or_default(),
+        user,
+        entry["message"].as_str().unwrap_or_default(),
+        args.join(" ")
+    )
+}
+
+/// Returns the name of the user running killport.
+fn current_user() -> Option<String> {
+    #[cfg(unix)]
+    return nix::unistd::User::from_uid(nix::unistd::getuid())
+        .ok()
+        .flatten()
+        .map(|user| user.name);
+
+    #[cfg(not(unix))]
+    env::var("USERNAME").ok()
+}
+
+/// Opens the file the actions taken from now on are appended to, creating
+/// it if needed.
+///
+/// # Arguments
+///
+/// * `path` - The log file.
+pub fn set_log_file(path: &Path) -> Result<(), Error> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Failed to open log file {}: {}", path.display(), e),
+            )
+        })?;
+
+    *LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner) = Some(file);
+    Ok(())
+}
+
+/// Connects to the system log the actions taken from now on are sent to:
+/// the systemd journal when it runs, or else the syslog daemon.
+#[cfg(unix)]
+pub fn set_system_log() -> Result<(), Error> {
+    let connect = |path: &str| -> Result<UnixDatagram, Error> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(socket)
+    };
+
+    let system_log = match connect(JOURNAL_SOCKET) {
+        Ok(socket) => SystemLog::Journal(socket),
+        Err(_) => SYSLOG_SOCKETS
+            .iter()
+            .find_map(|path| connect(path).ok())
+            .map(SystemLog::Syslog)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    "Neither the systemd journal nor a syslog daemon is running",
+                )
+            })?,
+    };
+
+    *SYSTEM_LOG.lock().unwrap_or_else(PoisonError::into_inner) = Some(system_log);
+    Ok(())
+}
+
+/// Records an action in the log file, the system log and the audit log, if
+/// they are set.
+/// A record that cannot be written is warned about rather than failing the
+/// action.
+///
+/// # Arguments
+///
+/// * `record` - The action taken.
+pub fn record(record: &ActionRecord) {
+    let mut log_file = LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(file) = log_file.as_mut() {
+        if let Err(e) = writeln!(file, "{}", record.to_line()) {
+            warn!("Failed to write to the log file: {}", e);
+        }
+    }
+
+    let mut audit_log = AUDIT_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(audit_log) = audit_log.as_mut() {
+        let entry = record.to_audit_entry(
+            audit_log.user.as_deref(),
+            audit_log.sudo_user.as_deref(),
+            &audit_log.args,
+        );
+        if let Err(e) = writeln!(audit_log.file, "{}", entry) {
+            warn!("Failed to write to the audit log: {}", e);
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        let system_log = SYSTEM_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+        let user = current_user();
+        let sent = match system_log.as_ref() {
+            Some(SystemLog::Journal(socket)) => {
+                socket.send(&record.to_journal_entry(user.as_deref()))
+            }
+            Some(SystemLog::Syslog(socket)) => {
+                // The user facility, at the notice or error level
+                let priority = match record.outcome {
+                    Outcome::Failed(_) => 11,
+                    _ => 13,
+                };
+                let mut line = format!("<{}>{}[{}]:", priority, IDENTIFIER, std::process::id());
+                if let Some(user) = &user {
+                    let _ = write!(line, " user={}", quote(user));
+                }
+                for (key, value) in record.fields() {
+                    let _ = write!(line, " {}={}", key, quote(&value));
+                }
+                socket.send(line.as_bytes())
+            }
+            None => Ok(0),
+        };
+        if let Err(e) = sent {
+            warn!("Failed to write to the system log: {}", e);
+        }
+    }
+}
+
+/// Quotes a field value holding spaces, quotes or `=`, escaping quotes and
+/// backslashes.
+///
+/// # Arguments
+///
+/// * `value` - The field value.
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains([' ', '"', '=', '\\', '\n', '\t']) {
+        return value.to_string();
+    }
+
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with seconds, e.g.
+/// `2026-10-16T09:30:00Z`.
+///
+/// # Arguments
+///
+/// * `time` - The time to format.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    let (days, time_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60
+    )
+}
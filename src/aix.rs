@@ -0,0 +1,86 @@
+//! Best-effort discovery on AIX, enabled with the `aix` feature.
+//!
+//! AIX has no procfs socket tables and perfstat only reports totals, so the
+//! sockets of each process are listed with `procfiles`, which needs no
+//! privileges beyond those to inspect the process. Unlike `rmsock`, it
+//! never removes the sockets it looks at.
+
+use crate::cli::Port;
+use crate::killport::Diagnostics;
+use crate::service::run_command;
+use crate::socket::parse_procfiles;
+use crate::unix::UnixProcess;
+use log::debug;
+use nix::errno::Errno;
+use nix::sys::signal::kill;
+use nix::unistd::{geteuid, Pid};
+use std::io::Error;
+use std::process::Command;
+
+/// Number of processes listed by each `procfiles` run, keeping its command
+/// line short.
+const PROCFILES_BATCH: usize = 256;
+
+/// Finds the processes associated with the specified `port`.
+///
+/// Processes that cannot be inspected, usually as they belong to another
+/// user, are recorded in `diagnostics`.
+///
+/// Returns a `Vec` of native processes.
+///
+/// # Arguments
+///
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects the processes that could not be inspected
+pub fn find_target_processes(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, Error> {
+    let pids: Vec<String> = run_command("ps", &["-e", "-o", "pid="])?
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    let mut target_pids: Vec<UnixProcess> = vec![];
+
+    for batch in pids.chunks(PROCFILES_BATCH) {
+        // procfiles fails when a process cannot be inspected, yet still
+        // lists the others
+        let output = Command::new("procfiles")
+            .arg("-n")
+            .args(batch)
+            .output()
+            .map_err(|e| Error::new(e.kind(), format!("Failed to run procfiles: {}", e)))?;
+        let holders = parse_procfiles(&String::from_utf8_lossy(&output.stdout), port);
+
+        for pid in batch.iter().filter_map(|pid| pid.parse::<u32>().ok()) {
+            if holders.iter().any(|holder| holder.pid == pid) {
+                continue;
+            }
+            // Only processes of other users are off limits, anything else
+            // has exited since it was listed
+            let alive = kill(Pid::from_raw(pid as i32), None) != Err(Errno::ESRCH);
+            if alive && !geteuid().is_root() {
+                debug!("Could not inspect process with PID {}", pid);
+                diagnostics.uninspected.push(pid);
+            }
+        }
+
+        for holder in holders
+            .into_iter()
+            .filter(|holder| !holder.sockets.is_empty())
+        {
+            debug!(
+                "Found process '{}' with PID {} holding {}",
+                holder.name,
+                holder.pid,
+                holder.sockets.join(", ")
+            );
+            target_pids.push(
+                UnixProcess::new(Pid::from_raw(holder.pid as i32), holder.name)
+                    .with_sockets(holder.sockets),
+            );
+        }
+    }
+
+    Ok(target_pids)
+}
@@ -1,8 +1,187 @@
 use clap::{Parser, ValueEnum};
 use clap_verbosity_flag::{Verbosity, WarnLevel};
 use core::fmt;
+use std::env;
+use std::io::{Error, ErrorKind};
+use std::iter;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
+use crate::docker::LabelFilter;
+use crate::killport::{KillOptions, GRACEFUL_PROCESSES};
 use crate::signal::KillportSignal;
+use crate::socket::Protocol;
+
+/// A port to act on, optionally restricted to one protocol.
+///
+/// Ports are given by number or by service name, either prefixed with a
+/// protocol, e.g. `8080`, `http` or `udp:53`. Port 0 is rejected, as nothing
+/// can be bound to it: binding it asks the system for any free port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Port {
+    number: u16,
+    protocol: Option<Protocol>,
+}
+
+impl Port {
+    /// Returns the port with the given number on every protocol.
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - The port number, from 1 to 65535.
+    pub fn new(number: u16) -> Result<Self, Error> {
+        if number == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Port 0 cannot be acted on, as binding it asks the system for any free port; ports go from 1 to 65535",
+            ));
+        }
+        Ok(Port {
+            number,
+            protocol: None,
+        })
+    }
+
+    /// Restricts the port to a protocol, leaving the sockets of the others
+    /// alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `protocol` - The protocol of the sockets acted on.
+    pub fn with_protocol(self, protocol: Protocol) -> Self {
+        Port {
+            protocol: Some(protocol),
+            ..self
+        }
+    }
+
+    /// Returns the port number.
+    pub fn number(&self) -> u16 {
+        self.number
+    }
+
+    /// Returns the protocol the port is restricted to, if any.
+    pub fn protocol(&self) -> Option<Protocol> {
+        self.protocol
+    }
+
+    /// Returns true if the sockets of `protocol` on the port are acted on.
+    ///
+    /// # Arguments
+    ///
+    /// * `protocol` - The protocol of a socket bound to the port.
+    pub fn carries(&self, protocol: Protocol) -> bool {
+        self.protocol.is_none_or(|wanted| wanted == protocol)
+    }
+}
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.number)
+    }
+}
+
+impl FromStr for Port {
+    type Err = Error;
+
+    /// Parses a port given by number or service name, optionally prefixed
+    /// with a protocol, e.g. "8080", "http" or "udp:53".
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (protocol, port) = match value.trim().split_once(':') {
+            Some((protocol, port)) => {
+                let protocol = protocol.parse::<Protocol>().map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "Unknown protocol '{}' in '{}'; ports are restricted to tcp, udp or sctp, e.g. udp:53",
+                            protocol, value
+                        ),
+                    )
+                })?;
+                (Some(protocol), port)
+            }
+            None => (None, value.trim()),
+        };
+
+        let number = if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) {
+            port.parse::<u16>().map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Port {} is out of range; ports go from 1 to 65535", port),
+                )
+            })?
+        } else {
+            lookup_service(port, protocol)?
+        };
+
+        let port = Port::new(number)?;
+        Ok(match protocol {
+            Some(protocol) => port.with_protocol(protocol),
+            None => port,
+        })
+    }
+}
+
+/// Returns the path of the services database, mapping service names to
+/// ports.
+fn services_path() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into()))
+            .join("System32\\drivers\\etc\\services")
+    } else {
+        PathBuf::from("/etc/services")
+    }
+}
+
+/// Returns the port of a service from the services database.
+///
+/// # Arguments
+///
+/// * `name` - The service name or alias, e.g. "http".
+/// * `protocol` - The protocol the service must be listed for, if any.
+pub fn lookup_service(name: &str, protocol: Option<Protocol>) -> Result<u16, Error> {
+    let path = services_path();
+    // A missing database only leaves the name unknown
+    let services = std::fs::read_to_string(&path).unwrap_or_default();
+    find_service_port(&services, name, protocol).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Unknown port '{}', which is neither a number from 1 to 65535 nor a service listed in {}",
+                name,
+                path.display()
+            ),
+        )
+    })
+}
+
+/// Finds the port of a service in a services database, whose lines read
+/// "name port/protocol [aliases...] [# comment]".
+///
+/// # Arguments
+///
+/// * `services` - The contents of the services database.
+/// * `name` - The service name or alias, in any case.
+/// * `protocol` - The protocol the service must be listed for, if any.
+pub fn find_service_port(services: &str, name: &str, protocol: Option<Protocol>) -> Option<u16> {
+    services.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let service = fields.next()?;
+        let (port, listed_protocol) = fields.next()?.split_once('/')?;
+
+        let named = iter::once(service)
+            .chain(fields)
+            .any(|alias| alias.eq_ignore_ascii_case(name));
+        let listed = protocol.is_none_or(|protocol| listed_protocol.parse().ok() == Some(protocol));
+        if named && listed {
+            port.parse().ok()
+        } else {
+            None
+        }
+    })
+}
 
 /// Modes of operation for killport.
 #[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
@@ -23,6 +202,100 @@ impl fmt::Display for Mode {
     }
 }
 
+/// Actions taken on the containers found on a port.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ContainerAction {
+    /// Send the signal to the container.
+    Kill,
+    /// Stop the container gracefully, killing it after the stop timeout.
+    Stop,
+    /// Stop the container, then remove it so its restart policy cannot
+    /// bring it back.
+    Rm,
+}
+
+impl fmt::Display for ContainerAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let variant = match *self {
+            ContainerAction::Kill => "kill",
+            ContainerAction::Stop => "stop",
+            ContainerAction::Rm => "rm",
+        };
+        write!(f, "{}", variant)
+    }
+}
+
+/// Formats of the run summary.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SummaryFormat {
+    /// A single JSON document.
+    Json,
+}
+
+impl fmt::Display for SummaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let variant = match *self {
+            SummaryFormat::Json => "json",
+        };
+        write!(f, "{}", variant)
+    }
+}
+
+/// Implementations finding the processes on a port.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Backend {
+    /// The platform's own, falling back to lsof, ss or netstat when the
+    /// processes on the port cannot be inspected.
+    Auto,
+    /// The socket tables of /proc (Linux).
+    Procfs,
+    /// The PCB lists, inspecting every process when they fall short (macOS).
+    Pcblist,
+    /// Inspecting the file descriptors of every process (macOS).
+    PidWalk,
+    /// The socket tables of the IP Helper API (Windows).
+    Iphelper,
+    /// The open files listed by procfiles (AIX).
+    Procfiles,
+    /// The sockets listed by lsof (Unix).
+    Lsof,
+    /// The sockets listed by ss (Linux).
+    Ss,
+    /// The sockets listed by netstat from net-tools (Linux).
+    Netstat,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let variant = match *self {
+            Backend::Auto => "auto",
+            Backend::Procfs => "procfs",
+            Backend::Pcblist => "pcblist",
+            Backend::PidWalk => "pid-walk",
+            Backend::Iphelper => "iphelper",
+            Backend::Procfiles => "procfiles",
+            Backend::Lsof => "lsof",
+            Backend::Ss => "ss",
+            Backend::Netstat => "netstat",
+        };
+        write!(f, "{}", variant)
+    }
+}
+
+impl Backend {
+    /// Returns true if the backend can run on this platform.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::Auto => true,
+            Backend::Procfs => cfg!(target_os = "linux"),
+            Backend::Pcblist | Backend::PidWalk => cfg!(target_os = "macos"),
+            Backend::Iphelper => cfg!(windows),
+            Backend::Procfiles => cfg!(all(target_os = "aix", feature = "aix")),
+            Backend::Lsof | Backend::Ss | Backend::Netstat => cfg!(unix),
+        }
+    }
+}
+
 /// Returns appropriate service descriptors based on the mode.
 ///
 /// # Arguments
@@ -42,13 +315,13 @@ pub fn service_descriptors(mode: Mode) -> (&'static str, &'static str) {
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct KillPortArgs {
-    /// A list of port numbers to kill processes on.
+    /// A list of ports to kill processes on.
     #[arg(
         name = "ports",
-        help = "The list of port numbers to kill processes or containers on",
-        required = true
+        help = "The list of ports to kill processes or containers on, by number or service name, optionally restricted to a protocol, e.g. 8080, http or udp:53",
+        required_unless_present = "show_audit"
     )]
-    pub ports: Vec<u16>,
+    pub ports: Vec<Port>,
 
     /// Operation mode.
     #[arg(
@@ -63,12 +336,210 @@ pub struct KillPortArgs {
         long,
         short = 's',
         name = "SIG",
-        help = "SIG is a signal name",
+        help = "SIG is a signal name or number, e.g. sigterm, term or 15, also written as for kill, e.g. -TERM or -15",
         default_value = "sigkill",
+        allow_hyphen_values = true,
         value_parser = parse_signal
     )]
     pub signal: KillportSignal,
 
+    /// More processes to send SIGTERM rather than SIGKILL.
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_delimiter = ',',
+        help = "Also send SIGTERM rather than the default SIGKILL to processes with this name, like to the databases postgres, mysqld, mariadbd, mongod and redis-server (repeatable or comma-separated)"
+    )]
+    pub graceful: Vec<String>,
+
+    /// Bypass the safety rails and prompts, e.g. for automation.
+    #[arg(
+        long,
+        visible_alias = "yes",
+        help = "Bypass every safety rail without asking: send SIGKILL even to databases and the processes given with --graceful, act on the containers of cluster nodes and kill the forwarders of container VMs"
+    )]
+    pub force: bool,
+
+    /// What to do with the containers on the port.
+    #[arg(
+        long,
+        value_name = "ACTION",
+        help = "Action to take on containers: kill (default, send the signal), stop (stop gracefully) or rm (stop, then remove)",
+        default_value_t = ContainerAction::Kill
+    )]
+    pub container_action: ContainerAction,
+
+    /// Seconds given to containers to stop before they are killed.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Seconds given to containers to stop with --container-action stop or rm before they are killed (default: the container's own stop timeout)"
+    )]
+    pub stop_timeout: Option<u64>,
+
+    /// Also match containers by the port they listen on internally.
+    #[arg(
+        long,
+        help = "Also match containers listening on the port inside their own network, even when it is not published on the host"
+    )]
+    pub internal_port: bool,
+
+    /// Time given to the Docker daemon to answer, in milliseconds.
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 2000,
+        help = "Milliseconds given to the Docker daemon to answer before containers are skipped"
+    )]
+    pub docker_timeout: u64,
+
+    /// Never connect to the Docker API, nor search the containers of
+    /// containerd.
+    #[arg(
+        long,
+        help = "Never connect to the Docker API or run nerdctl, e.g. in sandboxed or CI environments; containers are not searched"
+    )]
+    pub no_docker: bool,
+
+    /// Certificate authority of a Docker host secured with TLS.
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Certificate authority the certificate of a TLS-secured Docker host is checked against (default: ca.pem in DOCKER_CERT_PATH or ~/.docker)"
+    )]
+    pub docker_tls_ca: Option<PathBuf>,
+
+    /// Client certificate for a Docker host secured with TLS.
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Client certificate for a TLS-secured Docker host (default: cert.pem in DOCKER_CERT_PATH or ~/.docker)"
+    )]
+    pub docker_tls_cert: Option<PathBuf>,
+
+    /// Key of the client certificate for a Docker host secured with TLS.
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Key of the client certificate for a TLS-secured Docker host (default: key.pem in DOCKER_CERT_PATH or ~/.docker)"
+    )]
+    pub docker_tls_key: Option<PathBuf>,
+
+    /// Delete the Kubernetes pods of the targets.
+    #[arg(
+        long,
+        help = "Delete the Kubernetes pod a process runs in instead of killing it, or the pod a kubectl port-forward leads to along with the forward"
+    )]
+    pub delete_pod: bool,
+
+    /// Also act on the node containers of local Kubernetes clusters.
+    #[arg(
+        long,
+        help = "Also act on containers running the nodes of kind, k3d or minikube clusters, which are otherwise left alone with a warning"
+    )]
+    pub kill_nodes: bool,
+
+    /// Only act on containers with these labels.
+    #[arg(
+        long = "container-label",
+        value_name = "KEY[=VALUE]",
+        value_parser = parse_label_filter,
+        help = "Only act on containers with this label, or with this label set to VALUE; can be given multiple times"
+    )]
+    pub container_labels: Vec<LabelFilter>,
+
+    /// Only act on the containers of this Docker Compose project.
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Only act on the containers of this Docker Compose project"
+    )]
+    pub project: Option<String>,
+
+    /// Remove containers along with their anonymous volumes and endpoints.
+    #[arg(
+        long,
+        help = "After acting on a container, remove it along with its anonymous volumes and any network endpoint it left behind"
+    )]
+    pub prune_container: bool,
+
+    /// Act on the whole podman pod of a matched infra container.
+    #[arg(
+        long,
+        help = "When a container found is the infra container of a podman pod, act on the whole pod instead"
+    )]
+    pub stop_pod: bool,
+
+    /// File the actions taken are appended to.
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append a line for each action taken (timestamp, port, PID, name, signal and result) to this file, whatever the verbosity"
+    )]
+    pub log_file: Option<PathBuf>,
+
+    /// Send the actions taken to the systemd journal or syslog.
+    #[arg(
+        long,
+        hide = cfg!(not(unix)),
+        help = "Send each action taken to the systemd journal, with its details as fields, or else to syslog (Unix only)"
+    )]
+    pub syslog: bool,
+
+    /// Record the actions taken in the audit log.
+    #[arg(
+        long,
+        help = "Record each action taken, with the user and command line, in the audit log (killport/audit.jsonl in the user data directory)"
+    )]
+    pub audit: bool,
+
+    /// Show the audit log instead of killing anything.
+    #[arg(
+        long,
+        help = "Show the actions recorded in the audit log, only those on the given ports if any, instead of killing anything"
+    )]
+    pub show_audit: bool,
+
+    /// Write a summary of the run.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Write a summary of the run, with the result on each port, aggregate counts and the reason for the exit code, to stdout instead of the usual output, or to --summary-file"
+    )]
+    pub summary: Option<SummaryFormat>,
+
+    /// File the summary is written to.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "summary",
+        help = "Write the summary to a file rather than stdout, keeping the usual output"
+    )]
+    pub summary_file: Option<PathBuf>,
+
+    /// Explain why nothing was found on a port.
+    #[arg(
+        long,
+        help = "When nothing is found on a port, list what was searched and the likely reasons, such as processes that could not be inspected or an unreachable Docker daemon"
+    )]
+    pub why: bool,
+
+    /// Implementation finding the processes on the ports.
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        default_value_t = Backend::Auto,
+        help = "Find processes with this implementation, e.g. to tell whether a discrepancy comes from killport's own discovery: auto (default), procfs (Linux), pcblist or pid-walk (macOS), iphelper (Windows), procfiles (AIX), lsof, ss or netstat"
+    )]
+    pub backend: Backend,
+
+    /// Print the time spent in each phase of the run.
+    #[arg(
+        long,
+        help = "Print the time spent on each port finding processes, probing Docker, finding containers, acting on the targets and checking the port is free, to stderr"
+    )]
+    pub timings: bool,
+
     /// A verbosity flag to control the level of logging output.
     #[command(flatten)]
     pub verbose: Verbosity<WarnLevel>,
@@ -79,6 +550,126 @@ pub struct KillPortArgs {
         help = "Perform a dry run without killing any processes or containers"
     )]
     pub dry_run: bool,
+
+    /// Re-run killport as root, or elevated by UAC on Windows, when a
+    /// permission error is detected.
+    #[arg(
+        long,
+        visible_alias = "elevate",
+        help = "Re-run with sudo (or pkexec), or through UAC on Windows, when permissions are insufficient"
+    )]
+    pub sudo: bool,
+
+    /// Stop the service supervising a target instead of killing it.
+    #[arg(
+        long,
+        help = "Stop the service (e.g. systemd unit, launchd job, brew service, Windows service, portproxy rule, snap or flatpak) supervising a process instead of killing it"
+    )]
+    pub stop_service: bool,
+
+    /// Also kill the child processes of the processes on the port.
+    #[arg(
+        long,
+        help = "Also kill the processes spawned by the processes listening on the ports"
+    )]
+    pub kill_children: bool,
+
+    /// Also kill up to this many levels of parent processes of the processes on the port.
+    #[arg(
+        long,
+        value_name = "DEPTH",
+        default_value_t = 0,
+        help = "Also kill up to DEPTH levels of processes that spawned the processes listening on the ports, never going past shells, session leaders or system processes"
+    )]
+    pub kill_parents: usize,
+
+    /// Only kill the processes owned by this user.
+    #[arg(
+        long,
+        value_name = "USER",
+        help = "Only kill processes owned by USER (a name, or DOMAIN\\name on Windows), leaving those of other users alone"
+    )]
+    pub user: Option<String>,
+
+    /// Wait for the killed targets to exit and the ports to be freed.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "10",
+        help = "Wait up to SECONDS (default: 10) for each killed process or container to exit, then make sure the port is free, failing otherwise"
+    )]
+    pub wait: Option<u64>,
+
+    /// Block new connections to the ports with pf until interrupted (macOS only).
+    #[arg(
+        long,
+        hide = cfg!(not(target_os = "macos")),
+        help = "After killing, block new connections to the ports with a pf firewall rule until interrupted (requires root)"
+    )]
+    pub block: bool,
+
+    /// Exit code given to the processes terminated on Windows.
+    #[arg(
+        long,
+        value_name = "CODE",
+        default_value_t = 1,
+        hide = cfg!(not(windows)),
+        help = "Exit code given to terminated processes (Windows only)"
+    )]
+    pub exit_code: u32,
+
+    /// Record the processes killed on Windows in the Application Event Log.
+    #[arg(
+        long,
+        hide = cfg!(not(windows)),
+        help = "Record each killed process (PID, image path, port and user) in the Application Event Log (Windows only)"
+    )]
+    pub event_log: bool,
+}
+
+impl From<&KillPortArgs> for KillOptions {
+    fn from(args: &KillPortArgs) -> Self {
+        KillOptions {
+            signal: args.signal.clone(),
+            graceful: GRACEFUL_PROCESSES
+                .iter()
+                .map(|name| name.to_string())
+                .chain(args.graceful.iter().cloned())
+                .collect(),
+            force: args.force,
+            mode: args.mode,
+            backend: args.backend,
+            dry_run: args.dry_run,
+            stop_service: args.stop_service,
+            kill_children: args.kill_children,
+            kill_parents: args.kill_parents,
+            user: args.user.clone(),
+            wait: args.wait.map(Duration::from_secs),
+            container_action: args.container_action,
+            stop_timeout: args.stop_timeout.map(Duration::from_secs),
+            internal_port: args.internal_port,
+            docker_timeout: Duration::from_millis(args.docker_timeout),
+            no_docker: args.no_docker,
+            delete_pod: args.delete_pod,
+            kill_nodes: args.kill_nodes,
+            container_labels: args
+                .container_labels
+                .iter()
+                .cloned()
+                .chain(args.project.as_ref().map(|project| LabelFilter {
+                    key: LabelFilter::COMPOSE_PROJECT.to_string(),
+                    value: Some(project.to_string()),
+                }))
+                .collect(),
+            prune_container: args.prune_container,
+            stop_pod: args.stop_pod,
+        }
+    }
+}
+
+fn parse_label_filter(arg: &str) -> Result<LabelFilter, std::io::Error> {
+    arg.parse()
 }
 
 fn parse_signal(arg: &str) -> Result<KillportSignal, std::io::Error> {
@@ -0,0 +1,48 @@
+use log::debug;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::{Mutex, PoisonError};
+
+/// Held while a question is asked, so that the ports looked up at once ask
+/// theirs one after the other.
+static ASKING: Mutex<()> = Mutex::new(());
+
+/// Asks a yes/no question on the terminal, returning true only if it is
+/// answered yes.
+///
+/// Without a terminal to ask on, e.g. in CI or with input piped in, the
+/// question is declined at once rather than waited on; `--yes` answers it
+/// ahead of time instead.
+///
+/// # Arguments
+///
+/// * `question` - The question, without the `[y/N]` suffix.
+pub fn confirm(question: &str) -> bool {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        debug!("Not asking \"{}\" as stdin is not a terminal", question);
+        return false;
+    }
+
+    let _asking = ASKING.lock().unwrap_or_else(PoisonError::into_inner);
+    eprint!("{} [y/N] ", question);
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    match stdin.lock().read_line(&mut answer) {
+        Ok(_) => is_yes(&answer),
+        Err(e) => {
+            debug!("Failed to read the answer to \"{}\": {}", question, e);
+            false
+        }
+    }
+}
+
+/// Returns true if the answer to a question is yes, e.g. "y" or "Yes".
+/// Anything else, including no answer, is no.
+///
+/// # Arguments
+///
+/// * `answer` - The answer as typed.
+pub fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
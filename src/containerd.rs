@@ -0,0 +1,269 @@
+use crate::cli::Port;
+use crate::docker::LabelFilter;
+use crate::elevate::find_in_path;
+use crate::service::run_command;
+use crate::signal::KillportSignal;
+use log::debug;
+use std::env;
+use std::io::Error;
+use std::time::Duration;
+
+/// containerd namespace holding the containers of Docker, which are found
+/// through Docker instead.
+const DOCKER_NAMESPACE: &str = "moby";
+
+/// CLI of containerd taking a namespace, found in every installation of
+/// containerd without Docker.
+const NERDCTL: &str = "nerdctl";
+
+/// CLI of Finch, which runs nerdctl in its own containerd namespace, inside
+/// a VM on macOS and Windows.
+const FINCH: &str = "finch";
+
+/// containerd namespace holding the containers of Finch.
+const FINCH_NAMESPACE: &str = "finch";
+
+/// A container run by containerd without Docker, such as those of nerdctl,
+/// k3s, Rancher Desktop and Finch.
+pub struct ContainerdContainer {
+    pub name: String,
+    /// containerd namespace of the container, e.g. "default" or "k8s.io".
+    pub namespace: String,
+    /// CLI the container is reached through, "nerdctl" or "finch".
+    pub cli: &'static str,
+}
+
+impl ContainerdContainer {
+    /// Runs a command of the container's CLI, in the container's namespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The command and its arguments, e.g. `["kill", "web"]`.
+    fn run(&self, args: &[&str]) -> Result<String, Error> {
+        let mut command = vec![];
+        // Finch only manages its own namespace, which it picks itself
+        if self.cli == NERDCTL {
+            command.extend(["--namespace", self.namespace.as_str()]);
+        }
+        command.extend(args);
+
+        run_command(self.cli, &command)
+    }
+
+    /// Kill the containerd container through its CLI.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - A enum value representing the signal type.
+    pub fn kill_container(&self, signal: KillportSignal) -> Result<(), Error> {
+        self.run(&["kill", "--signal", signal.container_signal()?, &self.name])?;
+
+        Ok(())
+    }
+
+    /// Stop the containerd container through its CLI, then remove it if
+    /// asked to.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Time given to the container to stop before it is killed,
+    ///   or nerdctl's default when `None`.
+    /// * `remove` - Remove the container once stopped.
+    pub fn stop_container(&self, timeout: Option<Duration>, remove: bool) -> Result<(), Error> {
+        let timeout = timeout.map(|timeout| timeout.as_secs().to_string());
+        let mut args = vec!["stop"];
+        if let Some(timeout) = &timeout {
+            args.extend(["--time", timeout]);
+        }
+        args.push(&self.name);
+        self.run(&args)?;
+
+        if remove {
+            self.run(&["rm", "--force", &self.name])?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the containerd container through its CLI, along with its
+    /// anonymous volumes.
+    pub fn prune_container(&self) -> Result<(), Error> {
+        self.run(&["rm", "--force", "--volumes", &self.name])?;
+
+        Ok(())
+    }
+
+    /// Finds the containerd containers publishing the specified `port`, in
+    /// every namespace but Docker's, and those of Finch when it is
+    /// installed. Finch containers run in a VM on macOS, whose forwarder
+    /// holds the port on the host.
+    ///
+    /// Returns no containers when neither nerdctl nor Finch is installed or
+    /// containerd is not reachable.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - Target port, possibly restricted to a protocol.
+    /// * `label_filters` - Filters on the labels of the containers.
+    pub fn find_target_containers(port: Port, label_filters: &[LabelFilter]) -> Vec<Self> {
+        let mut containers = vec![];
+
+        if installed(NERDCTL) {
+            match run_command(NERDCTL, &["namespace", "ls", "--quiet"]) {
+                Ok(namespaces) => {
+                    for namespace in namespaces
+                        .lines()
+                        .map(str::trim)
+                        .filter(|namespace| !namespace.is_empty() && *namespace != DOCKER_NAMESPACE)
+                    {
+                        containers.extend(Self::find_published(
+                            NERDCTL,
+                            namespace,
+                            port,
+                            label_filters,
+                        ));
+                    }
+                }
+                Err(e) => debug!("Failed to list the containerd namespaces: {}", e),
+            }
+        }
+
+        if installed(FINCH) {
+            for container in Self::find_published(FINCH, FINCH_NAMESPACE, port, label_filters) {
+                // nerdctl sees the containers of Finch when both run on the host
+                if !containers.iter().any(|found| found.name == container.name) {
+                    containers.push(container);
+                }
+            }
+        }
+
+        containers
+    }
+
+    /// Finds the containers of a containerd namespace publishing `port`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli` - The CLI listing the containers, "nerdctl" or "finch".
+    /// * `namespace` - The containerd namespace.
+    /// * `port` - Target port, possibly restricted to a protocol.
+    /// * `label_filters` - Filters on the labels of the containers.
+    fn find_published(
+        cli: &'static str,
+        namespace: &str,
+        port: Port,
+        label_filters: &[LabelFilter],
+    ) -> Vec<Self> {
+        let filters: Vec<String> = label_filters
+            .iter()
+            .map(|filter| format!("label={}", filter))
+            .collect();
+
+        let mut args = vec!["ps"];
+        for filter in &filters {
+            args.extend(["--filter", filter]);
+        }
+        args.extend(["--format", "{{.Names}}\t{{.Ports}}"]);
+
+        let lister = ContainerdContainer {
+            name: String::new(),
+            namespace: namespace.to_string(),
+            cli,
+        };
+        let output = match lister.run(&args) {
+            Ok(output) => output,
+            Err(e) => {
+                debug!(
+                    "Failed to list the containers of containerd namespace {}: {}",
+                    namespace, e
+                );
+                return vec![];
+            }
+        };
+
+        parse_published_containers(&output, port)
+            .into_iter()
+            .map(|name| ContainerdContainer {
+                name,
+                namespace: namespace.to_string(),
+                cli,
+            })
+            .collect()
+    }
+}
+
+/// Returns true if the CLI `cli` is on `PATH`.
+///
+/// # Arguments
+///
+/// * `cli` - The CLI, "nerdctl" or "finch".
+fn installed(cli: &str) -> bool {
+    find_in_path(&format!("{}{}", cli, env::consts::EXE_SUFFIX)).is_some()
+}
+
+/// Finds the containers run by containerd without Docker, so that fake
+/// containers can stand in for them, e.g. in tests.
+pub trait ContainerdClient {
+    /// Finds the containers publishing `port` that match `label_filters`, see
+    /// [`ContainerdContainer::find_target_containers`].
+    fn find_target_containers(
+        &self,
+        port: Port,
+        label_filters: &[LabelFilter],
+    ) -> Vec<ContainerdContainer>;
+}
+
+/// The containers of containerd on this system, reached through nerdctl and
+/// Finch.
+pub struct ContainerdCli;
+
+impl ContainerdClient for ContainerdCli {
+    fn find_target_containers(
+        &self,
+        port: Port,
+        label_filters: &[LabelFilter],
+    ) -> Vec<ContainerdContainer> {
+        ContainerdContainer::find_target_containers(port, label_filters)
+    }
+}
+
+/// Returns the names of the containers publishing `port`, from the output of
+/// `nerdctl ps --format '{{.Names}}\t{{.Ports}}'`.
+///
+/// Published ports are listed like `0.0.0.0:8080->80/tcp` or, for ranges,
+/// `127.0.0.1:8080-8081->80-81/tcp`.
+///
+/// # Arguments
+///
+/// * `output` - The output of `nerdctl ps`.
+/// * `port` - Target port, possibly restricted to a protocol.
+pub fn parse_published_containers(output: &str, port: Port) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, ports) = line.split_once('\t')?;
+            ports
+                .split(", ")
+                .filter_map(|mapping| {
+                    let (host, container) = mapping.split_once("->")?;
+                    let protocol = container
+                        .rsplit_once('/')
+                        .map_or("tcp", |(_, protocol)| protocol);
+                    let carried = protocol
+                        .parse()
+                        .is_ok_and(|protocol| port.carries(protocol));
+                    host.rsplit_once(':').filter(|_| carried)
+                })
+                .any(|(_, host_ports)| {
+                    let (first, last) = host_ports
+                        .split_once('-')
+                        .unwrap_or((host_ports, host_ports));
+                    matches!(
+                        (first.parse::<u16>(), last.parse::<u16>()),
+                        (Ok(first), Ok(last)) if (first..=last).contains(&port.number())
+                    )
+                })
+                .then(|| name.trim().to_string())
+        })
+        .collect()
+}
@@ -1,92 +1,1576 @@
+use crate::cli;
+use crate::docker_cli::DockerCli;
+use crate::elevate::find_in_path;
+use crate::killport::Diagnostics;
+use crate::kubernetes::ClusterNode;
 use crate::signal::KillportSignal;
-use bollard::container::{KillContainerOptions, ListContainersOptions};
-use bollard::Docker;
-use log::debug;
+use crate::socket::Protocol;
+use bollard::container::{
+    KillContainerOptions, ListContainersOptions, RemoveContainerOptions, StopContainerOptions,
+};
+use bollard::network::DisconnectNetworkOptions;
+use bollard::service::MountPointTypeEnum;
+use bollard::service::{ContainerInspectResponse, ContainerSummary, Port};
+use bollard::{Docker, API_DEFAULT_VERSION};
+use log::{debug, info, warn};
 use std::collections::HashMap;
-use std::io::Error;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::Duration;
 use tokio::runtime::Runtime;
 
+/// Timeout in seconds for requests to a container runtime socket.
+const SOCKET_TIMEOUT: u64 = 120;
+
+/// Runtime shared by the container runtime requests of every port.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Running containers listed per container runtime socket, shared by the
+/// lookups of every port until a container is acted on.
+static CONTAINER_LISTINGS: Mutex<Vec<(Option<PathBuf>, Vec<ContainerSummary>)>> =
+    Mutex::new(Vec::new());
+
+/// Clients per container runtime socket, whose API version was negotiated
+/// with the daemon.
+static CLIENTS: Mutex<Vec<(Option<PathBuf>, Client)>> = Mutex::new(Vec::new());
+
+/// TLS files given on the command line for the Docker host.
+static TLS_FILES: Mutex<TlsFiles> = Mutex::new(TlsFiles {
+    ca: None,
+    cert: None,
+    key: None,
+});
+
+/// Installation directory of Finch on macOS.
+const FINCH_DIRECTORY: &str = "/Applications/Finch";
+
+/// Docker API sockets of finch-daemon in the Finch VM, forwarded to the host.
+const FINCH_SOCKETS: [&str; 1] = ["/Applications/Finch/lima/data/finch/sock/finch.sock"];
+
+/// Socket the Docker daemon listens on by default.
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// Docker sockets that Colima, Rancher Desktop, OrbStack and Docker Desktop
+/// create in the home directory, in the order they are tried.
+const HOME_SOCKETS: [&str; 5] = [
+    ".colima/default/docker.sock",
+    ".colima/docker.sock",
+    ".rd/docker.sock",
+    ".orbstack/run/docker.sock",
+    ".docker/run/docker.sock",
+];
+
 pub struct DockerContainer {
     pub name: String,
+    /// Full ID of the container, which it is acted on by.
+    pub id: String,
+    /// Image the container runs.
+    pub image: String,
+    /// Mappings of the target port, e.g. "0.0.0.0:8080->80/tcp".
+    pub ports: Vec<String>,
+    /// Labels of the container.
+    pub labels: HashMap<String, String>,
+    /// Kubernetes cluster node the container runs, if any.
+    pub node: Option<ClusterNode>,
+    /// Socket of the container runtime the container was found through, or
+    /// `None` for the default Docker host.
+    pub socket: Option<PathBuf>,
+}
+
+/// Resources of a container cleaned up along with it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PrunedResources {
+    /// Anonymous volumes removed with the container.
+    pub volumes: Vec<String>,
+    /// Networks the container's leftover endpoints were disconnected from.
+    pub networks: Vec<String>,
+}
+
+/// A filter on the labels of containers, given as `key` for containers that
+/// have the label or `key=value` for those where it has that value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl LabelFilter {
+    /// Label Docker Compose gives the containers of a project.
+    pub const COMPOSE_PROJECT: &'static str = "com.docker.compose.project";
+
+    /// Returns true if the labels of a container pass the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - The labels of the container.
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match (labels.get(&self.key), &self.value) {
+            (Some(value), Some(expected)) => value == expected,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+impl FromStr for LabelFilter {
+    type Err = Error;
+
+    fn from_str(filter: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match filter.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (filter, None),
+        };
+        if key.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid label filter {}, use key=value or key", filter),
+            ));
+        }
+
+        Ok(LabelFilter {
+            key: key.to_string(),
+            value,
+        })
+    }
+}
+
+impl fmt::Display for LabelFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.key, value),
+            None => f.write_str(&self.key),
+        }
+    }
+}
+
+/// A running container sharing the network of the host.
+pub struct HostNetworkContainer {
+    pub container: DockerContainer,
+    /// Whether the container declares the target port as exposed.
+    pub exposes_port: bool,
+}
+
+/// A VM running containers, whose published ports are forwarded to the host
+/// by a helper process rather than bound by the containers themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmForwarder {
+    /// Name of the product running the VM, e.g. "Docker Desktop".
+    pub runtime: &'static str,
+    /// Container runtime sockets of the VM, empty for the default Docker host.
+    pub sockets: Vec<PathBuf>,
+}
+
+/// A process standing in for a container on the host, found through its
+/// command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContainerProxy {
+    /// A docker-proxy forwarding a published port to a container address.
+    Forward {
+        container_ip: String,
+        container_port: u16,
+    },
+    /// A containerd shim running the container with this ID.
+    Shim { id: String },
+}
+
+impl fmt::Display for ContainerProxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerProxy::Forward {
+                container_ip,
+                container_port,
+            } if container_ip.contains(':') => {
+                write!(f, "docker-proxy to [{}]:{}", container_ip, container_port)
+            }
+            ContainerProxy::Forward {
+                container_ip,
+                container_port,
+            } => write!(f, "docker-proxy to {}:{}", container_ip, container_port),
+            ContainerProxy::Shim { id } => {
+                write!(f, "containerd shim of {}", &id[..id.len().min(12)])
+            }
+        }
+    }
+}
+
+/// Recognizes the command line of a docker-proxy, e.g. `docker-proxy -proto
+/// tcp -host-ip 0.0.0.0 -host-port 8080 -container-ip 172.17.0.2
+/// -container-port 80`, or of a containerd shim, e.g.
+/// `containerd-shim-runc-v2 -namespace moby -id <ID> -address ...`.
+///
+/// # Arguments
+///
+/// * `args` - The command line of the process.
+pub fn parse_container_proxy(args: &[String]) -> Option<ContainerProxy> {
+    let program = args.first()?.rsplit('/').next()?;
+
+    // Flags are given as -flag value, or as -flag=value or with two dashes
+    let flag = |name: &str| {
+        let mut rest = args.iter().skip(1);
+        while let Some(arg) = rest.next() {
+            let arg = arg.trim_start_matches('-');
+            match arg.split_once('=') {
+                Some((flag, value)) if flag == name => return Some(value.to_string()),
+                None if arg == name => return rest.next().cloned(),
+                _ => {}
+            }
+        }
+        None
+    };
+
+    if program == "docker-proxy" {
+        return Some(ContainerProxy::Forward {
+            container_ip: flag("container-ip")?,
+            container_port: flag("container-port")?.parse().ok()?,
+        });
+    }
+    if program.starts_with("containerd-shim") {
+        return Some(ContainerProxy::Shim { id: flag("id")? });
+    }
+
+    None
+}
+
+/// Files securing the connection to a Docker host with mutual TLS, like the
+/// `--tlscacert`, `--tlscert` and `--tlskey` options of the docker CLI.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsFiles {
+    /// Certificate authority the daemon's certificate is checked against.
+    pub ca: Option<PathBuf>,
+    /// Client certificate.
+    pub cert: Option<PathBuf>,
+    /// Key of the client certificate.
+    pub key: Option<PathBuf>,
+}
+
+impl TlsFiles {
+    /// Returns true if no file was given.
+    pub fn is_empty(&self) -> bool {
+        self.ca.is_none() && self.cert.is_none() && self.key.is_none()
+    }
+
+    /// Returns the certificate authority, client certificate and key, taking
+    /// those not given from `ca.pem`, `cert.pem` and `key.pem` in
+    /// `cert_path`, as the docker CLI does.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_path` - The directory holding the files, e.g. `DOCKER_CERT_PATH`.
+    pub fn resolve(&self, cert_path: &Path) -> (PathBuf, PathBuf, PathBuf) {
+        let file = |file: &Option<PathBuf>, default: &str| {
+            file.clone().unwrap_or_else(|| cert_path.join(default))
+        };
+
+        (
+            file(&self.ca, "ca.pem"),
+            file(&self.cert, "cert.pem"),
+            file(&self.key, "key.pem"),
+        )
+    }
+}
+
+/// Sets the TLS files used to reach a Docker host over TCP. Giving any of
+/// them secures the connection even without `DOCKER_TLS_VERIFY`.
+///
+/// # Arguments
+///
+/// * `files` - The files given on the command line.
+pub fn set_tls_files(files: TlsFiles) {
+    *lock(&TLS_FILES) = files;
+}
+
+/// A connection to a Docker daemon.
+#[derive(Clone)]
+enum Client {
+    /// Through the Docker API.
+    Api(Docker),
+    /// Through the docker CLI, for daemons the Docker API client cannot
+    /// reach, such as those of an ssh context.
+    Cli(DockerCli),
+}
+
+impl Client {
+    /// Returns the version of the daemon.
+    async fn version(&self) -> Result<String, Error> {
+        match self {
+            Client::Api(docker) => Ok(docker
+                .version()
+                .await
+                .map_err(docker_error)?
+                .version
+                .unwrap_or_default()),
+            Client::Cli(cli) => blocking(cli, |cli| cli.version()).await,
+        }
+    }
+
+    /// Returns the operating system of the daemon's containers, e.g. "linux".
+    async fn os_type(&self) -> Result<String, Error> {
+        match self {
+            Client::Api(docker) => Ok(docker
+                .version()
+                .await
+                .map_err(docker_error)?
+                .os
+                .unwrap_or_default()),
+            Client::Cli(cli) => blocking(cli, |cli| cli.os_type()).await,
+        }
+    }
+
+    /// Lists the running containers of the daemon.
+    async fn list_running(&self) -> Result<Vec<ContainerSummary>, Error> {
+        match self {
+            Client::Api(docker) => {
+                let mut filters = HashMap::new();
+                filters.insert("status".to_string(), vec!["running".to_string()]);
+
+                let options = ListContainersOptions {
+                    filters,
+                    ..Default::default()
+                };
+
+                docker
+                    .list_containers::<String>(Some(options))
+                    .await
+                    .map_err(docker_error)
+            }
+            Client::Cli(cli) => blocking(cli, |cli| cli.list_running()).await,
+        }
+    }
+
+    /// Inspects a container.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    async fn inspect(&self, name: &str) -> Result<ContainerInspectResponse, Error> {
+        match self {
+            Client::Api(docker) => docker
+                .inspect_container(name, None)
+                .await
+                .map_err(docker_error),
+            Client::Cli(cli) => {
+                let name = name.to_string();
+                blocking(cli, move |cli| cli.inspect(&name)).await
+            }
+        }
+    }
+
+    /// Sends a signal to a container. Returns false if the container is no
+    /// longer running.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `signal` - The signal name, e.g. "SIGTERM".
+    async fn kill(&self, name: &str, signal: String) -> Result<bool, Error> {
+        match self {
+            Client::Api(docker) => {
+                let options = KillContainerOptions { signal };
+
+                match docker.kill_container(name, Some(options)).await {
+                    Ok(()) => Ok(true),
+                    Err(bollard::errors::Error::DockerResponseServerError {
+                        status_code: 404 | 409,
+                        message,
+                    }) => {
+                        info!("Container '{}' is no longer running: {}", name, message);
+                        Ok(false)
+                    }
+                    Err(e) => Err(Error::other(e.to_string())),
+                }
+            }
+            Client::Cli(cli) => {
+                let name = name.to_string();
+                blocking(cli, move |cli| cli.kill(&name, &signal)).await
+            }
+        }
+    }
+
+    /// Stops a container.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `timeout` - Time given to the container to stop before it is killed,
+    ///   or the container's own stop timeout when `None`.
+    async fn stop(&self, name: &str, timeout: Option<Duration>) -> Result<(), Error> {
+        match self {
+            Client::Api(docker) => {
+                let options = timeout.map(|timeout| StopContainerOptions {
+                    t: timeout.as_secs() as i64,
+                });
+                docker
+                    .stop_container(name, options)
+                    .await
+                    .map_err(docker_error)
+            }
+            Client::Cli(cli) => {
+                let name = name.to_string();
+                blocking(cli, move |cli| cli.stop(&name, timeout)).await
+            }
+        }
+    }
+
+    /// Removes a container, even a running one.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `volumes` - Also remove the anonymous volumes of the container.
+    async fn remove(&self, name: &str, volumes: bool) -> Result<(), Error> {
+        match self {
+            Client::Api(docker) => {
+                let options = RemoveContainerOptions {
+                    v: volumes,
+                    force: true,
+                    ..Default::default()
+                };
+                docker
+                    .remove_container(name, Some(options))
+                    .await
+                    .map_err(docker_error)
+            }
+            Client::Cli(cli) => {
+                let name = name.to_string();
+                blocking(cli, move |cli| cli.remove(&name, volumes)).await
+            }
+        }
+    }
+
+    /// Disconnects a container from a network, even when the container is
+    /// gone and only its endpoint is left.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - The network name.
+    /// * `name` - A container name or ID.
+    async fn disconnect(&self, network: &str, name: &str) -> Result<(), Error> {
+        match self {
+            Client::Api(docker) => {
+                let options = DisconnectNetworkOptions {
+                    container: name,
+                    force: true,
+                };
+                docker
+                    .disconnect_network(network, options)
+                    .await
+                    .map_err(docker_error)
+            }
+            Client::Cli(cli) => {
+                let (network, name) = (network.to_string(), name.to_string());
+                blocking(cli, move |cli| cli.disconnect(&network, &name)).await
+            }
+        }
+    }
+}
+
+/// Runs a docker CLI command on the blocking threads of the runtime, so
+/// that the requests of other ports go on meanwhile.
+///
+/// # Arguments
+///
+/// * `cli` - The docker CLI.
+/// * `command` - Runs the command.
+async fn blocking<T, F>(cli: &DockerCli, command: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(DockerCli) -> Result<T, Error> + Send + 'static,
+{
+    let cli = cli.clone();
+    tokio::task::spawn_blocking(move || command(cli))
+        .await
+        .map_err(Error::other)?
 }
 
 impl DockerContainer {
+    /// Creates a container from its entry in a container listing, keeping
+    /// the port mappings it was matched by: those publishing `port` on the
+    /// host, or with `internal` set, those of `port` inside the container.
+    ///
+    /// # Arguments
+    ///
+    /// * `container` - The listed container.
+    /// * `port` - Target port number.
+    /// * `internal` - Whether the container was matched by its internal port.
+    /// * `socket` - The container runtime socket, or `None` for the default Docker host.
+    fn from_summary(
+        container: ContainerSummary,
+        port: u16,
+        internal: bool,
+        socket: Option<&PathBuf>,
+    ) -> Option<Self> {
+        let name = container.names?.into_iter().next()?;
+
+        let node_port = container
+            .ports
+            .iter()
+            .flatten()
+            .find(|mapping| mapping.public_port == Some(port))
+            .map(|mapping| mapping.private_port);
+        let labels = container.labels.unwrap_or_default();
+        let node = ClusterNode::from_labels(&labels, node_port);
+
+        let mut ports: Vec<String> = vec![];
+        for mapping in container.ports.iter().flatten() {
+            let mapping_port = format_port_mapping(mapping);
+            let matched = if internal {
+                mapping.private_port == port
+            } else {
+                mapping.public_port == Some(port)
+            };
+            if matched && !ports.contains(&mapping_port) {
+                ports.push(mapping_port);
+            }
+        }
+
+        Some(DockerContainer {
+            name: name.strip_prefix('/').unwrap_or(&name).to_string(),
+            id: container.id?,
+            image: container.image.unwrap_or_default(),
+            ports,
+            labels,
+            node,
+            socket: socket.cloned(),
+        })
+    }
+
+    /// Describes the container by name, short ID, image, port mappings and
+    /// the cluster it is a node of, e.g. "web-1 (3f4e1c5a9b7d, nginx:1.25, 0.0.0.0:8080->80/tcp)".
+    pub fn describe(&self) -> String {
+        let short_id = &self.id[..self.id.len().min(12)];
+        let node = self.node.as_ref().map(|node| node.to_string());
+        let details: Vec<&str> = [short_id, self.image.as_str()]
+            .into_iter()
+            .filter(|detail| !detail.is_empty())
+            .chain(self.ports.iter().map(String::as_str))
+            .chain(node.as_deref())
+            .collect();
+
+        if details.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{} ({})", self.name, details.join(", "))
+        }
+    }
+
     /// Kill the docker container.
     ///
+    /// Windows containers only take SIGKILL, or SIGTERM to shut them down
+    /// gracefully, so other signals are sent as SIGTERM to a Windows engine.
+    ///
+    /// Returns false if the container is no longer running, e.g. because it
+    /// was killed for another port already.
+    ///
     /// # Arguments
     ///
-    /// * `name` - A container name.
+    /// * `name` - A container name or ID.
+    /// * `socket` - The container runtime socket, or `None` for the default Docker host.
     /// * `signal` - A enum value representing the signal type.
-    pub fn kill_container(name: &str, signal: KillportSignal) -> Result<(), Error> {
-        let rt = Runtime::new()?;
-        rt.block_on(async {
-            let docker = Docker::connect_with_socket_defaults()
-                .map_err(|e| Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-
-            let options = KillContainerOptions {
-                signal: signal.to_string(),
+    pub fn kill_container(
+        name: &str,
+        socket: Option<&PathBuf>,
+        signal: KillportSignal,
+    ) -> Result<bool, Error> {
+        runtime()?.block_on(async {
+            let client = Self::client(socket).await?;
+
+            let mut signal = signal.container_signal()?.to_string();
+            if signal != "SIGKILL" {
+                let os_type = client.os_type().await?;
+                let engine_signal = Self::engine_signal(&signal, &os_type);
+                if engine_signal != signal {
+                    info!(
+                        "Sending {} to Windows container '{}' instead of {}",
+                        engine_signal, name, signal
+                    );
+                    signal = engine_signal.to_string();
+                }
+            }
+
+            let killed = client.kill(name, signal).await?;
+            if killed {
+                forget_listings();
+            }
+            Ok(killed)
+        })
+    }
+
+    /// Stop the docker container gracefully, then remove it if asked to.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `socket` - The container runtime socket, or `None` for the default Docker host.
+    /// * `timeout` - Time given to the container to stop before it is killed,
+    ///   or the container's own stop timeout when `None`.
+    /// * `remove` - Remove the container once stopped.
+    pub fn stop_container(
+        name: &str,
+        socket: Option<&PathBuf>,
+        timeout: Option<Duration>,
+        remove: bool,
+    ) -> Result<(), Error> {
+        runtime()?.block_on(async {
+            let client = Self::client(socket).await?;
+
+            client.stop(name, timeout).await?;
+            forget_listings();
+
+            if remove {
+                info!("Removing container '{}'", name);
+                client.remove(name, false).await?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Remove the docker container along with its anonymous volumes, then
+    /// disconnect any endpoint it left behind on its networks.
+    ///
+    /// Named volumes are kept, as other containers may use them.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `socket` - The container runtime socket, or `None` for the default Docker host.
+    pub fn prune_container(name: &str, socket: Option<&PathBuf>) -> Result<PrunedResources, Error> {
+        runtime()?.block_on(async {
+            let client = Self::client(socket).await?;
+
+            let inspection = client.inspect(name).await?;
+            let volumes: Vec<String> = inspection
+                .mounts
+                .into_iter()
+                .flatten()
+                .filter(|mount| mount.typ == Some(MountPointTypeEnum::VOLUME))
+                .filter_map(|mount| mount.name)
+                .filter(|volume| is_anonymous_volume(volume))
+                .collect();
+            let networks: Vec<String> = inspection
+                .network_settings
+                .and_then(|settings| settings.networks)
+                .map(|networks| networks.into_keys().collect())
+                .unwrap_or_default();
+
+            info!("Removing container '{}' and its anonymous volumes", name);
+            client.remove(name, true).await?;
+            forget_listings();
+
+            // Removing the container normally removes its endpoints, which
+            // are only left dangling when the daemon lost track of them
+            let mut pruned = PrunedResources {
+                volumes,
+                networks: vec![],
             };
+            for network in networks {
+                match client.disconnect(&network, name).await {
+                    Ok(()) => pruned.networks.push(network),
+                    Err(e) => debug!(
+                        "No endpoint of container '{}' left on network {}: {}",
+                        name, network, e
+                    ),
+                }
+            }
 
-            docker
-                .kill_container(name, Some(options))
-                .await
-                .map_err(|e| Error::new(std::io::ErrorKind::Other, e.to_string()))
+            Ok(pruned)
         })
     }
 
+    /// Returns the signal to send to a container for `signal` on an engine
+    /// running containers of `os_type`, e.g. "linux" or "windows".
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - The requested signal name, e.g. "SIGINT".
+    /// * `os_type` - The operating system of the engine's containers.
+    pub fn engine_signal<'a>(signal: &'a str, os_type: &str) -> &'a str {
+        match (os_type.eq_ignore_ascii_case("windows"), signal) {
+            (false, _) | (true, "SIGKILL") => signal,
+            (true, _) => "SIGTERM",
+        }
+    }
+
     /// Finds the Docker containers associated with the specified `port`.
-    pub fn find_target_containers(port: u16) -> Result<Vec<Self>, Error> {
-        let rt = Runtime::new()?;
-        rt.block_on(async {
-            let docker = Docker::connect_with_socket_defaults()
-                .map_err(|e| Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-
-            let mut filters = HashMap::new();
-            filters.insert("publish".to_string(), vec![port.to_string()]);
-            filters.insert("status".to_string(), vec!["running".to_string()]);
-
-            let options = ListContainersOptions {
-                filters,
-                ..Default::default()
-            };
+    pub fn find_target_containers(port: cli::Port) -> Result<Vec<Self>, Error> {
+        Self::find_target_containers_at(port, None)
+    }
 
-            let containers = docker
-                .list_containers::<String>(Some(options))
-                .await
-                .map_err(|e| Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    /// Finds the containers associated with the specified `port` through the
+    /// given container runtime socket.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - Target port, possibly restricted to a protocol.
+    /// * `socket` - The container runtime socket, or `None` for the default Docker host.
+    pub fn find_target_containers_at(
+        port: cli::Port,
+        socket: Option<&PathBuf>,
+    ) -> Result<Vec<Self>, Error> {
+        runtime()?.block_on(async {
+            let client = Self::client(socket).await?;
+            let containers = running_containers(&client, socket).await?;
 
             Ok(containers
-                .iter()
+                .into_iter()
+                .filter(|container| {
+                    container.ports.iter().flatten().any(|mapping| {
+                        mapping.public_port == Some(port.number()) && maps_protocol(mapping, port)
+                    })
+                })
                 .filter_map(|container| {
-                    container
-                        .names
-                        .as_ref()?
-                        .first()
-                        .map(|name| DockerContainer {
-                            name: name.strip_prefix('/').unwrap_or(name).to_string(),
-                        })
+                    let container = Self::from_summary(container, port.number(), false, socket)?;
+                    debug!(
+                        "Found container {} publishing port {} as {}",
+                        container.name,
+                        port,
+                        container.ports.join(", ")
+                    );
+                    Some(container)
                 })
                 .collect())
         })
     }
 
-    pub fn is_docker_present() -> Result<bool, Error> {
-        let rt = Runtime::new()?;
-        rt.block_on(async {
-            let docker = Docker::connect_with_socket_defaults()
-                .map_err(|e| Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    /// Finds the running Docker container a docker-proxy forwards `port` to,
+    /// by the address it forwards to, or the one a containerd shim runs, by
+    /// its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `proxy` - The proxy found on the port.
+    /// * `port` - Target port, possibly restricted to a protocol.
+    pub fn find_proxied_container(
+        proxy: &ContainerProxy,
+        port: cli::Port,
+    ) -> Result<Option<Self>, Error> {
+        runtime()?.block_on(async {
+            let client = Self::client(None).await?;
+            let containers = running_containers(&client, None).await?;
 
-            // Attempt to get the Docker version as a test of connectivity.
-            match docker.version().await {
-                Ok(version) => {
-                    debug!("Connected to Docker version: {:?}", version);
-                    Ok(true)
+            Ok(containers
+                .into_iter()
+                .find(|container| match proxy {
+                    ContainerProxy::Forward {
+                        container_ip,
+                        container_port,
+                    } => {
+                        let has_address = container
+                            .network_settings
+                            .iter()
+                            .flat_map(|settings| settings.networks.iter().flatten())
+                            .any(|(_, endpoint)| {
+                                endpoint.ip_address.as_ref() == Some(container_ip)
+                                    || endpoint.global_ipv6_address.as_ref() == Some(container_ip)
+                            });
+                        has_address
+                            && container.ports.iter().flatten().any(|mapping| {
+                                mapping.private_port == *container_port
+                                    && mapping.public_port == Some(port.number())
+                                    && maps_protocol(mapping, port)
+                            })
+                    }
+                    ContainerProxy::Shim { id } => container.id.as_ref() == Some(id),
+                })
+                .and_then(|container| Self::from_summary(container, port.number(), false, None)))
+        })
+    }
+
+    /// Finds the running Docker containers listening on `port` inside their
+    /// own network, whether or not the port is published on the host.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - Target port, checked against the container ports.
+    pub fn find_containers_by_internal_port(port: cli::Port) -> Result<Vec<Self>, Error> {
+        runtime()?.block_on(async {
+            let client = Self::client(None).await?;
+            let containers = running_containers(&client, None).await?;
+
+            Ok(containers
+                .into_iter()
+                .filter(|container| {
+                    container.ports.iter().flatten().any(|mapping| {
+                        mapping.private_port == port.number() && maps_protocol(mapping, port)
+                    })
+                })
+                .filter_map(|container| {
+                    let container = Self::from_summary(container, port.number(), true, None)?;
+                    debug!(
+                        "Found container {} listening internally on port {}",
+                        container.name, port
+                    );
+                    Some(container)
+                })
+                .collect())
+        })
+    }
+
+    /// Finds the running Docker containers on the host network, whose
+    /// sockets are bound by host processes rather than published.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - Target port, checked against the exposed ports.
+    pub fn find_host_network_containers(
+        port: cli::Port,
+    ) -> Result<Vec<HostNetworkContainer>, Error> {
+        runtime()?.block_on(async {
+            let client = Self::client(None).await?;
+            let containers = running_containers(&client, None).await?;
+
+            let mut host_network_containers = vec![];
+            for container in containers.into_iter().filter(|container| {
+                container
+                    .host_config
+                    .as_ref()
+                    .and_then(|host_config| host_config.network_mode.as_deref())
+                    == Some("host")
+            }) {
+                let Some(mut container) = Self::from_summary(container, port.number(), false, None)
+                else {
+                    continue;
+                };
+                container.ports = vec!["host network".to_string()];
+
+                let exposed_ports = client
+                    .inspect(&container.id)
+                    .await?
+                    .config
+                    .and_then(|config| config.exposed_ports)
+                    .unwrap_or_default();
+                let exposes_port = exposed_ports.keys().any(|exposed| {
+                    let (number, protocol) = exposed.split_once('/').unwrap_or((exposed, "tcp"));
+                    number == port.to_string()
+                        && protocol
+                            .parse()
+                            .is_ok_and(|protocol| port.carries(protocol))
+                });
+
+                debug!(
+                    "Found container {} on the host network, exposing {:?}",
+                    container.name,
+                    exposed_ports.keys()
+                );
+                host_network_containers.push(HostNetworkContainer {
+                    container,
+                    exposes_port,
+                });
+            }
+
+            Ok(host_network_containers)
+        })
+    }
+
+    /// Returns the VM whose published container ports the process forwards
+    /// to the host, if any.
+    ///
+    /// Docker Desktop, OrbStack, colima, Finch and lima run containers inside
+    /// a VM, so the host process bound to a published port is the VM's
+    /// forwarder rather than the container, and killing it takes down every
+    /// container.
+    ///
+    /// # Arguments
+    ///
+    /// * `process_name` - The name of the process bound to the port.
+    pub fn find_vm_forwarder(process_name: &str) -> Option<VmForwarder> {
+        let process_name = process_name.to_lowercase();
+        let home = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+
+        if ["com.docker.backend", "com.docker.vpnkit", "vpnkit"]
+            .iter()
+            .any(|forwarder| process_name.starts_with(forwarder))
+        {
+            return Some(VmForwarder {
+                runtime: "Docker Desktop",
+                sockets: vec![],
+            });
+        }
+
+        if process_name.contains("orbstack") {
+            return Some(VmForwarder {
+                runtime: "OrbStack",
+                sockets: vec![home.join(".orbstack/run/docker.sock")],
+            });
+        }
+
+        // colima and Finch run their VMs through lima, whose host agent
+        // forwards the ports. Finch's containers are reached through the
+        // Docker API of finch-daemon when it runs, else through its CLI
+        if process_name.starts_with("limactl") {
+            let colima = instance_sockets(&home.join(".colima"), "docker.sock");
+            let finch: Vec<PathBuf> = FINCH_SOCKETS
+                .iter()
+                .map(PathBuf::from)
+                .filter(|socket| socket.exists())
+                .collect();
+            let lima = instance_sockets(&home.join(".lima"), "sock/docker.sock");
+            let runtime = if !colima.is_empty() {
+                "colima"
+            } else if !finch.is_empty() || Path::new(FINCH_DIRECTORY).exists() {
+                "Finch"
+            } else {
+                "lima"
+            };
+            return Some(VmForwarder {
+                runtime,
+                sockets: colima.into_iter().chain(finch).chain(lima).collect(),
+            });
+        }
+
+        None
+    }
+
+    /// Connects to the given container runtime socket, or to the default
+    /// Docker host the docker CLI would use, see [`configured_host`]. Without
+    /// a configured host or default socket, the sockets of other container
+    /// runtimes are tried, see [`discover_socket`].
+    ///
+    /// A host secured with TLS is reached over TLS, see [`tls_client`].
+    ///
+    /// # Arguments
+    ///
+    /// * `socket` - The container runtime socket, or `None` for the default Docker host.
+    fn connect(socket: Option<&PathBuf>) -> Result<Client, Error> {
+        let host = match socket {
+            Some(socket) => Some(socket.to_string_lossy().into_owned()),
+            None => configured_host().or_else(|| {
+                let home = env::var_os("HOME")?;
+                discover_socket(Path::new(&home), Path::new(DEFAULT_SOCKET))
+                    .map(|socket| format!("unix://{}", socket.display()))
+            }),
+        };
+
+        match host {
+            Some(host) if host.starts_with("tcp://") || host.starts_with("http://") => {
+                if let Some(client) = tls_client(&host)? {
+                    return Ok(client);
                 }
-                Err(e) => {
-                    debug!("Failed to connect to Docker: {}", e);
-                    Ok(false)
+                Docker::connect_with_http(&host, SOCKET_TIMEOUT, API_DEFAULT_VERSION)
+            }
+            Some(host) if host.contains("://") && !is_local_host(&host) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Docker host {} is not supported", host),
+                ))
+            }
+            Some(host) => Docker::connect_with_socket(&host, SOCKET_TIMEOUT, API_DEFAULT_VERSION),
+            None => Docker::connect_with_local_defaults(),
+        }
+        .map(Client::Api)
+        .map_err(docker_error)
+    }
+
+    /// Returns a client of the given container runtime socket, or of the
+    /// default Docker host, speaking the newest API version both sides
+    /// support. The default Docker host falls back on the docker CLI when
+    /// the Docker API cannot reach it, see [`cli_fallback`].
+    ///
+    /// # Arguments
+    ///
+    /// * `socket` - The container runtime socket, or `None` for the default Docker host.
+    async fn client(socket: Option<&PathBuf>) -> Result<Client, Error> {
+        if let Some((_, client)) = lock(&CLIENTS)
+            .iter()
+            .find(|(client_socket, _)| client_socket.as_ref() == socket)
+        {
+            return Ok(client.clone());
+        }
+
+        let connected =
+            match Self::connect(socket) {
+                Ok(Client::Api(docker)) => docker
+                    .negotiate_version()
+                    .await
+                    .map_err(docker_error)
+                    .map(|docker| {
+                        debug!("Using Docker API version {}", docker.client_version());
+                        Client::Api(docker)
+                    }),
+                connected => connected,
+            };
+        let client = match connected {
+            Ok(client) => client,
+            Err(e) => match (socket.is_none() && !uses_tls())
+                .then(|| cli_fallback(&e))
+                .flatten()
+            {
+                Some(client) => client,
+                None => return Err(e),
+            },
+        };
+
+        lock(&CLIENTS).push((socket.cloned(), client.clone()));
+        Ok(client)
+    }
+
+    /// Returns true if the Docker daemon answers within `timeout`.
+    ///
+    /// A daemon that does not answer in time, e.g. because it is wedged or
+    /// `DOCKER_HOST` points at an unreachable host, is treated as absent so
+    /// that processes can still be killed. A daemon the Docker API cannot
+    /// reach is asked through the docker CLI, see [`cli_fallback`], unless
+    /// it is secured with TLS, see [`uses_tls`].
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Time given to the daemon to answer.
+    /// * `diagnostics` - Collects the reason the daemon could not be reached.
+    pub fn is_docker_present(
+        timeout: Duration,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<bool, Error> {
+        runtime()?.block_on(async {
+            let tls = uses_tls();
+            let mut fallback = false;
+            let mut client = match Self::connect(None) {
+                Ok(client) => client,
+                Err(e) => match (!tls).then(|| cli_fallback(&e)).flatten() {
+                    Some(client) => {
+                        fallback = true;
+                        client
+                    }
+                    None if matches!(e.kind(), ErrorKind::Unsupported | ErrorKind::NotFound) => {
+                        warn!("Containers will not be searched: {}", e);
+                        diagnostics
+                            .unsearched
+                            .push(format!("Docker containers: {}", e));
+                        return Ok(false);
+                    }
+                    None => return Err(e),
+                },
+            };
+
+            // Attempt to get the Docker version as a test of connectivity.
+            loop {
+                match tokio::time::timeout(timeout, client.version()).await {
+                    Ok(Ok(version)) => {
+                        debug!("Connected to Docker version {}", version);
+                        if fallback {
+                            lock(&CLIENTS).push((None, client));
+                        }
+                        return Ok(true);
+                    }
+                    Ok(Err(e)) => {
+                        if let Some(cli) = (!tls && matches!(client, Client::Api(_)))
+                            .then(|| cli_fallback(&e))
+                            .flatten()
+                        {
+                            fallback = true;
+                            client = cli;
+                            continue;
+                        }
+
+                        // A daemon secured with TLS was set up explicitly, so
+                        // failing to reach it is worth a warning
+                        if e.kind() == ErrorKind::PermissionDenied || tls {
+                            warn!("Containers will not be searched: {}", e);
+                        } else {
+                            debug!("Failed to connect to Docker: {}", e);
+                        }
+                        diagnostics
+                            .unsearched
+                            .push(format!("Docker containers: {}", e));
+                        return Ok(false);
+                    }
+                    Err(_) => {
+                        warn!(
+                            "Docker did not answer within {} ms, so containers will not be searched",
+                            timeout.as_millis()
+                        );
+                        diagnostics.unsearched.push(format!(
+                            "Docker containers: the daemon did not answer within {} ms",
+                            timeout.as_millis()
+                        ));
+                        return Ok(false);
+                    }
                 }
             }
         })
     }
 }
+
+/// The Docker API requests made while looking up the targets on a port, so
+/// a fake daemon can stand in for it, e.g. in tests.
+pub trait DockerClient {
+    /// Returns true if the Docker daemon answers within `timeout`.
+    fn is_present(&self, timeout: Duration, diagnostics: &mut Diagnostics) -> Result<bool, Error>;
+
+    /// Finds the containers publishing `port` through the given container
+    /// runtime socket, or the default Docker host when `None`.
+    fn find_target_containers(
+        &self,
+        port: cli::Port,
+        socket: Option<&PathBuf>,
+    ) -> Result<Vec<DockerContainer>, Error>;
+
+    /// Finds the containers listening on `port` inside their own network.
+    fn find_containers_by_internal_port(
+        &self,
+        port: cli::Port,
+    ) -> Result<Vec<DockerContainer>, Error>;
+
+    /// Finds the containers running on the host network.
+    fn find_host_network_containers(
+        &self,
+        port: cli::Port,
+    ) -> Result<Vec<HostNetworkContainer>, Error>;
+
+    /// Finds the container a docker-proxy or containerd shim stands in for.
+    fn find_proxied_container(
+        &self,
+        proxy: &ContainerProxy,
+        port: cli::Port,
+    ) -> Result<Option<DockerContainer>, Error>;
+}
+
+/// The Docker daemon, reached through its API.
+pub struct DockerApi;
+
+impl DockerClient for DockerApi {
+    fn is_present(&self, timeout: Duration, diagnostics: &mut Diagnostics) -> Result<bool, Error> {
+        DockerContainer::is_docker_present(timeout, diagnostics)
+    }
+
+    fn find_target_containers(
+        &self,
+        port: cli::Port,
+        socket: Option<&PathBuf>,
+    ) -> Result<Vec<DockerContainer>, Error> {
+        DockerContainer::find_target_containers_at(port, socket)
+    }
+
+    fn find_containers_by_internal_port(
+        &self,
+        port: cli::Port,
+    ) -> Result<Vec<DockerContainer>, Error> {
+        DockerContainer::find_containers_by_internal_port(port)
+    }
+
+    fn find_host_network_containers(
+        &self,
+        port: cli::Port,
+    ) -> Result<Vec<HostNetworkContainer>, Error> {
+        DockerContainer::find_host_network_containers(port)
+    }
+
+    fn find_proxied_container(
+        &self,
+        proxy: &ContainerProxy,
+        port: cli::Port,
+    ) -> Result<Option<DockerContainer>, Error> {
+        DockerContainer::find_proxied_container(proxy, port)
+    }
+}
+
+/// Merges the containers found more than once, e.g. through both their
+/// published and internal port, into one container holding the port
+/// mappings of all of them, so each container is acted on only once.
+///
+/// # Arguments
+///
+/// * `containers` - The containers found on a port.
+pub fn merge_containers(containers: Vec<DockerContainer>) -> Vec<DockerContainer> {
+    let mut merged: Vec<DockerContainer> = vec![];
+
+    for container in containers {
+        match merged.iter_mut().find(|found| found.id == container.id) {
+            Some(found) => {
+                for port in container.ports {
+                    if !found.ports.contains(&port) {
+                        found.ports.push(port);
+                    }
+                }
+            }
+            None => merged.push(container),
+        }
+    }
+
+    merged
+}
+
+/// Returns true if the volume name was generated by Docker, i.e. the volume
+/// was created for a single container rather than given a name.
+///
+/// # Arguments
+///
+/// * `volume` - The volume name.
+fn is_anonymous_volume(volume: &str) -> bool {
+    volume.len() == 64 && volume.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Returns true if a port mapping of a container carries the protocol of
+/// `port`, taking a mapping without a protocol for TCP as Docker does.
+///
+/// # Arguments
+///
+/// * `mapping` - The port mapping of a container listing.
+/// * `port` - Target port, possibly restricted to a protocol.
+pub fn maps_protocol(mapping: &Port, port: cli::Port) -> bool {
+    let protocol = mapping
+        .typ
+        .and_then(|typ| typ.to_string().parse().ok())
+        .unwrap_or(Protocol::Tcp);
+    port.carries(protocol)
+}
+
+/// Formats a port mapping of a container the way `docker ps` does, e.g.
+/// "0.0.0.0:8080->80/tcp", or "80/tcp" for a port that is not published.
+///
+/// # Arguments
+///
+/// * `mapping` - The port mapping of a container listing.
+pub fn format_port_mapping(mapping: &Port) -> String {
+    let protocol = mapping
+        .typ
+        .map(|typ| typ.to_string())
+        .filter(|typ| !typ.is_empty())
+        .unwrap_or_else(|| "tcp".to_string());
+
+    match (mapping.public_port, mapping.ip.as_deref()) {
+        (Some(public_port), Some(ip)) if ip.contains(':') => format!(
+            "[{}]:{}->{}/{}",
+            ip, public_port, mapping.private_port, protocol
+        ),
+        (Some(public_port), Some(ip)) if !ip.is_empty() => format!(
+            "{}:{}->{}/{}",
+            ip, public_port, mapping.private_port, protocol
+        ),
+        (Some(public_port), _) => {
+            format!("{}->{}/{}", public_port, mapping.private_port, protocol)
+        }
+        (None, _) => format!("{}/{}", mapping.private_port, protocol),
+    }
+}
+
+/// Returns the runtime shared by the container runtime requests, so the
+/// ports looked up concurrently do not each start their own.
+fn runtime() -> Result<&'static Runtime, Error> {
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+
+    let runtime = Runtime::new()?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+/// Lists the running containers of the container runtime behind `socket`,
+/// or returns the listing made for an earlier port.
+///
+/// # Arguments
+///
+/// * `client` - The connection to the container runtime.
+/// * `socket` - The container runtime socket, or `None` for the default Docker host.
+async fn running_containers(
+    client: &Client,
+    socket: Option<&PathBuf>,
+) -> Result<Vec<ContainerSummary>, Error> {
+    if let Some((_, containers)) = lock(&CONTAINER_LISTINGS)
+        .iter()
+        .find(|(listed_socket, _)| listed_socket.as_ref() == socket)
+    {
+        return Ok(containers.clone());
+    }
+
+    let containers = client.list_running().await?;
+    debug!("Listed {} running containers", containers.len());
+
+    lock(&CONTAINER_LISTINGS).push((socket.cloned(), containers.clone()));
+    Ok(containers)
+}
+
+/// Locks one of the caches shared by the ports. A cache is only ever
+/// extended or cleared, so one left behind by a panicking thread is fine.
+///
+/// # Arguments
+///
+/// * `cache` - The cache to lock.
+fn lock<T>(cache: &'static Mutex<T>) -> MutexGuard<'static, T> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Drops the cached container listings once a container has been acted on,
+/// so that later lookups see it gone.
+fn forget_listings() {
+    lock(&CONTAINER_LISTINGS).clear();
+}
+
+/// Turns an error of the Docker API into one that says what to do about
+/// it, such as a daemon too old or too new for the API version, or a socket
+/// the user may not access.
+///
+/// # Arguments
+///
+/// * `error` - The error returned by the Docker API.
+pub fn docker_error(error: bollard::errors::Error) -> Error {
+    if let bollard::errors::Error::DockerResponseServerError {
+        status_code,
+        message,
+    } = &error
+    {
+        let version_after = |prefix: &str| {
+            let (_, rest) = message.split_once(prefix)?;
+            let version: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '.')
+                .collect();
+            Some(version.trim_end_matches('.').to_string())
+        };
+
+        if let Some(version) = version_after("Minimum supported API version is ") {
+            return Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "The Docker daemon requires API version {} or later: {}",
+                    version, message
+                ),
+            );
+        }
+        if let Some(version) = version_after("Maximum supported API version is ") {
+            return Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "The Docker daemon supports API version {} at most, upgrade Docker: {}",
+                    version, message
+                ),
+            );
+        }
+
+        let kind = match status_code {
+            401 | 403 => ErrorKind::PermissionDenied,
+            404 => ErrorKind::NotFound,
+            _ => ErrorKind::Other,
+        };
+        return Error::new(kind, message.to_string());
+    }
+
+    // Connection errors carry the error of the socket somewhere in their chain
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&error);
+    while let Some(current) = source {
+        if let Some(io_error) = current.downcast_ref::<Error>() {
+            if io_error.kind() == ErrorKind::PermissionDenied {
+                return Error::new(
+                    ErrorKind::PermissionDenied,
+                    if cfg!(windows) {
+                        "Permission denied on the Docker named pipe; add yourself to the docker-users group, or run killport from an elevated terminal"
+                    } else {
+                        "Permission denied on the Docker socket; add yourself to the docker group (sudo usermod -aG docker $USER, then log in again), or run killport with sudo"
+                    },
+                );
+            }
+            return Error::new(io_error.kind(), error.to_string());
+        }
+        source = current.source();
+    }
+
+    Error::other(error.to_string())
+}
+
+/// Returns the docker CLI to list and act on the containers of the default
+/// Docker host when the Docker API cannot reach it, e.g. because the docker
+/// CLI uses an ssh context. Returns `None` when docker is not on `PATH`, or
+/// when the user may not access the daemon, which the docker CLI cannot
+/// change.
+///
+/// # Arguments
+///
+/// * `error` - The error of the Docker API.
+fn cli_fallback(error: &Error) -> Option<Client> {
+    if error.kind() == ErrorKind::PermissionDenied {
+        return None;
+    }
+
+    let docker = find_in_path(if cfg!(windows) {
+        "docker.exe"
+    } else {
+        "docker"
+    })?;
+    debug!(
+        "Falling back to {} as the Docker API is unusable: {}",
+        docker.display(),
+        error
+    );
+    Some(Client::Cli(DockerCli))
+}
+
+/// Returns true if TLS was asked for, with `DOCKER_TLS_VERIFY` or TLS files,
+/// see [`set_tls_files`].
+fn tls_requested() -> bool {
+    env::var_os("DOCKER_TLS_VERIFY").is_some_and(|verify| !verify.is_empty())
+        || !lock(&TLS_FILES).is_empty()
+}
+
+/// Returns true if the default Docker host is a TCP host reached over TLS,
+/// see [`tls_client`]. It is never handed to the docker CLI, which knows
+/// nothing of the TLS files given to killport.
+fn uses_tls() -> bool {
+    tls_requested()
+        && configured_host()
+            .is_some_and(|host| host.starts_with("tcp://") || host.starts_with("http://"))
+}
+
+/// Returns a client reaching a TCP Docker host over mutual TLS, if
+/// `DOCKER_TLS_VERIFY` is set or TLS files were given, see [`set_tls_files`].
+/// The files not given are taken from `DOCKER_CERT_PATH`, or else from the
+/// docker CLI configuration directory.
+///
+/// # Arguments
+///
+/// * `host` - The Docker host URL, e.g. "tcp://build-1:2376".
+fn tls_client(host: &str) -> Result<Option<Client>, Error> {
+    if !tls_requested() {
+        return Ok(None);
+    }
+    let files = lock(&TLS_FILES).clone();
+
+    let cert_path = env::var_os("DOCKER_CERT_PATH")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(config_dir)
+        .unwrap_or_default();
+    let (ca, cert, key) = files.resolve(&cert_path);
+    if let Some(missing) = [&ca, &cert, &key].into_iter().find(|file| !file.exists()) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "TLS file {} of Docker host {} not found; set DOCKER_CERT_PATH or pass --docker-tls-ca, --docker-tls-cert and --docker-tls-key",
+                missing.display(),
+                host
+            ),
+        ));
+    }
+
+    // TLS is used on the address whatever its scheme, as with the docker CLI
+    let host = host.replacen("http://", "tcp://", 1);
+    debug!("Reaching Docker host {} over TLS", host);
+    Docker::connect_with_ssl(&host, &key, &cert, &ca, SOCKET_TIMEOUT, API_DEFAULT_VERSION)
+        .map(|docker| Some(Client::Api(docker)))
+        .map_err(docker_error)
+}
+
+/// Returns the docker CLI configuration directory: `DOCKER_CONFIG`, or else
+/// `.docker` in the home directory.
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("DOCKER_CONFIG").map(PathBuf::from).or_else(|| {
+        env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+            .map(|home| PathBuf::from(home).join(".docker"))
+    })
+}
+
+/// Returns true if `host` is a Unix socket or named pipe URL.
+///
+/// # Arguments
+///
+/// * `host` - The Docker host URL.
+fn is_local_host(host: &str) -> bool {
+    host.starts_with("unix://") || host.starts_with("npipe://")
+}
+
+/// Returns the Docker host the docker CLI would use: `DOCKER_HOST`, or else
+/// the endpoint of the context named by `DOCKER_CONTEXT` or by the
+/// `currentContext` of the docker CLI configuration. `None` stands for the
+/// local default host.
+fn configured_host() -> Option<String> {
+    if let Some(host) = env::var("DOCKER_HOST").ok().filter(|host| !host.is_empty()) {
+        debug!("Using Docker host {} from DOCKER_HOST", host);
+        return Some(host);
+    }
+
+    let config_dir = config_dir()?;
+
+    let context = match env::var("DOCKER_CONTEXT").ok().filter(|c| !c.is_empty()) {
+        Some(context) => context,
+        None => current_context(&config_dir)?,
+    };
+    if context == "default" {
+        return None;
+    }
+
+    let host = context_host(&config_dir, &context);
+    match &host {
+        Some(host) => debug!("Using Docker host {} of context {}", host, context),
+        None => debug!("Docker context {} has no Docker endpoint", context),
+    }
+    host
+}
+
+/// Returns the first Docker socket of [`HOME_SOCKETS`] found in `home`,
+/// unless the default socket exists. Docker Desktop links the default
+/// socket to its own, while Colima, Rancher Desktop and OrbStack may leave it
+/// missing.
+///
+/// # Arguments
+///
+/// * `home` - The home directory of the user.
+/// * `default_socket` - The socket the Docker daemon listens on by default.
+pub fn discover_socket(home: &Path, default_socket: &Path) -> Option<PathBuf> {
+    if cfg!(windows) || default_socket.exists() {
+        return None;
+    }
+
+    let socket = HOME_SOCKETS
+        .iter()
+        .map(|socket| home.join(socket))
+        .find(|socket| socket.exists())?;
+    debug!("Using Docker socket {}", socket.display());
+    Some(socket)
+}
+
+/// Returns the `currentContext` set in the docker CLI configuration.
+///
+/// # Arguments
+///
+/// * `config_dir` - The docker CLI configuration directory, e.g. `~/.docker`.
+pub fn current_context(config_dir: &Path) -> Option<String> {
+    let config = fs::read_to_string(config_dir.join("config.json")).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&config).ok()?;
+
+    config["currentContext"]
+        .as_str()
+        .filter(|context| !context.is_empty())
+        .map(str::to_string)
+}
+
+/// Returns the Docker endpoint of a docker CLI context.
+///
+/// Contexts are stored as `contexts/meta/<digest>/meta.json` in the
+/// configuration directory, so they are looked up by the name they hold.
+///
+/// # Arguments
+///
+/// * `config_dir` - The docker CLI configuration directory, e.g. `~/.docker`.
+/// * `context` - The context name.
+pub fn context_host(config_dir: &Path, context: &str) -> Option<String> {
+    fs::read_dir(config_dir.join("contexts").join("meta"))
+        .ok()?
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.path().join("meta.json")).ok())
+        .filter_map(|meta| serde_json::from_str::<serde_json::Value>(&meta).ok())
+        .find(|meta| meta["Name"].as_str() == Some(context))?["Endpoints"]["docker"]["Host"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Returns the container runtime sockets of the VM instances kept in
+/// `directory`, one subdirectory per instance.
+///
+/// # Arguments
+///
+/// * `directory` - The directory holding the instances, e.g. `~/.colima`.
+/// * `socket` - The path of the socket within an instance directory.
+fn instance_sockets(directory: &PathBuf, socket: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return vec![];
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path().join(socket))
+        .filter(|path| path.exists())
+        .collect()
+}
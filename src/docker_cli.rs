@@ -0,0 +1,267 @@
+use crate::service::run_command;
+use bollard::service::{
+    ContainerConfig, ContainerInspectResponse, ContainerSummary, ContainerSummaryHostConfig,
+    ContainerSummaryNetworkSettings, EndpointSettings, HostConfig, MountPoint, NetworkSettings,
+    Port, PortMap,
+};
+use log::info;
+use std::collections::HashMap;
+use std::io::Error;
+use std::time::Duration;
+
+/// The docker CLI, through which killport reaches the Docker daemons the
+/// Docker API client cannot, such as those of an ssh context.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DockerCli;
+
+impl DockerCli {
+    /// Runs a docker command against the daemon, returning its output.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The command and its arguments, e.g. `["ps", "--quiet"]`.
+    fn run(&self, args: &[&str]) -> Result<String, Error> {
+        run_command("docker", args)
+    }
+
+    /// Returns the version of the Docker daemon.
+    pub fn version(&self) -> Result<String, Error> {
+        Ok(self
+            .run(&["version", "--format", "{{.Server.Version}}"])?
+            .trim()
+            .to_string())
+    }
+
+    /// Returns the operating system of the daemon's containers, e.g. "linux".
+    pub fn os_type(&self) -> Result<String, Error> {
+        Ok(self
+            .run(&["version", "--format", "{{.Server.Os}}"])?
+            .trim()
+            .to_string())
+    }
+
+    /// Lists the running containers of the daemon.
+    pub fn list_running(&self) -> Result<Vec<ContainerSummary>, Error> {
+        let output = self.run(&["ps", "--quiet", "--no-trunc"])?;
+        let ids: Vec<&str> = output.split_whitespace().collect();
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut args = vec!["inspect"];
+        args.extend(ids);
+        let inspections: serde_json::Value =
+            serde_json::from_str(&self.run(&args)?).map_err(Error::other)?;
+
+        Ok(inspections
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(inspection_from_json)
+            .map(summary_from_inspection)
+            .collect())
+    }
+
+    /// Inspects a container.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    pub fn inspect(&self, name: &str) -> Result<ContainerInspectResponse, Error> {
+        let inspection: serde_json::Value =
+            serde_json::from_str(&self.run(&["container", "inspect", name])?)
+                .map_err(Error::other)?;
+
+        Ok(inspection_from_json(&inspection[0]))
+    }
+
+    /// Sends a signal to a container. Returns false if the container is no
+    /// longer running.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `signal` - The signal name, e.g. "SIGTERM".
+    pub fn kill(&self, name: &str, signal: &str) -> Result<bool, Error> {
+        match self.run(&["kill", "--signal", signal, name]) {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.to_string().contains("No such container")
+                    || e.to_string().contains("is not running") =>
+            {
+                info!("Container '{}' is no longer running: {}", name, e);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stops a container.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `timeout` - Time given to the container to stop before it is killed,
+    ///   or the container's own stop timeout when `None`.
+    pub fn stop(&self, name: &str, timeout: Option<Duration>) -> Result<(), Error> {
+        let timeout = timeout.map(|timeout| timeout.as_secs().to_string());
+        let mut args = vec!["stop"];
+        if let Some(timeout) = &timeout {
+            args.extend(["--time", timeout]);
+        }
+        args.push(name);
+        self.run(&args)?;
+
+        Ok(())
+    }
+
+    /// Removes a container, even a running one.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A container name or ID.
+    /// * `volumes` - Also remove the anonymous volumes of the container.
+    pub fn remove(&self, name: &str, volumes: bool) -> Result<(), Error> {
+        let mut args = vec!["rm", "--force"];
+        if volumes {
+            args.push("--volumes");
+        }
+        args.push(name);
+        self.run(&args)?;
+
+        Ok(())
+    }
+
+    /// Disconnects a container from a network, even when the container is
+    /// gone and only its endpoint is left.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - The network name.
+    /// * `name` - A container name or ID.
+    pub fn disconnect(&self, network: &str, name: &str) -> Result<(), Error> {
+        self.run(&["network", "disconnect", "--force", network, name])?;
+
+        Ok(())
+    }
+}
+
+/// Reads the parts killport uses of a container inspection printed by
+/// `docker inspect`, which holds the same fields as the Docker API returns.
+/// They are read one by one, as newer daemons may add values the API models
+/// do not know.
+///
+/// # Arguments
+///
+/// * `inspection` - The inspection of one container.
+pub fn inspection_from_json(inspection: &serde_json::Value) -> ContainerInspectResponse {
+    let string = |value: &serde_json::Value| value.as_str().map(str::to_string);
+    let config = &inspection["Config"];
+    let network_settings = &inspection["NetworkSettings"];
+
+    ContainerInspectResponse {
+        id: string(&inspection["Id"]),
+        name: string(&inspection["Name"]),
+        config: Some(ContainerConfig {
+            image: string(&config["Image"]),
+            labels: serde_json::from_value(config["Labels"].clone()).ok(),
+            exposed_ports: config["ExposedPorts"].as_object().map(|ports| {
+                ports
+                    .keys()
+                    .map(|port| (port.to_string(), HashMap::new()))
+                    .collect()
+            }),
+            ..Default::default()
+        }),
+        host_config: Some(HostConfig {
+            network_mode: string(&inspection["HostConfig"]["NetworkMode"]),
+            ..Default::default()
+        }),
+        mounts: inspection["Mounts"].as_array().map(|mounts| {
+            mounts
+                .iter()
+                .map(|mount| MountPoint {
+                    typ: mount["Type"].as_str().and_then(|typ| typ.parse().ok()),
+                    name: string(&mount["Name"]),
+                    ..Default::default()
+                })
+                .collect()
+        }),
+        network_settings: Some(NetworkSettings {
+            ports: serde_json::from_value(network_settings["Ports"].clone()).ok(),
+            networks: network_settings["Networks"].as_object().map(|networks| {
+                networks
+                    .iter()
+                    .map(|(network, endpoint)| {
+                        let endpoint = EndpointSettings {
+                            ip_address: string(&endpoint["IPAddress"]),
+                            global_ipv6_address: string(&endpoint["GlobalIPv6Address"]),
+                            ..Default::default()
+                        };
+                        (network.to_string(), endpoint)
+                    })
+                    .collect()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Turns the inspection of a running container into its entry in a
+/// container listing, with a port mapping per host binding of each port.
+///
+/// # Arguments
+///
+/// * `inspection` - The inspection of the container.
+pub fn summary_from_inspection(inspection: ContainerInspectResponse) -> ContainerSummary {
+    let config = inspection.config.unwrap_or_default();
+    let network_settings = inspection.network_settings.unwrap_or_default();
+    let port_map: PortMap = network_settings.ports.unwrap_or_default();
+    let mut port_map: Vec<_> = port_map.into_iter().collect();
+    port_map.sort_by(|(port, _), (other, _)| port.cmp(other));
+
+    let mut ports = vec![];
+    for (port, bindings) in port_map {
+        let (private_port, protocol) = port.split_once('/').unwrap_or((&port, "tcp"));
+        let Ok(private_port) = private_port.parse() else {
+            continue;
+        };
+        let typ = protocol.parse().ok();
+
+        let bindings = bindings.unwrap_or_default();
+        if bindings.is_empty() {
+            ports.push(Port {
+                private_port,
+                typ,
+                ..Default::default()
+            });
+        }
+        for binding in bindings {
+            ports.push(Port {
+                ip: binding.host_ip.filter(|ip| !ip.is_empty()),
+                private_port,
+                public_port: binding.host_port.and_then(|port| port.parse().ok()),
+                typ,
+            });
+        }
+    }
+
+    ContainerSummary {
+        id: inspection.id,
+        names: inspection.name.map(|name| vec![name]),
+        image: config.image,
+        labels: config.labels,
+        ports: Some(ports),
+        state: Some("running".to_string()),
+        host_config: Some(ContainerSummaryHostConfig {
+            network_mode: inspection
+                .host_config
+                .and_then(|host_config| host_config.network_mode),
+        }),
+        network_settings: Some(ContainerSummaryNetworkSettings {
+            networks: network_settings.networks,
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,404 @@
+//! Socket owner resolution on Linux through an eBPF iterator, enabled with the
+//! `ebpf` feature.
+//!
+//! A `bpf_iter` program walks the open files of every task inside the kernel
+//! and reports each socket with the ID of the process holding it, sparing the
+//! walk through `/proc/<pid>/fd` of every process. Sockets passed between
+//! processes are reported for each of their holders.
+//!
+//! The program is assembled here rather than compiled, with the offsets of
+//! the few kernel fields it reads looked up in the kernel BTF, so no eBPF
+//! toolchain or loader library is needed. Loading it requires a kernel with
+//! BTF (5.8 or later) and `CAP_BPF`, which in practice means root.
+
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    fs,
+    io::{Error, ErrorKind, Read},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+/// Location of the BTF describing the running kernel.
+const VMLINUX_BTF: &str = "/sys/kernel/btf/vmlinux";
+
+/// Size of each record written by the program: the socket inode, then the
+/// process and file descriptor numbers.
+const RECORD_SIZE: usize = 16;
+
+// bpf(2) commands
+const BPF_PROG_LOAD: libc::c_long = 5;
+const BPF_LINK_CREATE: libc::c_long = 28;
+const BPF_ITER_CREATE: libc::c_long = 33;
+
+const BPF_PROG_TYPE_TRACING: u32 = 26;
+const BPF_TRACE_ITER: u32 = 28;
+
+/// `bpf_seq_write` helper ID.
+const BPF_FUNC_SEQ_WRITE: i32 = 127;
+
+// BTF type kinds
+const BTF_KIND_STRUCT: u32 = 4;
+const BTF_KIND_UNION: u32 = 5;
+const BTF_KIND_TYPEDEF: u32 = 8;
+const BTF_KIND_VOLATILE: u32 = 9;
+const BTF_KIND_CONST: u32 = 10;
+const BTF_KIND_FUNC: u32 = 12;
+const BTF_KIND_TYPE_TAG: u32 = 18;
+
+/// Finds the IDs of the processes holding any of the sockets `inodes`.
+///
+/// Fails if the eBPF iterator cannot be loaded, e.g. without BTF or
+/// privileges, in which case the caller falls back to scanning `/proc`.
+///
+/// # Arguments
+///
+/// * `inodes` - The inodes of the sockets.
+pub fn socket_owners(inodes: &HashSet<u64>) -> Result<HashSet<i32>, Error> {
+    let owners = socket_files()?
+        .into_iter()
+        .filter(|file| inodes.contains(&file.inode))
+        .map(|file| file.pid)
+        .collect();
+
+    Ok(owners)
+}
+
+/// A socket held open by a process, as reported by the iterator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketFile {
+    /// The inode of the socket.
+    pub inode: u64,
+    /// The ID of the process holding the socket.
+    pub pid: i32,
+    /// The file descriptor of the socket in that process.
+    pub fd: u32,
+}
+
+/// Lists the sockets held open by every process, through the `task_file`
+/// eBPF iterator.
+pub fn socket_files() -> Result<Vec<SocketFile>, Error> {
+    let btf = Btf::parse(fs::read(VMLINUX_BTF)?)?;
+    let offsets = Offsets {
+        f_inode: btf.member_offset("file", "f_inode")?,
+        i_mode: btf.member_offset("inode", "i_mode")?,
+        i_ino: btf.member_offset("inode", "i_ino")?,
+        tgid: btf.member_offset("task_struct", "tgid")?,
+    };
+    let attach_btf_id = btf.type_id(BTF_KIND_FUNC, "bpf_iter_task_file")?;
+
+    let program = load_program(&program(&offsets), attach_btf_id)?;
+    let link = create_link(&program)?;
+    let iterator = create_iterator(&link)?;
+
+    let mut records = Vec::new();
+    fs::File::from(iterator).read_to_end(&mut records)?;
+
+    Ok(records
+        .chunks_exact(RECORD_SIZE)
+        .map(|record| SocketFile {
+            inode: u64::from_ne_bytes(record[0..8].try_into().unwrap_or_default()),
+            pid: i32::from_ne_bytes(record[8..12].try_into().unwrap_or_default()),
+            fd: u32::from_ne_bytes(record[12..16].try_into().unwrap_or_default()),
+        })
+        .collect())
+}
+
+/// Byte offsets of the kernel fields read by the program.
+struct Offsets {
+    f_inode: i16,
+    i_mode: i16,
+    i_ino: i16,
+    tgid: i16,
+}
+
+/// Encodes an eBPF instruction.
+fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> [u8; 8] {
+    let off = off.to_ne_bytes();
+    let imm = imm.to_ne_bytes();
+    [
+        code,
+        dst | (src << 4),
+        off[0],
+        off[1],
+        imm[0],
+        imm[1],
+        imm[2],
+        imm[3],
+    ]
+}
+
+/// Assembles the iterator program.
+///
+/// For every open file that is a socket, it writes the inode, process ID and
+/// file descriptor to the iterator output. Its context is
+/// `struct bpf_iter__task_file`: the iterator metadata, task, descriptor
+/// and file, eight bytes apart.
+fn program(offsets: &Offsets) -> Vec<[u8; 8]> {
+    const LDX_DW: u8 = 0x79;
+    const LDX_W: u8 = 0x61;
+    const LDX_H: u8 = 0x69;
+    const STX_DW: u8 = 0x7b;
+    const STX_W: u8 = 0x63;
+    const MOV_X: u8 = 0xbf;
+    const MOV_K: u8 = 0xb7;
+    const ADD_K: u8 = 0x07;
+    const AND_K: u8 = 0x57;
+    const JEQ_K: u8 = 0x15;
+    const JNE_K: u8 = 0x55;
+    const CALL: u8 = 0x85;
+    const EXIT: u8 = 0x95;
+    // Index of the final `r0 = 0` every check jumps to
+    const DONE: i16 = 22;
+
+    let skip = |pc: i16| DONE - pc - 1;
+
+    vec![
+        // r6 = ctx
+        insn(MOV_X, 6, 1, 0, 0),
+        // r7 = ctx->task, skipped when iteration ends
+        insn(LDX_DW, 7, 6, 8, 0),
+        insn(JEQ_K, 7, 0, skip(2), 0),
+        // r8 = ctx->file
+        insn(LDX_DW, 8, 6, 24, 0),
+        insn(JEQ_K, 8, 0, skip(4), 0),
+        // r9 = file->f_inode
+        insn(LDX_DW, 9, 8, offsets.f_inode, 0),
+        insn(JEQ_K, 9, 0, skip(6), 0),
+        // Only sockets: (inode->i_mode & S_IFMT) == S_IFSOCK
+        insn(LDX_H, 2, 9, offsets.i_mode, 0),
+        insn(AND_K, 2, 0, 0, 0o170000),
+        insn(JNE_K, 2, 0, skip(9), 0o140000),
+        // Record on the stack: inode, task->tgid, ctx->fd
+        insn(LDX_DW, 2, 9, offsets.i_ino, 0),
+        insn(STX_DW, 10, 2, -16, 0),
+        insn(LDX_W, 2, 7, offsets.tgid, 0),
+        insn(STX_W, 10, 2, -8, 0),
+        insn(LDX_DW, 2, 6, 16, 0),
+        insn(STX_W, 10, 2, -4, 0),
+        // bpf_seq_write(ctx->meta->seq, record, sizeof(record))
+        insn(LDX_DW, 1, 6, 0, 0),
+        insn(LDX_DW, 1, 1, 0, 0),
+        insn(MOV_X, 2, 10, 0, 0),
+        insn(ADD_K, 2, 0, 0, -(RECORD_SIZE as i32)),
+        insn(MOV_K, 3, 0, 0, RECORD_SIZE as i32),
+        insn(CALL, 0, 0, 0, BPF_FUNC_SEQ_WRITE),
+        // return 0
+        insn(MOV_K, 0, 0, 0, 0),
+        insn(EXIT, 0, 0, 0, 0),
+    ]
+}
+
+/// Calls bpf(2) with the attributes `attr`, returning the new descriptor.
+fn bpf(cmd: libc::c_long, attr: &mut [u8]) -> Result<OwnedFd, Error> {
+    // SAFETY: `attr` is a valid, zero padded `union bpf_attr` for `cmd`, and
+    // the buffers it points to outlive the call.
+    let fd = unsafe { libc::syscall(libc::SYS_bpf, cmd, attr.as_mut_ptr(), attr.len()) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    // SAFETY: the descriptor was just created and is owned by nobody else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/// Writes `value` at `offset` of the attributes `attr`.
+fn put(attr: &mut [u8], offset: usize, value: &[u8]) {
+    attr[offset..offset + value.len()].copy_from_slice(value);
+}
+
+/// Loads the iterator program, attached to the iterator `attach_btf_id`.
+fn load_program(instructions: &[[u8; 8]], attach_btf_id: u32) -> Result<OwnedFd, Error> {
+    let license = CString::new("Dual MIT/GPL").unwrap_or_default();
+    let mut log = vec![0u8; 64 * 1024];
+    let mut attr = [0u8; 128];
+
+    put(&mut attr, 0, &BPF_PROG_TYPE_TRACING.to_ne_bytes());
+    put(&mut attr, 4, &(instructions.len() as u32).to_ne_bytes());
+    put(&mut attr, 8, &(instructions.as_ptr() as u64).to_ne_bytes());
+    put(&mut attr, 16, &(license.as_ptr() as u64).to_ne_bytes());
+    put(&mut attr, 24, &1u32.to_ne_bytes());
+    put(&mut attr, 28, &(log.len() as u32).to_ne_bytes());
+    put(&mut attr, 32, &(log.as_mut_ptr() as u64).to_ne_bytes());
+    put(&mut attr, 48, b"killport_socks");
+    put(&mut attr, 68, &BPF_TRACE_ITER.to_ne_bytes());
+    put(&mut attr, 108, &attach_btf_id.to_ne_bytes());
+
+    bpf(BPF_PROG_LOAD, &mut attr).map_err(|e| {
+        let log = String::from_utf8_lossy(&log);
+        let log = log.trim_end_matches('\0').trim();
+        if log.is_empty() {
+            e
+        } else {
+            Error::new(e.kind(), format!("{}: {}", e, log))
+        }
+    })
+}
+
+/// Creates an iterator link for the program.
+fn create_link(program: &OwnedFd) -> Result<OwnedFd, Error> {
+    let mut attr = [0u8; 64];
+
+    put(&mut attr, 0, &(program.as_raw_fd() as u32).to_ne_bytes());
+    put(&mut attr, 8, &BPF_TRACE_ITER.to_ne_bytes());
+
+    bpf(BPF_LINK_CREATE, &mut attr)
+}
+
+/// Creates an iterator from the link, whose reads run the program.
+fn create_iterator(link: &OwnedFd) -> Result<OwnedFd, Error> {
+    let mut attr = [0u8; 16];
+
+    put(&mut attr, 0, &(link.as_raw_fd() as u32).to_ne_bytes());
+
+    bpf(BPF_ITER_CREATE, &mut attr)
+}
+
+/// The parts of a BTF blob needed to find kernel types and their members.
+struct Btf {
+    data: Vec<u8>,
+    strings: usize,
+    /// Offset of each type in `data`, indexed by type ID minus one.
+    types: Vec<usize>,
+}
+
+impl Btf {
+    /// Parses the header and indexes the types of a BTF blob.
+    fn parse(data: Vec<u8>) -> Result<Self, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "Malformed kernel BTF");
+        let u32_at = |offset: usize| -> Result<usize, Error> {
+            data.get(offset..offset + 4)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(|bytes| u32::from_ne_bytes(bytes) as usize)
+                .ok_or_else(invalid)
+        };
+
+        if data.get(0..2) != Some(&0xeb9fu16.to_ne_bytes()[..]) {
+            return Err(invalid());
+        }
+        let header_len = u32_at(4)?;
+        let types_start = header_len + u32_at(8)?;
+        let types_end = types_start + u32_at(12)?;
+        let strings = header_len + u32_at(16)?;
+
+        let mut types = Vec::new();
+        let mut offset = types_start;
+        while offset < types_end {
+            types.push(offset);
+            let info = u32_at(offset + 4)?;
+            let vlen = info & 0xffff;
+            let extra = match (info >> 24) & 0x1f {
+                // int, var, decl_tag
+                1 | 14 | 17 => 4,
+                // array
+                3 => 12,
+                // struct, union, datasec, enum64
+                4 | 5 | 15 | 19 => vlen * 12,
+                // enum, func_proto
+                6 | 13 => vlen * 8,
+                _ => 0,
+            };
+            offset += 12 + extra;
+        }
+
+        Ok(Btf {
+            data,
+            strings,
+            types,
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        self.data
+            .get(offset..offset + 4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u32::from_ne_bytes)
+            .unwrap_or_default()
+    }
+
+    fn name(&self, name_off: u32) -> &[u8] {
+        let start = self.strings + name_off as usize;
+        let rest = self.data.get(start..).unwrap_or_default();
+        let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        &rest[..end]
+    }
+
+    fn kind(&self, id: u32) -> Option<(usize, u32)> {
+        let offset = *self.types.get((id as usize).checked_sub(1)?)?;
+        Some((offset, (self.u32_at(offset + 4) >> 24) & 0x1f))
+    }
+
+    /// Finds the ID of the type of kind `kind` called `name`.
+    fn type_id(&self, kind: u32, name: &str) -> Result<u32, Error> {
+        self.types
+            .iter()
+            .enumerate()
+            .find(|(_, &offset)| {
+                let info = self.u32_at(offset + 4);
+                (info >> 24) & 0x1f == kind
+                    && (kind != BTF_KIND_STRUCT || info & 0xffff != 0)
+                    && self.name(self.u32_at(offset)) == name.as_bytes()
+            })
+            .map(|(index, _)| index as u32 + 1)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("Kernel BTF does not describe '{}'", name),
+                )
+            })
+    }
+
+    /// Finds the byte offset of `member` in the struct `name`.
+    fn member_offset(&self, name: &str, member: &str) -> Result<i16, Error> {
+        let id = self.type_id(BTF_KIND_STRUCT, name)?;
+        self.find_member(id, member.as_bytes())
+            .and_then(|bits| i16::try_from(bits / 8).ok())
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("Kernel BTF does not describe '{}.{}'", name, member),
+                )
+            })
+    }
+
+    /// Finds the bit offset of `member` in the struct or union `id`, looking
+    /// into its anonymous members too.
+    fn find_member(&self, id: u32, member: &[u8]) -> Option<u32> {
+        let (offset, _) = self.kind(id)?;
+        let info = self.u32_at(offset + 4);
+        let bitfields = info >> 31 == 1;
+
+        for index in 0..(info & 0xffff) as usize {
+            let entry = offset + 12 + index * 12;
+            let name_off = self.u32_at(entry);
+            let member_type = self.u32_at(entry + 4);
+            let bits = match bitfields {
+                true => self.u32_at(entry + 8) & 0xffffff,
+                false => self.u32_at(entry + 8),
+            };
+
+            if name_off == 0 {
+                if let Some(inner) = self.find_member(self.resolve(member_type), member) {
+                    return Some(bits + inner);
+                }
+            } else if self.name(name_off) == member {
+                return Some(bits);
+            }
+        }
+
+        None
+    }
+
+    /// Skips the typedefs and qualifiers in front of the type `id`.
+    fn resolve(&self, mut id: u32) -> u32 {
+        while let Some((offset, kind)) = self.kind(id) {
+            match kind {
+                BTF_KIND_TYPEDEF | BTF_KIND_VOLATILE | BTF_KIND_CONST | BTF_KIND_TYPE_TAG => {
+                    id = self.u32_at(offset + 8)
+                }
+                BTF_KIND_STRUCT | BTF_KIND_UNION => return id,
+                _ => return 0,
+            }
+        }
+        0
+    }
+}
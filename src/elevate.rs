@@ -0,0 +1,212 @@
+//! Relaunching killport with elevated privileges.
+
+use std::{env, path::PathBuf};
+
+#[cfg(any(unix, windows))]
+use log::info;
+#[cfg(any(unix, windows))]
+use std::io::Error;
+#[cfg(unix)]
+use std::{io::ErrorKind, os::unix::process::CommandExt, process::Command};
+#[cfg(windows)]
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, FALSE, HANDLE},
+    Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+    System::Threading::{
+        GetCurrentProcess, GetExitCodeProcess, OpenProcessToken, WaitForSingleObject, INFINITE,
+    },
+    UI::{
+        Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW},
+        WindowsAndMessaging::SW_SHOWNORMAL,
+    },
+};
+
+/// Tools that can run a command as root, in order of preference.
+#[cfg(unix)]
+const ELEVATION_TOOLS: [&str; 2] = ["sudo", "pkexec"];
+
+/// Returns true if the current process already runs with elevated privileges.
+#[cfg(unix)]
+pub fn is_elevated() -> bool {
+    nix::unistd::geteuid().is_root()
+}
+
+/// Suggests how to get past a target that could not be acted on for lack of
+/// permission, which depends on whether killport already runs as root.
+#[cfg(unix)]
+pub fn escalation_hint() -> &'static str {
+    match (is_elevated(), cfg!(target_os = "macos")) {
+        (false, _) => "Run killport with sudo, or pass --sudo to do it automatically",
+        (true, true) => {
+            "killport already runs as root, so the target is most likely protected by System Integrity Protection, which cannot be bypassed"
+        }
+        (true, false) => {
+            "killport already runs as root, so the target is protected by something else, such as a security module (SELinux, AppArmor) or a user namespace"
+        }
+    }
+}
+
+/// Re-executes the current killport binary as root with the same arguments,
+/// using `sudo` or, when it is not installed, polkit's `pkexec`.
+///
+/// On success this never returns as the current process image is replaced.
+#[cfg(unix)]
+pub fn relaunch_elevated() -> Error {
+    let Some(tool) = ELEVATION_TOOLS.iter().find_map(|tool| find_in_path(tool)) else {
+        return Error::new(
+            ErrorKind::NotFound,
+            "Neither sudo nor pkexec is available to elevate privileges",
+        );
+    };
+
+    let executable = match env::current_exe() {
+        Ok(executable) => executable,
+        Err(e) => return e,
+    };
+
+    info!(
+        "Re-running killport with elevated privileges through {}",
+        tool.display()
+    );
+
+    Command::new(tool)
+        .arg(executable)
+        .args(env::args_os().skip(1))
+        .exec()
+}
+
+/// Returns true if the current process already runs elevated by UAC.
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    unsafe {
+        let mut token: HANDLE = 0;
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == FALSE {
+            return false;
+        }
+
+        let mut elevation: TOKEN_ELEVATION = std::mem::zeroed();
+        let mut size: u32 = 0;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut std::ffi::c_void,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        CloseHandle(token);
+
+        result != FALSE && elevation.TokenIsElevated != 0
+    }
+}
+
+/// Suggests how to get past a target that could not be acted on for lack of
+/// permission, which depends on whether killport already runs elevated.
+#[cfg(windows)]
+pub fn escalation_hint() -> &'static str {
+    if is_elevated() {
+        "killport already runs elevated, so the target is most likely a protected process, such as an antivirus, which cannot be terminated"
+    } else {
+        "Run killport from an elevated terminal (Run as administrator, or `runas /user:Administrator`), or pass --elevate to do it automatically"
+    }
+}
+
+/// Re-runs the current killport binary elevated with the same arguments,
+/// through the UAC prompt, and waits for it to finish.
+///
+/// The elevated run gets a console window of its own. On success this never
+/// returns as killport exits with the exit code of the elevated run.
+#[cfg(windows)]
+pub fn relaunch_elevated() -> Error {
+    let executable = match env::current_exe() {
+        Ok(executable) => executable,
+        Err(e) => return e,
+    };
+
+    let parameters = env::args()
+        .skip(1)
+        .map(|arg| quote_argument(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    info!("Re-running killport with elevated privileges through UAC");
+
+    let wide = |value: &std::ffi::OsStr| -> Vec<u16> {
+        use std::os::windows::ffi::OsStrExt;
+        value.encode_wide().chain(std::iter::once(0)).collect()
+    };
+    let verb = wide("runas".as_ref());
+    let file = wide(executable.as_os_str());
+    let parameters = wide(parameters.as_ref());
+
+    unsafe {
+        let mut info: SHELLEXECUTEINFOW = std::mem::zeroed();
+        info.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+        info.fMask = SEE_MASK_NOCLOSEPROCESS;
+        info.lpVerb = verb.as_ptr();
+        info.lpFile = file.as_ptr();
+        info.lpParameters = parameters.as_ptr();
+        info.nShow = SW_SHOWNORMAL;
+
+        // Fails with ERROR_CANCELLED when the UAC prompt is declined
+        if ShellExecuteExW(&mut info) == FALSE {
+            return Error::last_os_error();
+        }
+
+        WaitForSingleObject(info.hProcess, INFINITE);
+        let mut exit_code: u32 = 1;
+        GetExitCodeProcess(info.hProcess, &mut exit_code);
+        CloseHandle(info.hProcess);
+
+        std::process::exit(exit_code as i32)
+    }
+}
+
+/// Quotes a command line argument so that it is parsed back unchanged by
+/// the Windows C runtime.
+///
+/// # Arguments
+///
+/// * `arg` - The argument.
+#[cfg(windows)]
+pub fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes preceding a quote are escaped, as is the quote
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // Backslashes before the closing quote are escaped too
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+
+    quoted
+}
+
+/// Looks up an executable by name in the directories listed in `PATH`.
+///
+/// # Arguments
+///
+/// * `name` - The executable name.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,349 @@
+//! Discovery through `lsof`, `ss` or `netstat`, for platforms without a
+//! native backend, and for ports whose processes the native backend could
+//! not inspect.
+
+use crate::cli::Port;
+use crate::elevate::find_in_path;
+use crate::killport::Diagnostics;
+use crate::socket::{Protocol, SocketHolder};
+use crate::unix::UnixProcess;
+use log::{debug, info};
+use nix::unistd::Pid;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::process::Command;
+
+/// Tools the fallback backend lists the sockets on a port with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackTool {
+    Lsof,
+    Ss,
+    Netstat,
+}
+
+impl fmt::Display for FallbackTool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.program())
+    }
+}
+
+impl FallbackTool {
+    /// Every tool, in the order they are tried.
+    pub const ALL: [FallbackTool; 3] =
+        [FallbackTool::Lsof, FallbackTool::Ss, FallbackTool::Netstat];
+
+    /// Returns the first tool that is installed, if any.
+    pub fn installed() -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|tool| find_in_path(tool.program()).is_some())
+    }
+
+    /// Returns the executable of the tool.
+    pub fn program(&self) -> &'static str {
+        match self {
+            FallbackTool::Lsof => "lsof",
+            FallbackTool::Ss => "ss",
+            FallbackTool::Netstat => "netstat",
+        }
+    }
+
+    /// Returns the processes holding sockets on `port`, as listed by the
+    /// tool.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - Target port, possibly restricted to a protocol.
+    pub fn find_holders(&self, port: Port) -> Result<Vec<SocketHolder>, Error> {
+        let local_port = format!(":{}", port.number());
+        let args: Vec<&str> = match self {
+            FallbackTool::Lsof => vec!["-nP", "-i", &local_port, "-F", "pcftPn"],
+            FallbackTool::Ss => vec![
+                "-H",
+                "-t",
+                "-u",
+                "-a",
+                "-n",
+                "-p",
+                "sport",
+                "=",
+                &local_port,
+            ],
+            FallbackTool::Netstat => vec!["-t", "-u", "-a", "-n", "-p"],
+        };
+        debug!("Running {} {}", self, args.join(" "));
+
+        let output = Command::new(self.program())
+            .args(&args)
+            .output()
+            .map_err(|e| Error::new(e.kind(), format!("Failed to run {}: {}", self, e)))?;
+        // lsof fails when it finds nothing, so the output is parsed whatever
+        // the exit status
+        if !output.status.success() {
+            debug!(
+                "{} exited with {}: {}",
+                self,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let output = String::from_utf8_lossy(&output.stdout);
+        Ok(match self {
+            FallbackTool::Lsof => parse_lsof(&output, port),
+            FallbackTool::Ss => parse_ss(&output, port),
+            FallbackTool::Netstat => parse_netstat(&output, port),
+        })
+    }
+}
+
+/// Finds the processes associated with the specified `port` with the first
+/// of lsof, ss and netstat that is installed, on platforms without a native
+/// backend.
+///
+/// Returns a `Vec` of native processes.
+///
+/// # Arguments
+///
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `_diagnostics` - Collects observations made during discovery
+pub fn find_target_processes(
+    port: Port,
+    _diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, Error> {
+    let tool = FallbackTool::installed().ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            "Processes cannot be found on this platform without lsof, ss or netstat; install one of them",
+        )
+    })?;
+    find_target_processes_with(tool, port)
+}
+
+/// Finds the processes associated with the specified `port` with `tool`.
+///
+/// # Arguments
+///
+/// * `tool` - The tool listing the sockets.
+/// * `port` - Target port, possibly restricted to a protocol
+pub fn find_target_processes_with(
+    tool: FallbackTool,
+    port: Port,
+) -> Result<Vec<UnixProcess>, Error> {
+    info!(
+        "Finding the processes on port {} with the {} fallback backend",
+        port, tool
+    );
+
+    Ok(tool
+        .find_holders(port)?
+        .into_iter()
+        .filter(|holder| !holder.sockets.is_empty())
+        .map(|holder| {
+            info!(
+                "Found process '{}' with PID {} holding {} through {}",
+                holder.name,
+                holder.pid,
+                holder.sockets.join(", "),
+                tool
+            );
+            UnixProcess::new(Pid::from_raw(holder.pid as i32), holder.name)
+                .with_sockets(holder.sockets)
+        })
+        .collect())
+}
+
+/// Adds a socket held on the port to the holder with `pid`, adding the
+/// holder first if it is not listed yet.
+///
+/// # Arguments
+///
+/// * `holders` - The holders found so far.
+/// * `pid` - The process holding the socket.
+/// * `name` - The name of the process.
+/// * `protocol` - The protocol of the socket.
+/// * `ipv6` - Whether the socket is an IPv6 one.
+fn add_holder(
+    holders: &mut Vec<SocketHolder>,
+    pid: u32,
+    name: &str,
+    protocol: Protocol,
+    ipv6: bool,
+) {
+    let index = match holders.iter().position(|holder| holder.pid == pid) {
+        Some(index) => index,
+        None => {
+            holders.push(SocketHolder {
+                pid,
+                name: name.to_string(),
+                sockets: vec![],
+            });
+            holders.len() - 1
+        }
+    };
+    holders[index].add_socket(protocol, ipv6);
+}
+
+/// Returns the port of a local address, e.g. 8080 for "[::1]:8080".
+///
+/// # Arguments
+///
+/// * `address` - The address, ending with a colon and the port.
+fn local_port(address: &str) -> Option<u16> {
+    address.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Parses the output of `lsof -nP -i :PORT -F pcftPn`, returning every
+/// process listed along with the sockets it holds on `port`, if any.
+///
+/// Each line is a field: `p` starts a process, `c` names it, `f` starts one
+/// of its files, whose IP version (`t`), protocol (`P`) and addresses (`n`)
+/// follow, e.g. `n127.0.0.1:8080->127.0.0.1:52345`.
+///
+/// # Arguments
+///
+/// * `output` - The lsof output.
+/// * `port` - The port, possibly restricted to a protocol.
+pub fn parse_lsof(output: &str, port: Port) -> Vec<SocketHolder> {
+    let mut holders: Vec<SocketHolder> = vec![];
+    let (mut ipv6, mut protocol) = (false, None);
+
+    for line in output.lines() {
+        let (Some(field), Some(value)) = (line.get(..1), line.get(1..)) else {
+            continue;
+        };
+
+        match field {
+            "p" => {
+                if let Ok(pid) = value.parse() {
+                    holders.push(SocketHolder {
+                        pid,
+                        name: String::new(),
+                        sockets: vec![],
+                    });
+                }
+            }
+            "c" => {
+                if let Some(holder) = holders.last_mut() {
+                    holder.name = value.to_string();
+                }
+            }
+            "f" => (ipv6, protocol) = (false, None),
+            "t" => ipv6 = value == "IPv6",
+            "P" => protocol = value.parse::<Protocol>().ok(),
+            "n" => {
+                // Connections list the local address first
+                let local = value.split("->").next().unwrap_or(value);
+                let (Some(holder), Some(protocol)) = (holders.last_mut(), protocol) else {
+                    continue;
+                };
+                if local_port(local) == Some(port.number()) && port.carries(protocol) {
+                    holder.add_socket(protocol, ipv6);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    holders
+}
+
+/// Parses the output of `ss -H -t -u -a -n -p`, returning the processes
+/// holding sockets on `port`.
+///
+/// Lines read like `tcp LISTEN 0 4096 [::]:8080 [::]:*
+/// users:(("node",pid=4242,fd=20))`, naming every process sharing the
+/// socket.
+///
+/// # Arguments
+///
+/// * `output` - The ss output.
+/// * `port` - The port, possibly restricted to a protocol.
+pub fn parse_ss(output: &str, port: Port) -> Vec<SocketHolder> {
+    let mut holders: Vec<SocketHolder> = vec![];
+
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(protocol), Some(local)) = (
+            fields
+                .first()
+                .and_then(|netid| netid.parse::<Protocol>().ok()),
+            fields.get(4),
+        ) else {
+            continue;
+        };
+        if local_port(local) != Some(port.number()) || !port.carries(protocol) {
+            continue;
+        }
+        // IPv6 addresses are bracketed, or a star when bound to any address
+        let ipv6 = local.starts_with('[') || local.starts_with("*:");
+
+        let Some((_, users)) = line.split_once("users:((") else {
+            continue;
+        };
+        for user in users.split("),(") {
+            let owner = user.strip_prefix('"').and_then(|user| {
+                let (name, rest) = user.split_once("\",")?;
+                let pid = rest.strip_prefix("pid=")?.split(',').next()?.parse().ok()?;
+                Some((pid, name))
+            });
+            if let Some((pid, name)) = owner {
+                add_holder(&mut holders, pid, name, protocol, ipv6);
+            }
+        }
+    }
+
+    holders
+}
+
+/// Parses the output of `netstat -t -u -a -n -p` from net-tools, returning
+/// the processes holding sockets on `port`.
+///
+/// Lines read like `tcp6 0 0 :::8080 :::* LISTEN 4242/node`, UDP ones
+/// usually without a state. Sockets of processes that cannot be inspected
+/// are listed with `-` instead of the process, and skipped.
+///
+/// # Arguments
+///
+/// * `output` - The netstat output.
+/// * `port` - The port, possibly restricted to a protocol.
+pub fn parse_netstat(output: &str, port: Port) -> Vec<SocketHolder> {
+    let mut holders: Vec<SocketHolder> = vec![];
+
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(&netid) = fields.first() else {
+            continue;
+        };
+        let (netid, ipv6) = match netid.strip_suffix('6') {
+            Some(netid) => (netid, true),
+            None => (netid, false),
+        };
+        let (Ok(protocol), Some(local)) = (netid.parse::<Protocol>(), fields.get(3)) else {
+            continue;
+        };
+        if local_port(local) != Some(port.number()) || !port.carries(protocol) {
+            continue;
+        }
+
+        // The process comes last, as "PID/name" where the name may hold
+        // spaces
+        let owner = fields
+            .iter()
+            .enumerate()
+            .skip(5)
+            .find_map(|(index, field)| {
+                let (pid, name) = field.split_once('/')?;
+                let name = std::iter::once(name)
+                    .chain(fields[index + 1..].iter().copied())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Some((pid.parse::<u32>().ok()?, name))
+            });
+        if let Some((pid, name)) = owner {
+            add_holder(&mut holders, pid, &name, protocol, ipv6);
+        }
+    }
+
+    holders
+}
@@ -1,26 +1,374 @@
-use crate::docker::DockerContainer;
+use crate::action_log::{self, ActionRecord, Outcome};
+#[cfg(target_os = "aix")]
+use crate::aix::find_target_processes;
+use crate::confirm::confirm;
+use crate::containerd::{ContainerdCli, ContainerdClient, ContainerdContainer};
+use crate::docker::{
+    merge_containers, ContainerProxy, DockerApi, DockerClient, DockerContainer, LabelFilter,
+};
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "macos", target_os = "aix"))
+))]
+use crate::fallback::find_target_processes;
+#[cfg(unix)]
+use crate::fallback::{find_target_processes_with, FallbackTool};
+use crate::kubernetes::Pod;
 #[cfg(target_os = "linux")]
 use crate::linux::find_target_processes;
 #[cfg(target_os = "macos")]
 use crate::macos::find_target_processes;
+use crate::podman::PodmanPod;
+use crate::timings;
+#[cfg(unix)]
+use crate::unix::UnixProcess as NativeProcess;
 #[cfg(target_os = "windows")]
 use crate::windows::find_target_processes;
-use crate::{cli::Mode, signal::KillportSignal};
-use std::{fmt::Display, io::Error};
+#[cfg(target_os = "windows")]
+use crate::windows::WindowsProcess as NativeProcess;
+use crate::{
+    cli::{Backend, ContainerAction, Mode, Port},
+    service::Service,
+    signal::KillportSignal,
+    socket::{KernelSocket, OrphanSocket},
+};
+use log::{debug, info, warn};
+use std::{
+    fmt::Display,
+    io::{Error, ErrorKind},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Time given to the targets to release the port, and to whatever restarts
+/// them to take it back, before the port is first checked.
+const VERIFY_DELAY: Duration = Duration::from_millis(500);
+
+/// Time given to the port to be freed after its targets were acted on,
+/// without `--wait`, before it is reported as still in use.
+const VERIFY_GRACE: Duration = Duration::from_secs(2);
+
+/// Time given to the port to be freed, without `--wait`, once a process was
+/// sent a graceful signal rather than SIGKILL, as a database may first write
+/// out its data.
+const GRACEFUL_VERIFY_GRACE: Duration = Duration::from_secs(30);
+
+/// Interval at which the port is checked for having been freed.
+const VERIFY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Names of the processes that forward published ports to containers
+/// without naming the container, i.e. rootlessport of rootless runtimes and
+/// gvproxy of Podman. docker-proxy is recognized by its command line instead.
+const CONTAINER_FORWARDERS: [&str; 2] = ["rootlessport", "gvproxy"];
+
+/// Names of the processes sent SIGTERM rather than SIGKILL unless forced,
+/// i.e. databases a hard kill could leave corrupted or in need of recovery.
+pub const GRACEFUL_PROCESSES: [&str; 5] =
+    ["postgres", "mysqld", "mariadbd", "mongod", "redis-server"];
+
+/// Sorts the targets found on a port so they are acted on and reported in
+/// the same order on every run: processes by PID, then everything else by
+/// name.
+///
+/// # Arguments
+///
+/// * `targets` - The targets found on the port.
+pub fn sort_targets(targets: &mut [Box<dyn Killable>]) {
+    targets.sort_by_key(|target| {
+        (
+            target.get_pid().is_none(),
+            target.get_pid(),
+            target.get_name(),
+        )
+    });
+}
+
+/// Returns true if a process is one of `graceful`, comparing the name of its
+/// program, e.g. "postgres" for "/usr/lib/postgresql/16/bin/postgres -D
+/// /var/lib/postgresql" or for "postgres: checkpointer".
+///
+/// # Arguments
+///
+/// * `name` - The process name or command line.
+/// * `graceful` - The names of the processes preferring SIGTERM.
+pub fn prefers_graceful(name: &str, graceful: &[String]) -> bool {
+    let program = name.split_whitespace().next().unwrap_or_default();
+    let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    let program = program.trim_end_matches(':');
+    let program = program
+        .strip_suffix(".exe")
+        .or_else(|| program.strip_suffix(".EXE"))
+        .unwrap_or(program);
+
+    graceful.iter().any(|graceful| graceful == program)
+}
+
+/// Returns the signal sent rather than SIGKILL to a process of the graceful
+/// list: SIGINT to PostgreSQL, as it would wait on SIGTERM for every client
+/// to disconnect, and SIGTERM to anything else.
+///
+/// # Arguments
+///
+/// * `name` - The process name or command line.
+pub fn graceful_signal(name: &str) -> KillportSignal {
+    if prefers_graceful(name, &["postgres".to_string()]) {
+        KillportSignal::interrupt()
+    } else {
+        KillportSignal::terminate()
+    }
+}
 
 /// Interface for killable targets such as native process and docker container.
-pub trait Killable {
+///
+/// Targets are found on every port at once, then acted on one port at a time,
+/// so they are sent across threads.
+pub trait Killable: Send {
     fn kill(&self, signal: KillportSignal) -> Result<bool, Error>;
 
     fn get_type(&self) -> KillableType;
 
     fn get_name(&self) -> String;
+
+    /// Returns the service supervising the target, which may restart it
+    /// after it has been killed.
+    fn get_service(&self) -> Option<Service> {
+        None
+    }
+
+    /// Stops the target gracefully, giving it up to `timeout` before it is
+    /// killed, or its own default when `None`.
+    fn stop(&self, _timeout: Option<Duration>) -> Result<bool, Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "{} '{}' cannot be stopped",
+                self.get_type(),
+                self.get_name()
+            ),
+        ))
+    }
+
+    /// Stops the target like [`Killable::stop`], then removes it.
+    fn remove(&self, _timeout: Option<Duration>) -> Result<bool, Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "{} '{}' cannot be removed",
+                self.get_type(),
+                self.get_name()
+            ),
+        ))
+    }
+
+    /// Removes the target along with the resources it leaves behind, and
+    /// returns those resources.
+    fn prune(&self) -> Result<Vec<(KillableType, String)>, Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            format!("{} '{}' cannot be pruned", self.get_type(), self.get_name()),
+        ))
+    }
+
+    /// Waits up to `timeout` for the killed target to exit, returning false
+    /// if it is still running by then.
+    fn wait(&self, _timeout: Duration) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    /// Returns the ID of the container the target runs in, if it is a
+    /// process of a container.
+    fn get_container_id(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the process ID of the target, if it is a process.
+    fn get_pid(&self) -> Option<u32> {
+        None
+    }
+
+    /// Returns the processes spawned by the target, directly or indirectly.
+    fn get_children(&self) -> Vec<Box<dyn Killable>> {
+        vec![]
+    }
+
+    /// Returns the user owning the target, e.g. `alice` or `DOMAIN\alice`
+    /// on Windows.
+    fn get_owner(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns up to `depth` processes the target was spawned by, closest
+    /// first, stopping at processes that must never be killed as a parent.
+    fn get_parents(&self, _depth: usize) -> Vec<Box<dyn Killable>> {
+        vec![]
+    }
+
+    /// Returns the Kubernetes pod the target runs in or forwards a port to.
+    fn get_pod(&self) -> Option<Pod> {
+        None
+    }
+
+    /// Returns the container the target stands in for on the host, if it
+    /// is a docker-proxy or containerd shim.
+    fn get_container_proxy(&self) -> Option<ContainerProxy> {
+        None
+    }
+
+    /// Returns the sockets the target holds on the port, e.g. "tcp6", if it
+    /// is a process.
+    fn get_sockets(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// Returns the descendants of a process, parents before their children.
+///
+/// # Arguments
+///
+/// * `pid` - The process whose descendants to find.
+/// * `parents` - Pairs of process ID and parent process ID of every process.
+pub fn descendants<T: Copy + PartialEq>(pid: T, parents: &[(T, T)]) -> Vec<T> {
+    let mut found: Vec<T> = vec![];
+    let mut next = 0;
+    let mut parent = pid;
+
+    loop {
+        for &(child, _) in parents
+            .iter()
+            .filter(|(child, ppid)| *ppid == parent && *child != pid)
+        {
+            if !found.contains(&child) {
+                found.push(child);
+            }
+        }
+
+        let Some(&child) = found.get(next) else {
+            break;
+        };
+        parent = child;
+        next += 1;
+    }
+
+    found
+}
+
+/// Returns true if `container` runs a node of a local Kubernetes cluster and
+/// is left alone, which is reported along with the workload the port leads
+/// to. Killing a node container takes down every workload on the node.
+///
+/// # Arguments
+///
+/// * `container` - The container publishing the port.
+/// * `port` - The target port.
+/// * `options` - The flags to act with.
+/// * `diagnostics` - Collects the container when it is left alone.
+fn is_spared_node(
+    container: &DockerContainer,
+    port: Port,
+    options: &KillOptions,
+    diagnostics: &mut Diagnostics,
+) -> bool {
+    let Some(node) = container.node.as_ref().filter(|_| !options.kill_nodes) else {
+        return false;
+    };
+
+    let workload = node
+        .find_workload()
+        .or_else(|| {
+            node.node_port
+                .map(|node_port| format!("port {} of the node", node_port))
+        })
+        .unwrap_or_else(|| "the cluster".to_string());
+    let rail = format!(
+        "Port {} is published by container '{}', a node of {}, and leads to {}; acting on the container would take down the node",
+        port, container.name, node, workload
+    );
+    if acknowledged(&rail, options) {
+        return false;
+    }
+
+    warn!(
+        "{}, so it is left alone; pass --kill-nodes or --force to act on it anyway",
+        rail
+    );
+    diagnostics
+        .skipped
+        .push((KillableType::Container, container.get_name()));
+    true
+}
+
+/// Returns true if the user acknowledged a safety rail, letting killport go
+/// past it: with `--force`, or by answering yes when asked on the terminal.
+/// Dry runs never ask.
+///
+/// # Arguments
+///
+/// * `rail` - Explains what going past the rail would do.
+/// * `options` - The options killport runs with.
+fn acknowledged(rail: &str, options: &KillOptions) -> bool {
+    if options.force {
+        warn!("{}; going ahead as --force was given", rail);
+        return true;
+    }
+
+    !options.dry_run && confirm(&format!("{}. Go ahead anyway?", rail))
+}
+
+/// Returns true if the labels of `container` pass every filter. Containers
+/// that do not are left out as someone else's.
+///
+/// # Arguments
+///
+/// * `container` - The container found on the port.
+/// * `filters` - The filters on container labels.
+fn matches_label_filters(container: &DockerContainer, filters: &[LabelFilter]) -> bool {
+    let matches = filters
+        .iter()
+        .all(|filter| filter.matches(&container.labels));
+    if !matches {
+        info!(
+            "Not targeting container '{}' as its labels do not match {}",
+            container.name,
+            filters
+                .iter()
+                .map(|filter| filter.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    matches
+}
+
+/// Returns true if the user name `owner` refers to `user`.
+///
+/// Windows accounts, given as `DOMAIN\name`, match either in full or by
+/// their name alone, ignoring case like Windows does.
+///
+/// # Arguments
+///
+/// * `owner` - The user owning a target.
+/// * `user` - The user name to match.
+pub fn is_owned_by(owner: &str, user: &str) -> bool {
+    match owner.rsplit_once('\\') {
+        Some((_, name)) => owner.eq_ignore_ascii_case(user) || name.eq_ignore_ascii_case(user),
+        None => owner == user,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KillableType {
     Process,
     Container,
+    /// A service stopped through its manager instead of being killed.
+    Service,
+    /// A Kubernetes pod deleted instead of or along with its process.
+    Pod,
+    /// An anonymous volume removed along with its container.
+    Volume,
+    /// A leftover endpoint of a container disconnected from its network.
+    NetworkEndpoint,
+    /// A podman pod acted on as a whole instead of its infra container.
+    PodmanPod,
 }
 
 impl Display for KillableType {
@@ -28,10 +376,211 @@ impl Display for KillableType {
         f.write_str(match self {
             KillableType::Process => "process",
             KillableType::Container => "container",
+            KillableType::Service => "service",
+            KillableType::Pod => "pod",
+            KillableType::Volume => "volume",
+            KillableType::NetworkEndpoint => "network endpoint",
+            KillableType::PodmanPod => "podman pod",
         })
     }
 }
 
+/// A target acted on, or that would be in a dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandledTarget {
+    pub target_type: KillableType,
+    pub name: String,
+    /// The sockets the target held on the port, e.g. "tcp" and "tcp6" for a
+    /// process listening on both IPv4 and IPv6.
+    pub sockets: Vec<String>,
+}
+
+impl HandledTarget {
+    /// Returns a target that held no socket of its own, such as a pod or a
+    /// service.
+    pub fn new(target_type: KillableType, name: String) -> Self {
+        HandledTarget {
+            target_type,
+            name,
+            sockets: vec![],
+        }
+    }
+
+    /// Returns the socket kinds the target held, each named once.
+    pub fn socket_kinds(&self) -> Vec<String> {
+        let mut kinds = self.sockets.clone();
+        kinds.sort();
+        kinds.dedup();
+        kinds
+    }
+}
+
+/// Options controlling how the targets on a port are acted upon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KillOptions {
+    /// The signal to send to the targets.
+    pub signal: KillportSignal,
+    /// Names of the processes sent SIGTERM rather than SIGKILL, as a hard
+    /// kill could corrupt their data.
+    pub graceful: Vec<String>,
+    /// Bypass the safety rails without asking, e.g. send SIGKILL even to the
+    /// processes of `graceful`.
+    pub force: bool,
+    /// The mode of operation, determining if processes, containers, or both should be targeted.
+    pub mode: Mode,
+    /// The implementation finding the processes.
+    pub backend: Backend,
+    /// Only report what would be done, without killing anything.
+    pub dry_run: bool,
+    /// Stop the service supervising a target instead of signaling the target.
+    pub stop_service: bool,
+    /// Also signal the processes spawned by the targets.
+    pub kill_children: bool,
+    /// Also signal up to this many levels of processes that spawned the targets.
+    pub kill_parents: usize,
+    /// Only act on the processes owned by this user.
+    pub user: Option<String>,
+    /// Wait up to this long for each killed target to exit, then make sure
+    /// the port was freed.
+    pub wait: Option<Duration>,
+    /// What to do with the containers found.
+    pub container_action: ContainerAction,
+    /// Time given to containers to stop, or their own default when `None`.
+    pub stop_timeout: Option<Duration>,
+    /// Also match containers listening on the port inside their own network,
+    /// whether or not it is published.
+    pub internal_port: bool,
+    /// Time given to the Docker daemon to answer before it is treated as
+    /// absent.
+    pub docker_timeout: Duration,
+    /// Never connect to the Docker API, nor search the containers of
+    /// containerd.
+    pub no_docker: bool,
+    /// Delete the Kubernetes pods the targets run in or forward a port to.
+    pub delete_pod: bool,
+    /// Also act on containers running the nodes of local Kubernetes clusters.
+    pub kill_nodes: bool,
+    /// Only act on the containers whose labels pass all of these filters.
+    pub container_labels: Vec<LabelFilter>,
+    /// Remove the containers acted on along with their anonymous volumes
+    /// and leftover network endpoints.
+    pub prune_container: bool,
+    /// Act on the whole podman pod a matched infra container belongs to.
+    pub stop_pod: bool,
+}
+
+impl Default for KillOptions {
+    /// Returns the options killport runs with when no flag is given.
+    fn default() -> Self {
+        KillOptions {
+            signal: KillportSignal::kill(),
+            graceful: GRACEFUL_PROCESSES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            force: false,
+            mode: Mode::Auto,
+            backend: Backend::Auto,
+            dry_run: false,
+            stop_service: false,
+            kill_children: false,
+            kill_parents: 0,
+            user: None,
+            wait: None,
+            container_action: ContainerAction::Kill,
+            stop_timeout: None,
+            internal_port: false,
+            docker_timeout: Duration::from_secs(2),
+            no_docker: false,
+            delete_pod: false,
+            kill_nodes: false,
+            container_labels: vec![],
+            prune_container: false,
+            stop_pod: false,
+        }
+    }
+}
+
+/// Observations gathered while looking up the targets on a port.
+///
+/// Platform backends record anything that might explain why a target could
+/// not be found, so it can be reported back to the user alongside the results.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// IDs of the processes whose sockets could not be inspected, usually
+    /// because they belong to another user.
+    pub uninspected: Vec<u32>,
+    /// Sockets bound to the port that no process owns anymore.
+    pub orphans: Vec<OrphanSocket>,
+    /// Sockets bound to the port by the kernel rather than by a process.
+    pub kernel_sockets: Vec<KernelSocket>,
+    /// Targets that were found but deliberately left alone.
+    pub skipped: Vec<(KillableType, String)>,
+    /// What was searched for targets, e.g. "processes with a socket bound to
+    /// port 8080".
+    pub searched: Vec<String>,
+    /// What could not be searched, with the reason, e.g. "Docker containers:
+    /// the daemon did not answer within 1000 ms".
+    pub unsearched: Vec<String>,
+}
+
+impl Diagnostics {
+    /// Explains why nothing was found on a port, from what was searched and
+    /// what stood in the way. Sockets held by the kernel or left without a
+    /// process are described by [`describe_kernel_sockets`] and
+    /// [`describe_orphans`] instead.
+    ///
+    /// [`describe_kernel_sockets`]: crate::socket::describe_kernel_sockets
+    /// [`describe_orphans`]: crate::socket::describe_orphans
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - The port nothing was found on.
+    pub fn explain(&self, port: Port) -> Vec<String> {
+        let mut lines = vec![format!("Searched for targets on port {}:", port)];
+        lines.extend(
+            self.searched
+                .iter()
+                .map(|searched| format!("  - {}", searched)),
+        );
+        if self.searched.is_empty() {
+            lines.push("  - nothing".to_string());
+        }
+
+        let mut reasons: Vec<String> = vec![];
+        if !self.uninspected.is_empty() {
+            let pids: Vec<String> = self.uninspected.iter().map(u32::to_string).collect();
+            reasons.push(format!(
+                "{} processes could not be inspected, usually as they belong to another user, and may hold the port (PIDs {}); run killport with sudo",
+                self.uninspected.len(),
+                pids.join(", ")
+            ));
+        }
+        for (killable_type, name) in &self.skipped {
+            reasons.push(format!(
+                "{} '{}' was found but left alone, see the warning above",
+                killable_type, name
+            ));
+        }
+        for unsearched in &self.unsearched {
+            reasons.push(format!("Not searched: {}", unsearched));
+        }
+        if !self.kernel_sockets.is_empty() || !self.orphans.is_empty() {
+            reasons.push("The port is held by sockets without a process, see above".to_string());
+        }
+        if reasons.is_empty() {
+            reasons.push(format!(
+                "No socket is bound to port {}, so it is free unless another network namespace or host is meant",
+                port
+            ));
+        }
+
+        lines.push("Likely reasons:".to_string());
+        lines.extend(reasons.into_iter().map(|reason| format!("  - {}", reason)));
+        lines
+    }
+}
+
 impl Killable for DockerContainer {
     /// Entry point to kill the docker containers.
     ///
@@ -39,9 +588,7 @@ impl Killable for DockerContainer {
     ///
     /// * `signal` - A enum value representing the signal type.
     fn kill(&self, signal: KillportSignal) -> Result<bool, Error> {
-        Self::kill_container(&self.name, signal)?;
-
-        Ok(true)
+        Self::kill_container(&self.id, self.socket.as_ref(), signal)
     }
 
     /// Returns the type of the killable target.
@@ -53,7 +600,56 @@ impl Killable for DockerContainer {
     /// # Returns
     ///
     /// * `String` - A string that describes the type of the killable target. For a `UnixProcess` it will return "process",
-    /// and for a `DockerContainer` it will return "container".
+    ///   and for a `DockerContainer` it will return "container".
+    fn get_type(&self) -> KillableType {
+        KillableType::Container
+    }
+
+    fn get_name(&self) -> String {
+        self.describe()
+    }
+
+    fn stop(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        Self::stop_container(&self.id, self.socket.as_ref(), timeout, false)?;
+
+        Ok(true)
+    }
+
+    fn remove(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        Self::stop_container(&self.id, self.socket.as_ref(), timeout, true)?;
+
+        Ok(true)
+    }
+
+    fn prune(&self) -> Result<Vec<(KillableType, String)>, Error> {
+        let pruned = Self::prune_container(&self.id, self.socket.as_ref())?;
+
+        Ok(pruned
+            .volumes
+            .into_iter()
+            .map(|volume| (KillableType::Volume, volume))
+            .chain(pruned.networks.into_iter().map(|network| {
+                (
+                    KillableType::NetworkEndpoint,
+                    format!("{} on {}", self.name, network),
+                )
+            }))
+            .collect())
+    }
+}
+
+impl Killable for ContainerdContainer {
+    /// Entry point to kill the containerd containers.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - A enum value representing the signal type.
+    fn kill(&self, signal: KillportSignal) -> Result<bool, Error> {
+        self.kill_container(signal)?;
+
+        Ok(true)
+    }
+
     fn get_type(&self) -> KillableType {
         KillableType::Container
     }
@@ -61,99 +657,848 @@ impl Killable for DockerContainer {
     fn get_name(&self) -> String {
         self.name.to_string()
     }
+
+    fn stop(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        self.stop_container(timeout, false)?;
+
+        Ok(true)
+    }
+
+    fn remove(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        self.stop_container(timeout, true)?;
+
+        Ok(true)
+    }
+
+    fn prune(&self) -> Result<Vec<(KillableType, String)>, Error> {
+        self.prune_container()?;
+
+        Ok(vec![])
+    }
 }
 
+impl Killable for PodmanPod {
+    /// Entry point to kill the containers of the podman pod.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - A enum value representing the signal type.
+    fn kill(&self, signal: KillportSignal) -> Result<bool, Error> {
+        self.kill_pod(signal)?;
+
+        Ok(true)
+    }
+
+    fn get_type(&self) -> KillableType {
+        KillableType::PodmanPod
+    }
+
+    fn get_name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn stop(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        self.stop_pod(timeout, false)?;
+
+        Ok(true)
+    }
+
+    fn remove(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        self.stop_pod(timeout, true)?;
+
+        Ok(true)
+    }
+}
+
+/// The operations killport runs on a port. Only finding the targets depends
+/// on the platform; acting on them is provided on top of it.
 pub trait KillportOperations {
     /// Finds the killables (native processes and docker containers) associated with the specified `port`.
-    fn find_target_killables(&self, port: u16, mode: Mode)
-        -> Result<Vec<Box<dyn Killable>>, Error>;
+    fn find_target_killables(
+        &self,
+        port: Port,
+        options: &KillOptions,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Box<dyn Killable>>, Error>;
 
     /// Manages the action of killing or simulating the killing of services by port.
+    /// This function can either actually kill processes or containers, or simulate the action based on the `dry_run` flag.
+    ///
+    /// Targets are found through [`KillportOperations::find_target_killables`]
+    /// and acted on through [`KillportOperations::act_on_targets`], so the
+    /// same handling applies whatever discovers them.
+    ///
+    /// # Arguments
+    /// * `port` - The port number to check for killable entities.
+    /// * `options` - The signal, mode and flags to act with.
+    /// * `diagnostics` - Collects observations made during discovery.
+    ///
+    /// # Returns
+    /// * `Result<Vec<HandledTarget>, Error>` - The targets acted on, with the sockets they held, or an error.
     fn kill_service_by_port(
         &self,
-        port: u16,
-        signal: KillportSignal,
-        mode: Mode,
-        dry_run: bool,
-    ) -> Result<Vec<(KillableType, String)>, Error>;
+        port: Port,
+        options: &KillOptions,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<HandledTarget>, Error> {
+        let target_killables = self.find_target_killables(port, options, diagnostics)?;
+        self.act_on_targets(port, target_killables, options, diagnostics)
+    }
+
+    /// Acts on the targets found on `port`, or only reports them in a dry
+    /// run.
+    ///
+    /// Targets supervised by a service are stopped through their service
+    /// manager instead when `stop_service` is set, and reported as services.
+    /// With `kill_children` set, the processes spawned by a target are
+    /// killed right after it, and with `kill_parents` set, the processes
+    /// that spawned it after those. With `user` set, processes owned by
+    /// anyone else are left alone. With `wait` set, killed targets must exit
+    /// in time, or a `TimedOut` error is returned.
+    ///
+    /// Once the targets are ended, the port is checked to be free after
+    /// [`VERIFY_DELAY`], giving it up to [`VERIFY_GRACE`] when not waiting,
+    /// or [`GRACEFUL_VERIFY_GRACE`] once a process of the graceful list was
+    /// sent a graceful signal, or an `AddrInUse` error is returned naming
+    /// what still holds it.
+    /// Signals that do not end their target, such as SIGHUP, are not checked
+    /// without `wait`.
+    ///
+    /// # Arguments
+    /// * `port` - The port the targets were found on.
+    /// * `target_killables` - The targets found on the port.
+    /// * `options` - The signal, mode and flags to act with.
+    /// * `diagnostics` - The observations made during discovery, collecting
+    ///   the targets left alone.
+    fn act_on_targets(
+        &self,
+        port: Port,
+        target_killables: Vec<Box<dyn Killable>>,
+        options: &KillOptions,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<HandledTarget>, Error> {
+        let mut results = Vec::new();
+
+        // A process can come up more than once, e.g. as the child of another
+        // target, and is only signaled the first time
+        let mut handled_pids: Vec<u32> = vec![];
+        // Whether a process was sent a graceful signal, and may take a while
+        // to shut down
+        let mut shutting_down = false;
+
+        for killable in target_killables {
+            if let (Some(user), KillableType::Process) = (&options.user, killable.get_type()) {
+                let owner = killable.get_owner();
+                if !owner
+                    .as_deref()
+                    .is_some_and(|owner| is_owned_by(owner, user))
+                {
+                    warn!(
+                        "Not killing {} '{}' on port {} as it is owned by {}, not {}",
+                        killable.get_type(),
+                        killable.get_name(),
+                        port,
+                        owner.as_deref().unwrap_or("an unknown user"),
+                        user
+                    );
+                    diagnostics
+                        .skipped
+                        .push((killable.get_type(), killable.get_name()));
+                    continue;
+                }
+            }
+
+            if let Some(pod) = killable.get_pod() {
+                if options.delete_pod {
+                    if !options.dry_run {
+                        info!("Deleting pod {}", pod);
+                        let deleted = pod.delete().map(|()| true);
+                        action_log::record(&ActionRecord {
+                            timestamp: SystemTime::now(),
+                            port,
+                            pid: None,
+                            target_type: KillableType::Pod,
+                            name: pod.to_string(),
+                            action: "delete",
+                            signal: None,
+                            outcome: Outcome::of(&deleted),
+                        });
+                        deleted?;
+                    }
+                    let result = HandledTarget::new(KillableType::Pod, pod.to_string());
+                    if !results.contains(&result) {
+                        results.push(result);
+                    }
+                    // A port forward outlives its pod, so it is killed as well
+                    if !pod.forwarded {
+                        continue;
+                    }
+                } else if !pod.forwarded {
+                    warn!(
+                        "{} '{}' runs in pod {}, which restarts it; pass --delete-pod to delete the pod instead",
+                        killable.get_type(),
+                        killable.get_name(),
+                        pod
+                    );
+                }
+            }
+
+            if let Some(service) = killable.get_service() {
+                if let (Some(reason), false) = (service.keep_process_reason(), options.stop_service)
+                {
+                    warn!("Port {} is held by {} {}", port, service, reason);
+                    diagnostics
+                        .skipped
+                        .push((killable.get_type(), killable.get_name()));
+                    continue;
+                }
+
+                if options.stop_service {
+                    if !options.dry_run {
+                        info!("Stopping {} instead of killing its process", service);
+                        let stopped = service.stop().map(|()| true);
+                        action_log::record(&ActionRecord {
+                            timestamp: SystemTime::now(),
+                            port,
+                            pid: None,
+                            target_type: KillableType::Service,
+                            name: service.to_string(),
+                            action: "stop",
+                            signal: None,
+                            outcome: Outcome::of(&stopped),
+                        });
+                        stopped?;
+                    }
+                    let result = HandledTarget::new(KillableType::Service, service.to_string());
+                    if !results.contains(&result) {
+                        results.push(result);
+                    }
+                    continue;
+                }
+
+                warn!(
+                    "{} '{}' is managed by {}, which may restart it; pass --stop-service to stop the service instead",
+                    killable.get_type(),
+                    killable.get_name(),
+                    service
+                );
+            }
+
+            // Children and parents are looked up before the target is gone
+            let children = if options.kill_children {
+                killable.get_children()
+            } else {
+                vec![]
+            };
+            let parents = if options.kill_parents > 0 {
+                killable.get_parents(options.kill_parents)
+            } else {
+                vec![]
+            };
+
+            for killable in std::iter::once(killable).chain(children).chain(parents) {
+                if let Some(pid) = killable.get_pid() {
+                    if handled_pids.contains(&pid) {
+                        debug!(
+                            "{} '{}' with PID {} was already handled",
+                            killable.get_type(),
+                            killable.get_name(),
+                            pid
+                        );
+                        continue;
+                    }
+                    handled_pids.push(pid);
+                }
+
+                // Databases shut down cleanly on SIGTERM or SIGINT, while
+                // SIGKILL may leave their data to be recovered or corrupted
+                let signal = if options.signal.is_kill()
+                    && !options.force
+                    && killable.get_type() == KillableType::Process
+                    && prefers_graceful(&killable.get_name(), &options.graceful)
+                {
+                    let signal = graceful_signal(&killable.get_name());
+                    warn!(
+                        "Sending {} rather than SIGKILL to {} '{}' so it can shut down cleanly, as a hard kill could corrupt its data; pass --force to send SIGKILL",
+                        signal,
+                        killable.get_type(),
+                        killable.get_name()
+                    );
+                    shutting_down = true;
+                    signal
+                } else {
+                    options.signal.clone()
+                };
+
+                let handled = HandledTarget {
+                    target_type: killable.get_type(),
+                    name: killable.get_name(),
+                    sockets: killable.get_sockets(),
+                };
+                if options.dry_run {
+                    // In dry-run mode, collect information about the entity without killing
+                    results.push(handled);
+                } else {
+                    // In actual mode, attempt to kill the entity and collect its information if successful
+                    let started = Instant::now();
+                    let (action, result) = match (killable.get_type(), options.container_action) {
+                        (
+                            KillableType::Container | KillableType::PodmanPod,
+                            ContainerAction::Stop,
+                        ) => ("stop", killable.stop(options.stop_timeout)),
+                        // Pruned containers are removed along with their
+                        // volumes below, which a plain removal would orphan
+                        (KillableType::Container, ContainerAction::Rm)
+                            if options.prune_container =>
+                        {
+                            ("stop", killable.stop(options.stop_timeout))
+                        }
+                        (
+                            KillableType::Container | KillableType::PodmanPod,
+                            ContainerAction::Rm,
+                        ) => ("remove", killable.remove(options.stop_timeout)),
+                        _ => ("kill", killable.kill(signal.clone())),
+                    };
+                    timings::record(port, "act on targets", started.elapsed());
+                    action_log::record(&ActionRecord {
+                        timestamp: SystemTime::now(),
+                        port,
+                        pid: killable.get_pid(),
+                        target_type: killable.get_type(),
+                        name: killable.get_name(),
+                        action,
+                        signal: (action == "kill").then(|| signal.to_string()),
+                        outcome: Outcome::of(&result),
+                    });
+                    let done = result?;
+                    if done {
+                        if let Some(timeout) = options.wait {
+                            if !timings::time(port, "wait for targets to exit", || {
+                                killable.wait(timeout)
+                            })? {
+                                return Err(Error::new(
+                                    ErrorKind::TimedOut,
+                                    format!(
+                                        "{} '{}' did not exit within {} seconds",
+                                        killable.get_type(),
+                                        killable.get_name(),
+                                        timeout.as_secs()
+                                    ),
+                                ));
+                            }
+                        }
+                        results.push(handled);
+                        if options.prune_container && killable.get_type() == KillableType::Container
+                        {
+                            let pruned = killable.prune();
+                            action_log::record(&ActionRecord {
+                                timestamp: SystemTime::now(),
+                                port,
+                                pid: None,
+                                target_type: killable.get_type(),
+                                name: killable.get_name(),
+                                action: "prune",
+                                signal: None,
+                                outcome: match &pruned {
+                                    Ok(_) => Outcome::Done,
+                                    Err(e) => Outcome::Failed(e.to_string()),
+                                },
+                            });
+                            results.extend(
+                                pruned?.into_iter().map(|(target_type, name)| {
+                                    HandledTarget::new(target_type, name)
+                                }),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        // Something may still hold the port, such as a child the kill missed
+        // or a supervisor restarting the target. Targets left alone on
+        // purpose do not count, and discovery runs as a dry run so that no
+        // question is asked twice
+        if !options.dry_run
+            && !results.is_empty()
+            && (options.wait.is_some() || options.signal.ends_process())
+        {
+            let verify_options = KillOptions {
+                dry_run: true,
+                ..options.clone()
+            };
+            let deadline = Instant::now()
+                + match options.wait {
+                    Some(_) => Duration::ZERO,
+                    None if shutting_down => GRACEFUL_VERIFY_GRACE,
+                    None => VERIFY_GRACE,
+                };
+            let started = Instant::now();
+            thread::sleep(VERIFY_DELAY);
+            loop {
+                let remaining = self
+                    .find_target_killables(port, &verify_options, &mut Diagnostics::default())?
+                    .into_iter()
+                    .find(|killable| {
+                        !diagnostics
+                            .skipped
+                            .contains(&(killable.get_type(), killable.get_name()))
+                    });
+                let Some(killable) = remaining else {
+                    timings::record(port, "check the port is free", started.elapsed());
+                    break;
+                };
+                if Instant::now() >= deadline {
+                    timings::record(port, "check the port is free", started.elapsed());
+                    return Err(Error::new(
+                        ErrorKind::AddrInUse,
+                        format!(
+                            "Port {} is still in use by {} '{}', which may have been restarted by a supervisor or spawned by a process the kill missed; see --stop-service, --kill-children and --kill-parents",
+                            port,
+                            killable.get_type(),
+                            killable.get_name()
+                        ),
+                    ));
+                }
+                thread::sleep(VERIFY_INTERVAL);
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+/// Finds the processes on `port` with the backend chosen in `options`.
+///
+/// In auto mode, processes the platform's own discovery could not inspect
+/// may still be listed by lsof, ss or netstat, e.g. when those are installed
+/// setuid.
+///
+/// # Arguments
+///
+/// * `port` - The port, possibly restricted to a protocol.
+/// * `options` - The options killport runs with.
+/// * `diagnostics` - Collects observations made during discovery.
+fn find_processes(
+    port: Port,
+    options: &KillOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<NativeProcess>, Error> {
+    if !options.backend.is_available() {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "The {} backend is not available on this platform",
+                options.backend
+            ),
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        let tool = match options.backend {
+            Backend::Lsof => Some(FallbackTool::Lsof),
+            Backend::Ss => Some(FallbackTool::Ss),
+            Backend::Netstat => Some(FallbackTool::Netstat),
+            _ => None,
+        };
+        if let Some(tool) = tool {
+            diagnostics.searched.push(format!(
+                "processes with a socket bound to port {}, as listed by {}",
+                port, tool
+            ));
+            return find_target_processes_with(tool, port);
+        }
+    }
+
+    diagnostics
+        .searched
+        .push(format!("processes with a socket bound to port {}", port));
+
+    #[cfg(target_os = "macos")]
+    if options.backend == Backend::PidWalk {
+        return crate::macos::walk_target_processes(port, diagnostics);
+    }
+
+    let processes = find_target_processes(port, diagnostics)?;
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "aix"))]
+    if options.backend == Backend::Auto
+        && processes.is_empty()
+        && !diagnostics.uninspected.is_empty()
+    {
+        if let Some(tool) = FallbackTool::installed() {
+            diagnostics.searched.push(format!(
+                "processes with a socket bound to port {}, as listed by {}",
+                port, tool
+            ));
+            match find_target_processes_with(tool, port) {
+                Ok(processes) => return Ok(processes),
+                Err(e) => debug!("The {} fallback backend failed: {}", tool, e),
+            }
+        }
+    }
+
+    Ok(processes)
+}
+
+/// Finds the processes holding a port, so that fake processes can stand in
+/// for them, e.g. in tests.
+pub trait ProcessDiscovery {
+    /// Finds the processes with a socket bound to `port`, through the backend
+    /// of `options`.
+    fn find_processes(
+        &self,
+        port: Port,
+        options: &KillOptions,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Box<dyn Killable>>, Error>;
+}
+
+/// The processes of this system, found through its native backends.
+pub struct NativeProcesses;
+
+impl ProcessDiscovery for NativeProcesses {
+    fn find_processes(
+        &self,
+        port: Port,
+        options: &KillOptions,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Box<dyn Killable>>, Error> {
+        Ok(find_processes(port, options, diagnostics)?
+            .into_iter()
+            .map(|process| Box::new(process) as Box<dyn Killable>)
+            .collect())
+    }
+}
+
+/// Finds the targets on a port among the processes of `P`, the containers
+/// of the Docker daemon `D` and those of containerd `C`.
+pub struct Killport<P = NativeProcesses, D = DockerApi, C = ContainerdCli> {
+    processes: P,
+    docker: D,
+    containerd: C,
+}
+
+impl Killport {
+    /// Returns a `Killport` acting on the processes and containers of this
+    /// system.
+    pub fn new() -> Self {
+        Killport::with(NativeProcesses, DockerApi, ContainerdCli)
+    }
+}
+
+impl Default for Killport {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-pub struct Killport;
+impl<P: ProcessDiscovery, D: DockerClient, C: ContainerdClient> Killport<P, D, C> {
+    /// Returns a `Killport` finding processes through `processes` and
+    /// containers through `docker` and `containerd`.
+    pub fn with(processes: P, docker: D, containerd: C) -> Self {
+        Killport {
+            processes,
+            docker,
+            containerd,
+        }
+    }
+}
 
-impl KillportOperations for Killport {
+impl<P: ProcessDiscovery, D: DockerClient, C: ContainerdClient> KillportOperations
+    for Killport<P, D, C>
+{
     /// Finds the killables (native processes and docker containers) associated with the specified `port`.
     ///
     /// Returns a `Vec` of killables.
     ///
     /// # Arguments
     ///
-    /// * `port` - A u16 value representing the port number.
+    /// * `port` - The port, possibly restricted to a protocol.
+    /// * `options` - The mode, determining if processes, containers, or both should be targeted, and how containers are matched.
+    /// * `diagnostics` - Collects observations made during discovery.
     fn find_target_killables(
         &self,
-        port: u16,
-        mode: Mode,
+        port: Port,
+        options: &KillOptions,
+        diagnostics: &mut Diagnostics,
     ) -> Result<Vec<Box<dyn Killable>>, Error> {
+        let mode = options.mode;
         let mut target_killables: Vec<Box<dyn Killable>> = vec![];
-        let docker_present = mode != Mode::Process && DockerContainer::is_docker_present()?;
+        let mut vm_forwarders = vec![];
 
-        if mode != Mode::Container {
-            let target_processes = find_target_processes(port)?;
+        let mut target_processes = if mode != Mode::Container {
+            timings::time(port, "find processes", || {
+                self.processes.find_processes(port, options, diagnostics)
+            })?
+        } else {
+            vec![]
+        };
+
+        // In auto mode Docker is only asked about the port when no process
+        // holds it, e.g. with the userland proxy disabled, or when one of the
+        // processes on it may belong to a container
+        let docker_needed = match mode {
+            Mode::Process => false,
+            Mode::Container => true,
+            Mode::Auto => {
+                options.internal_port
+                    || target_processes.is_empty()
+                    || target_processes.iter().any(|process| {
+                        let name = process.get_name().to_lowercase();
+                        process.get_container_id().is_some()
+                            || process.get_container_proxy().is_some()
+                            || CONTAINER_FORWARDERS
+                                .iter()
+                                .any(|forwarder| name.contains(forwarder))
+                            || DockerContainer::find_vm_forwarder(&name).is_some()
+                    })
+            }
+        };
+        if docker_needed && options.no_docker {
+            diagnostics
+                .unsearched
+                .push("Docker and containerd containers, as --no-docker was given".to_string());
+        }
+
+        // Containers run by containerd without Docker, e.g. through nerdctl
+        // or k3s, are reached through nerdctl, when Docker would be asked
+        let containerd_containers: Vec<ContainerdContainer> = if docker_needed && !options.no_docker
+        {
+            timings::time(port, "find containerd containers", || {
+                self.containerd
+                    .find_target_containers(port, &options.container_labels)
+            })
+        } else {
+            vec![]
+        };
+        let docker_present = !options.no_docker
+            && docker_needed
+            && timings::time(port, "probe docker", || {
+                self.docker.is_present(options.docker_timeout, diagnostics)
+            })?;
+        if docker_present {
+            diagnostics
+                .searched
+                .push(format!("Docker containers publishing port {}", port));
+        }
+
+        // Containers on the host network bind the port with their own
+        // processes, which the publish filter does not match
+        let host_network_containers = if docker_present {
+            timings::time(port, "find docker containers", || {
+                self.docker.find_host_network_containers(port)
+            })?
+        } else {
+            vec![]
+        };
+        let mut attributed_container_ids: Vec<String> = vec![];
+        let mut proxied_containers: Vec<DockerContainer> = vec![];
+        let mut port_has_processes = false;
+
+        if mode == Mode::Container && !host_network_containers.is_empty() {
+            target_processes = self.processes.find_processes(port, options, diagnostics)?;
+        }
+
+        if mode != Mode::Container || !host_network_containers.is_empty() {
+            port_has_processes = !target_processes.is_empty();
 
             for process in target_processes {
-                // Check if the process name contains 'docker' and skip if in docker mode
-                if docker_present && process.get_name().to_lowercase().contains("docker") {
+                // Processes of a container on the host network are acted on
+                // through their container
+                if let Some(container) = process.get_container_id().and_then(|id| {
+                    host_network_containers
+                        .iter()
+                        .find(|container| container.container.id == id)
+                }) {
+                    info!(
+                        "Process '{}' on port {} runs in container '{}' on the host network, which is targeted instead",
+                        process.get_name(),
+                        port,
+                        container.container.name
+                    );
+                    if !attributed_container_ids.contains(&container.container.id) {
+                        attributed_container_ids.push(container.container.id.clone());
+                    }
                     continue;
                 }
 
-                target_killables.push(Box::new(process));
+                if mode == Mode::Container {
+                    continue;
+                }
+
+                // Never kill the forwarder of a container VM, the container
+                // behind the forward is targeted instead
+                if let Some(forwarder) = DockerContainer::find_vm_forwarder(&process.get_name()) {
+                    vm_forwarders.push((process, forwarder));
+                    continue;
+                }
+
+                // A docker-proxy or containerd shim is acted on through its
+                // container, while one left behind by a container that is gone
+                // is killed to free the port
+                if let Some(proxy) = process.get_container_proxy().filter(|_| docker_present) {
+                    match self.docker.find_proxied_container(&proxy, port)? {
+                        Some(container) => {
+                            info!(
+                                "Process '{}' on port {} is the {} of container '{}', which is targeted instead",
+                                process.get_name(),
+                                port,
+                                proxy,
+                                container.name
+                            );
+                            proxied_containers.push(container);
+                            continue;
+                        }
+                        None => debug!(
+                            "No running container matches the {} on port {}",
+                            proxy, port
+                        ),
+                    }
+                }
+
+                // Rootless containerd publishes ports through rootlessport,
+                // which forwards every published port
+                if !containerd_containers.is_empty()
+                    && ["containerd", "rootlessport"]
+                        .iter()
+                        .any(|name| process.get_name().to_lowercase().contains(name))
+                {
+                    continue;
+                }
+
+                target_killables.push(process);
             }
         }
 
-        // Add containers if Docker is present and mode is not set to only process
+        // Add containers if Docker is present and mode is not set to only process.
+        // Docker containers are told apart by ID, as names may collide across
+        // runtimes, containerd containers by name
+        let mut found_containers: Vec<String> = vec![];
         if docker_present && mode != Mode::Process {
-            let target_containers = DockerContainer::find_target_containers(port)?; // Assume this function returns Result<Vec<DockerContainer>, Error>
+            let mut target_containers = timings::time(port, "find docker containers", || {
+                self.docker.find_target_containers(port, None)
+            })?;
+            if options.internal_port {
+                target_containers.extend(self.docker.find_containers_by_internal_port(port)?);
+            }
+            target_containers.extend(proxied_containers);
+
+            for container in merge_containers(target_containers)
+                .into_iter()
+                .filter(|container| matches_label_filters(container, &options.container_labels))
+            {
+                if !found_containers.contains(&container.id) {
+                    found_containers.push(container.id.clone());
+                    if is_spared_node(&container, port, options, diagnostics) {
+                        continue;
+                    }
 
-            for container in target_containers {
+                    // The infra container of a podman pod holds the ports of
+                    // every container in the pod
+                    match PodmanPod::find_by_infra_container(&container.name, &container.id) {
+                        Some(pod) if options.stop_pod => {
+                            if !found_containers.contains(&pod.name) {
+                                found_containers.push(pod.name.clone());
+                                target_killables.push(Box::new(pod));
+                            }
+                        }
+                        Some(pod) => {
+                            warn!(
+                                "Container '{}' is the infra container of podman pod '{}', whose other containers keep running; pass --stop-pod to act on the whole pod",
+                                container.name, pod.name
+                            );
+                            target_killables.push(Box::new(container));
+                        }
+                        None => target_killables.push(Box::new(container)),
+                    }
+                }
+            }
+        }
+
+        // Without a process on the port, the listener may run in a VM, so
+        // containers declaring the port as exposed are taken at their word
+        for host_network_container in host_network_containers {
+            let container = host_network_container.container;
+            if (attributed_container_ids.contains(&container.id)
+                || (host_network_container.exposes_port && !port_has_processes))
+                && !found_containers.contains(&container.id)
+                && matches_label_filters(&container, &options.container_labels)
+            {
+                found_containers.push(container.id.clone());
                 target_killables.push(Box::new(container));
             }
         }
 
-        Ok(target_killables)
-    }
+        for container in containerd_containers {
+            if !found_containers.contains(&container.name) {
+                found_containers.push(container.name.clone());
+                target_killables.push(Box::new(container));
+            }
+        }
 
-    /// Manages the action of killing or simulating the killing of services by port.
-    /// This function can either actually kill processes or containers, or simulate the action based on the `dry_run` flag.
-    ///
-    /// # Arguments
-    /// * `port` - The port number to check for killable entities.
-    /// * `signal` - The signal to send if not simulating.
-    /// * `mode` - The mode of operation, determining if processes, containers, or both should be targeted.
-    /// * `dry_run` - If true, simulates the actions without actually killing any entities.
-    ///
-    /// # Returns
-    /// * `Result<Vec<(String, String)>, Error>` - A list of killable entities or an error.
-    fn kill_service_by_port(
-        &self,
-        port: u16,
-        signal: KillportSignal,
-        mode: Mode,
-        dry_run: bool,
-    ) -> Result<Vec<(KillableType, String)>, Error> {
-        let mut results = Vec::new();
-        let target_killables = self.find_target_killables(port, mode)?; // Use the existing function to find targets
+        // Containers of other VMs are reached through their own runtime socket
+        if mode != Mode::Process && !options.no_docker {
+            for (_, forwarder) in &vm_forwarders {
+                for socket in &forwarder.sockets {
+                    match self.docker.find_target_containers(port, Some(socket)) {
+                        Ok(target_containers) => {
+                            for container in merge_containers(target_containers).into_iter().filter(
+                                |container| {
+                                    matches_label_filters(container, &options.container_labels)
+                                },
+                            ) {
+                                if !found_containers.contains(&container.id) {
+                                    found_containers.push(container.id.clone());
+                                    if !is_spared_node(&container, port, options, diagnostics) {
+                                        target_killables.push(Box::new(container));
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => debug!(
+                            "Could not list the containers of {} through {}: {}",
+                            forwarder.runtime,
+                            socket.display(),
+                            e
+                        ),
+                    }
+                }
+            }
+        }
 
-        for killable in target_killables {
-            if dry_run {
-                // In dry-run mode, collect information about the entity without killing
-                results.push((killable.get_type(), killable.get_name()));
-            } else {
-                // In actual mode, attempt to kill the entity and collect its information if successful
-                if killable.kill(signal.clone())? {
-                    results.push((killable.get_type(), killable.get_name()));
+        if found_containers.is_empty() {
+            for (process, forwarder) in vm_forwarders {
+                let rail = format!(
+                    "Port {} is forwarded to a container by {} ('{}'); killing it would stop every container of its VM",
+                    port,
+                    forwarder.runtime,
+                    process.get_name()
+                );
+                if acknowledged(&rail, options) {
+                    target_killables.push(process);
+                    continue;
                 }
+
+                warn!(
+                    "{}, so it is left alone; {}",
+                    rail,
+                    if mode == Mode::Process {
+                        "run killport in auto or container mode to kill the container instead"
+                    } else {
+                        "no running container could be found publishing this port"
+                    }
+                );
+                diagnostics
+                    .skipped
+                    .push((process.get_type(), process.get_name()));
             }
         }
 
-        Ok(results)
+        // Discovery goes through hash maps on some platforms
+        sort_targets(&mut target_killables);
+
+        Ok(target_killables)
     }
 }
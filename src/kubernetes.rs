@@ -0,0 +1,319 @@
+use crate::service::run_command;
+use log::debug;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Error;
+
+/// Options of kubectl that take an argument, which may be passed as the next
+/// word rather than after `=`.
+const OPTIONS_WITH_ARGUMENT: [&str; 14] = [
+    "-n",
+    "--namespace",
+    "--context",
+    "--kubeconfig",
+    "--cluster",
+    "--user",
+    "-s",
+    "--server",
+    "--token",
+    "--as",
+    "--as-group",
+    "--address",
+    "--pod-running-timeout",
+    "--request-timeout",
+];
+
+/// Container labels identifying the node containers of local Kubernetes
+/// distributions, by distribution.
+const NODE_LABELS: [(&str, &str); 3] = [
+    ("kind", "io.x-k8s.kind.cluster"),
+    ("k3d", "k3d.cluster"),
+    ("minikube", "name.minikube.sigs.k8s.io"),
+];
+
+/// A container running a node of a local Kubernetes cluster, such as those
+/// of kind, k3d and minikube. Its published ports lead to the workloads of
+/// the cluster rather than to the container itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterNode {
+    /// The distribution running the cluster, e.g. "kind".
+    pub distribution: &'static str,
+    /// Name of the cluster, or the minikube profile.
+    pub cluster: String,
+    /// Port inside the node the target port is published to.
+    pub node_port: Option<u16>,
+}
+
+impl ClusterNode {
+    /// Recognizes a node container by its labels.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - The labels of the container.
+    /// * `node_port` - Port inside the node the target port is published to.
+    pub fn from_labels(labels: &HashMap<String, String>, node_port: Option<u16>) -> Option<Self> {
+        NODE_LABELS
+            .iter()
+            .find_map(|(distribution, label)| {
+                labels.get(*label).map(|cluster| (*distribution, cluster))
+            })
+            .map(|(distribution, cluster)| ClusterNode {
+                distribution,
+                cluster: cluster.to_string(),
+                node_port,
+            })
+    }
+
+    /// Returns the kubectl context the distribution creates for the cluster.
+    pub fn context(&self) -> String {
+        match self.distribution {
+            "minikube" => self.cluster.to_string(),
+            distribution => format!("{}-{}", distribution, self.cluster),
+        }
+    }
+
+    /// Describes the workload the port leads to, e.g. "NodePort service
+    /// prod/web", by looking up the services of the cluster through kubectl.
+    pub fn find_workload(&self) -> Option<String> {
+        let node_port = self.node_port?;
+        let output = match run_command(
+            "kubectl",
+            &[
+                "--context",
+                &self.context(),
+                "get",
+                "services",
+                "--all-namespaces",
+                "--output",
+                "json",
+            ],
+        ) {
+            Ok(output) => output,
+            Err(e) => {
+                debug!("Failed to list the services of {}: {}", self, e);
+                return None;
+            }
+        };
+        let services: serde_json::Value = serde_json::from_str(&output).ok()?;
+
+        find_service_by_port(&services, node_port)
+    }
+}
+
+impl fmt::Display for ClusterNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} cluster '{}'", self.distribution, self.cluster)
+    }
+}
+
+/// Describes the service of a `kubectl get services --output json` listing
+/// that serves `node_port`, either as its node port or as the port of a
+/// load balancer, which k3d and minikube implement on the node.
+///
+/// # Arguments
+///
+/// * `services` - The parsed service listing.
+/// * `node_port` - Port inside the node.
+pub fn find_service_by_port(services: &serde_json::Value, node_port: u16) -> Option<String> {
+    let items = services["items"].as_array()?;
+    let name = |service: &serde_json::Value| {
+        format!(
+            "{}/{}",
+            service["metadata"]["namespace"]
+                .as_str()
+                .unwrap_or("default"),
+            service["metadata"]["name"].as_str().unwrap_or_default()
+        )
+    };
+    let has_port = |service: &serde_json::Value, field: &str| {
+        service["spec"]["ports"]
+            .as_array()
+            .is_some_and(|ports| ports.iter().any(|port| port[field] == node_port))
+    };
+
+    if let Some(service) = items.iter().find(|service| has_port(service, "nodePort")) {
+        return Some(format!("NodePort service {}", name(service)));
+    }
+
+    items
+        .iter()
+        .find(|service| service["spec"]["type"] == "LoadBalancer" && has_port(service, "port"))
+        .map(|service| format!("LoadBalancer service {}", name(service)))
+}
+
+/// A Kubernetes pod, either running the target or receiving the connections
+/// it forwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pod {
+    pub name: String,
+    /// Namespace of the pod, or `None` for the default namespace of the
+    /// kubectl context.
+    pub namespace: Option<String>,
+    /// kubectl context the pod was reached through, or `None` for the
+    /// current context.
+    pub context: Option<String>,
+    /// Whether the target forwards a port to the pod rather than running in
+    /// it.
+    pub forwarded: bool,
+}
+
+impl Pod {
+    /// Deletes the pod through kubectl.
+    pub fn delete(&self) -> Result<(), Error> {
+        let mut args = vec![];
+        if let Some(context) = &self.context {
+            args.extend(["--context", context]);
+        }
+        if let Some(namespace) = &self.namespace {
+            args.extend(["--namespace", namespace]);
+        }
+        args.extend(["delete", "pod", &self.name]);
+        run_command("kubectl", &args)?;
+
+        Ok(())
+    }
+
+    /// Finds the pod running the container with the given ID, through the
+    /// labels the kubelet gives its containers.
+    ///
+    /// Returns `None` when crictl is not installed or does not know the
+    /// container.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_id` - Full ID of the container.
+    pub fn find_by_container(container_id: &str) -> Option<Self> {
+        let output = match run_command("crictl", &["inspect", "--output", "json", container_id]) {
+            Ok(output) => output,
+            Err(e) => {
+                debug!("Failed to inspect container {}: {}", container_id, e);
+                return None;
+            }
+        };
+        let inspection: serde_json::Value = serde_json::from_str(&output).ok()?;
+        let labels = &inspection["status"]["labels"];
+
+        Some(Pod {
+            name: labels["io.kubernetes.pod.name"].as_str()?.to_string(),
+            namespace: labels["io.kubernetes.pod.namespace"]
+                .as_str()
+                .map(str::to_string),
+            context: None,
+            forwarded: false,
+        })
+    }
+}
+
+impl fmt::Display for Pod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.namespace {
+            Some(namespace) => write!(f, "{}/{}", namespace, self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// A `kubectl port-forward` listening on the target port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortForward {
+    /// The forwarded resource as given to kubectl, e.g. "pod/web-0" or
+    /// "svc/web".
+    pub resource: String,
+    /// Namespace of the resource, or `None` for the default namespace of
+    /// the kubectl context.
+    pub namespace: Option<String>,
+    /// kubectl context of the forward, or `None` for the current context.
+    pub context: Option<String>,
+}
+
+impl PortForward {
+    /// Describes the forward, e.g. "kubectl port-forward to pod/web-0 in
+    /// namespace prod".
+    pub fn describe(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!(
+                "kubectl port-forward to {} in namespace {}",
+                self.resource, namespace
+            ),
+            None => format!("kubectl port-forward to {}", self.resource),
+        }
+    }
+
+    /// Returns the pod connections are forwarded to. Forwards to services
+    /// and workloads pick one of their pods, which is not known here.
+    pub fn pod(&self) -> Option<Pod> {
+        let name = match self.resource.split_once('/') {
+            Some(("pod" | "pods" | "po", name)) => name,
+            Some(_) => return None,
+            None => &self.resource,
+        };
+
+        Some(Pod {
+            name: name.to_string(),
+            namespace: self.namespace.clone(),
+            context: self.context.clone(),
+            forwarded: true,
+        })
+    }
+}
+
+/// Parses the command line of a `kubectl port-forward` process listening on
+/// `port`.
+///
+/// # Arguments
+///
+/// * `args` - The command line of the process.
+/// * `port` - The port the process listens on.
+pub fn parse_port_forward(args: &[String], port: u16) -> Option<PortForward> {
+    let program = args.first()?.rsplit(['/', '\\']).next()?;
+    if program != "kubectl" && program != "kubectl.exe" {
+        return None;
+    }
+
+    let mut namespace = None;
+    let mut context = None;
+    let mut positional: Vec<&str> = vec![];
+
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        if !arg.starts_with('-') {
+            positional.push(arg);
+            continue;
+        }
+
+        let (option, value) = match arg.split_once('=') {
+            Some((option, value)) => (option, Some(value.to_string())),
+            None if OPTIONS_WITH_ARGUMENT.contains(&arg.as_str()) => {
+                (arg.as_str(), rest.next().cloned())
+            }
+            None => (arg.as_str(), None),
+        };
+        match option {
+            "-n" | "--namespace" => namespace = value,
+            "--context" => context = value,
+            _ => {}
+        }
+    }
+
+    let [subcommand, resource, ports @ ..] = positional.as_slice() else {
+        return None;
+    };
+    if *subcommand != "port-forward" {
+        return None;
+    }
+
+    // Ports are given as LOCAL:REMOTE, PORT, or :REMOTE for a random local port
+    let forwards_port = ports.iter().any(|spec| {
+        let local = spec.split_once(':').map_or(*spec, |(local, _)| local);
+        local.is_empty() || local.parse::<u16>().ok() == Some(port)
+    });
+    if !forwards_port {
+        return None;
+    }
+
+    Some(PortForward {
+        resource: resource.to_string(),
+        namespace,
+        context,
+    })
+}
@@ -1,14 +1,34 @@
+pub mod action_log;
 pub mod cli;
+pub mod confirm;
+pub mod containerd;
 pub mod docker;
+pub mod docker_cli;
+pub mod elevate;
 pub mod killport;
+pub mod kubernetes;
+pub mod podman;
+pub mod service;
 pub mod signal;
+pub mod socket;
+pub mod summary;
+pub mod timings;
 
+#[cfg(unix)]
+pub mod fallback;
 #[cfg(unix)]
 pub mod unix;
 
+#[cfg(all(target_os = "aix", feature = "aix"))]
+pub mod aix;
+#[cfg(all(target_os = "linux", feature = "ebpf"))]
+pub mod ebpf;
 #[cfg(target_os = "linux")]
 pub mod linux;
 #[cfg(target_os = "macos")]
 pub mod macos;
 #[cfg(target_os = "windows")]
 pub mod windows;
+
+#[cfg(all(target_os = "aix", not(feature = "aix")))]
+compile_error!("AIX support is best effort and has to be enabled with `--features aix`");
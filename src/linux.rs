@@ -1,28 +1,54 @@
-use crate::unix::UnixProcess;
+use crate::cli::Port;
+use crate::docker::parse_container_proxy;
+use crate::killport::{descendants, Diagnostics, KillableType};
+use crate::kubernetes::{parse_port_forward, Pod};
+use crate::service::{run_command, systemctl, Service, ServiceKind};
+use crate::socket::{KernelSocket, OrphanSocket, Protocol, SocketState};
+use crate::unix::{describe_ssh_forward, UnixProcess};
 
-use log::debug;
+use log::{debug, log_enabled, warn, Level};
 use nix::unistd::Pid;
+use procfs::net::{TcpState, UdpState};
 use procfs::process::FDTarget;
-use std::io::Error;
+use procfs::ProcError;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Error,
+    path::Path,
+    time::Duration,
+};
 
 /// Finds the inodes associated with the specified `port`.
 ///
-/// Returns a `Vec` of inodes for both IPv4 and IPv6 connections.
+/// Returns a map from inode to protocol and socket table, e.g. "tcp6", for
+/// both IPv4 and IPv6 TCP and UDP sockets and SCTP sockets, or only those of
+/// the protocol the port is restricted to. Raw sockets are left out, as they
+/// have no port: their tables report the IP protocol number in its place.
+/// Sockets that are no longer attached to an inode are recorded in
+/// `diagnostics` instead: listeners without an inode belong to the kernel,
+/// anything else is a connection left behind by its process (e.g. `TIME_WAIT`).
 ///
 /// # Arguments
 ///
-/// * `port` - A u16 value representing the port number.
-fn find_target_inodes(port: u16) -> Vec<u64> {
+/// * `port` - The port, possibly restricted to a protocol.
+/// * `diagnostics` - Collects the sockets without an owner.
+fn find_target_inodes(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> HashMap<u64, (Protocol, &'static str)> {
     let tcp = procfs::net::tcp();
     let tcp6 = procfs::net::tcp6();
     let udp = procfs::net::udp();
     let udp6 = procfs::net::udp6();
-    let mut target_inodes = Vec::new();
+    let mut target_inodes = HashMap::new();
 
     trait NetEntry {
         fn local_address(&self) -> std::net::SocketAddr;
 
         fn inode(&self) -> u64;
+
+        fn state(&self) -> SocketState;
     }
 
     impl NetEntry for procfs::net::TcpNetEntry {
@@ -33,6 +59,22 @@ fn find_target_inodes(port: u16) -> Vec<u64> {
         fn inode(&self) -> u64 {
             self.inode
         }
+
+        fn state(&self) -> SocketState {
+            match self.state {
+                TcpState::Established => SocketState::Established,
+                TcpState::SynSent => SocketState::SynSent,
+                TcpState::SynRecv | TcpState::NewSynRecv => SocketState::SynRecv,
+                TcpState::FinWait1 => SocketState::FinWait1,
+                TcpState::FinWait2 => SocketState::FinWait2,
+                TcpState::TimeWait => SocketState::TimeWait,
+                TcpState::Close => SocketState::Close,
+                TcpState::CloseWait => SocketState::CloseWait,
+                TcpState::LastAck => SocketState::LastAck,
+                TcpState::Listen => SocketState::Listen,
+                TcpState::Closing => SocketState::Closing,
+            }
+        }
     }
 
     impl NetEntry for procfs::net::UdpNetEntry {
@@ -43,66 +85,876 @@ fn find_target_inodes(port: u16) -> Vec<u64> {
         fn inode(&self) -> u64 {
             self.inode
         }
+
+        fn state(&self) -> SocketState {
+            match self.state {
+                UdpState::Established => SocketState::Established,
+                UdpState::Close => SocketState::Close,
+            }
+        }
     }
 
     fn add_matching_inodes<T: NetEntry>(
-        target_inodes: &mut Vec<u64>,
+        target_inodes: &mut HashMap<u64, (Protocol, &'static str)>,
+        diagnostics: &mut Diagnostics,
         net_entries: procfs::ProcResult<Vec<T>>,
-        port: u16,
+        (protocol, table): (Protocol, &'static str),
+        port: Port,
     ) {
+        if !port.carries(protocol) {
+            return;
+        }
+
         if let Ok(net_entries) = net_entries {
-            target_inodes.extend(
-                net_entries
-                    .into_iter()
-                    .filter(move |net_entry| net_entry.local_address().port() == port)
-                    .map(|net_entry| net_entry.inode()),
-            );
+            for net_entry in net_entries {
+                if net_entry.local_address().port() != port.number() {
+                    continue;
+                }
+
+                let state = net_entry.state();
+
+                if net_entry.inode() != 0 {
+                    target_inodes.insert(net_entry.inode(), (protocol, table));
+                } else if state == SocketState::Listen
+                    || (protocol == Protocol::Udp && state == SocketState::Close)
+                {
+                    diagnostics.kernel_sockets.push(KernelSocket {
+                        protocol,
+                        subsystem: kernel_subsystem(protocol, port.number()),
+                    });
+                } else {
+                    diagnostics.orphans.push(OrphanSocket {
+                        protocol,
+                        state,
+                        remaining: None,
+                    });
+                }
+            }
         }
     }
 
-    add_matching_inodes(&mut target_inodes, tcp, port);
-    add_matching_inodes(&mut target_inodes, tcp6, port);
-    add_matching_inodes(&mut target_inodes, udp, port);
-    add_matching_inodes(&mut target_inodes, udp6, port);
+    add_matching_inodes(
+        &mut target_inodes,
+        diagnostics,
+        tcp,
+        (Protocol::Tcp, "tcp"),
+        port,
+    );
+    add_matching_inodes(
+        &mut target_inodes,
+        diagnostics,
+        tcp6,
+        (Protocol::Tcp, "tcp6"),
+        port,
+    );
+    add_matching_inodes(
+        &mut target_inodes,
+        diagnostics,
+        udp,
+        (Protocol::Udp, "udp"),
+        port,
+    );
+    add_matching_inodes(
+        &mut target_inodes,
+        diagnostics,
+        udp6,
+        (Protocol::Udp, "udp6"),
+        port,
+    );
+
+    if port.carries(Protocol::Sctp) {
+        for inode in sctp_inodes(port.number()) {
+            target_inodes.insert(inode, (Protocol::Sctp, "sctp"));
+        }
+    }
+
+    if diagnostics
+        .orphans
+        .iter()
+        .any(|orphan| orphan.state == SocketState::TimeWait)
+    {
+        let remaining = time_wait_remaining(port.number());
+        diagnostics
+            .orphans
+            .iter_mut()
+            .filter(|orphan| orphan.state == SocketState::TimeWait)
+            .for_each(|orphan| orphan.remaining = remaining);
+    }
 
     target_inodes
 }
 
+/// Finds the inodes of the SCTP endpoints and associations bound to `port`.
+///
+/// # Arguments
+///
+/// * `port` - A u16 value representing the port number.
+fn sctp_inodes(port: u16) -> Vec<u64> {
+    let mut inodes = Vec::new();
+
+    for (table, _, _) in SCTP_TABLES {
+        // The tables only exist once the sctp module is loaded
+        let Ok(contents) = fs::read_to_string(table) else {
+            continue;
+        };
+
+        inodes.extend(parse_sctp_table(table, &contents, port));
+    }
+
+    inodes
+}
+
+/// The SCTP tables, with the column positions of the local port and inode.
+const SCTP_TABLES: [(&str, usize, usize); 2] = [
+    // ENDPT SOCK STY SST HBKT LPORT UID INODE LADDRS
+    ("/proc/net/sctp/eps", 5, 7),
+    // ASSOC SOCK STY SST ST HBKT ASSOC-ID TX_QUEUE RX_QUEUE UID INODE LPORT ...
+    ("/proc/net/sctp/assocs", 11, 10),
+];
+
+/// Parses the inodes of the SCTP sockets bound to `port` out of one of the
+/// SCTP tables.
+///
+/// # Arguments
+///
+/// * `table` - The path of the table, e.g. "/proc/net/sctp/eps".
+/// * `contents` - The contents of the table.
+/// * `port` - A u16 value representing the port number.
+pub fn parse_sctp_table(table: &str, contents: &str, port: u16) -> Vec<u64> {
+    let Some((_, port_column, inode_column)) =
+        SCTP_TABLES.into_iter().find(|(path, _, _)| *path == table)
+    else {
+        return vec![];
+    };
+
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let local_port = fields.get(port_column)?.parse::<u16>().ok()?;
+            let inode = fields.get(inode_column)?.parse::<u64>().ok()?;
+
+            (local_port == port && inode != 0).then_some(inode)
+        })
+        .collect()
+}
+
+/// Returns the longest time any `TIME_WAIT` socket on `port` still has to
+/// live, read from the timer column of the kernel TCP tables.
+///
+/// # Arguments
+///
+/// * `port` - A u16 value representing the port number.
+fn time_wait_remaining(port: u16) -> Option<Duration> {
+    const TIME_WAIT: &str = "06";
+
+    let ticks_per_second = procfs::ticks_per_second();
+    let mut remaining: Option<Duration> = None;
+
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(contents) = fs::read_to_string(table) else {
+            continue;
+        };
+
+        for line in contents.lines().skip(1) {
+            // sl local_address rem_address st tx_queue:rx_queue tr:tm->when ...
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || fields[3] != TIME_WAIT {
+                continue;
+            }
+
+            let local_port = fields[1]
+                .rsplit(':')
+                .next()
+                .and_then(|value| u16::from_str_radix(value, 16).ok());
+            if local_port != Some(port) {
+                continue;
+            }
+
+            let ticks = fields[5]
+                .split(':')
+                .nth(1)
+                .and_then(|value| u64::from_str_radix(value, 16).ok());
+            if let Some(ticks) = ticks {
+                let timeout = Duration::from_millis(ticks * 1000 / ticks_per_second);
+                remaining = remaining.max(Some(timeout));
+            }
+        }
+    }
+
+    remaining
+}
+
+/// Guesses which kernel subsystem holds a socket on `port`.
+///
+/// Kernel sockets carry no owner information, so this relies on well-known
+/// ports and on which modules are loaded.
+///
+/// # Arguments
+///
+/// * `protocol` - The protocol of the socket.
+/// * `port` - A u16 value representing the port number.
+fn kernel_subsystem(protocol: Protocol, port: u16) -> Option<&'static str> {
+    let sysctl_port = |name: &str| {
+        fs::read_to_string(format!("/proc/sys/fs/nfs/{}", name))
+            .ok()
+            .and_then(|value| value.trim().parse::<u16>().ok())
+    };
+    let module_loaded = |name: &str| Path::new("/sys/module").join(name).exists();
+
+    match (protocol, port) {
+        (_, 2049) => Some("NFS server (nfsd)"),
+        (Protocol::Tcp, _) if sysctl_port("nlm_tcpport") == Some(port) => {
+            Some("NFS lock manager (lockd)")
+        }
+        (Protocol::Udp, _) if sysctl_port("nlm_udpport") == Some(port) => {
+            Some("NFS lock manager (lockd)")
+        }
+        (Protocol::Tcp, 445) if module_loaded("ksmbd") => Some("SMB server (ksmbd)"),
+        (Protocol::Udp, 4789 | 8472) => Some("VXLAN tunnel"),
+        (Protocol::Udp, 6081) => Some("Geneve tunnel"),
+        (Protocol::Udp, 51820) => Some("WireGuard"),
+        (Protocol::Udp, _)
+            if module_loaded("wireguard") && wireguard_listen_ports().contains(&port) =>
+        {
+            Some("WireGuard")
+        }
+        _ => None,
+    }
+}
+
+/// Returns the ports the configured WireGuard interfaces listen on, or none
+/// when `wg` is missing or may not read them (it needs CAP_NET_ADMIN).
+fn wireguard_listen_ports() -> Vec<u16> {
+    run_command("wg", &["show", "all", "listen-port"])
+        .map(|output| parse_wireguard_listen_ports(&output))
+        .unwrap_or_default()
+}
+
+/// Parses the output of `wg show all listen-port`, one `interface port` line
+/// per interface, e.g. `wg0\t51820`. Interfaces that do not listen report
+/// port 0 and are left out.
+///
+/// # Arguments
+///
+/// * `output` - The output of the command.
+pub fn parse_wireguard_listen_ports(output: &str) -> Vec<u16> {
+    output
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1)?.parse().ok())
+        .filter(|&port| port != 0)
+        .collect()
+}
+
+/// Names of the Linux capabilities, indexed by capability number.
+const CAPABILITY_NAMES: [&str; 41] = [
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+/// Formats a capability set as a list of capability names.
+///
+/// # Arguments
+///
+/// * `set` - The capability bitmask as found in `/proc/<pid>/status`.
+fn format_capabilities(set: u64) -> String {
+    if set == 0 {
+        return "none".to_string();
+    }
+
+    let all = (1u64 << CAPABILITY_NAMES.len()) - 1;
+    if set & all == all {
+        return "all".to_string();
+    }
+
+    (0..64)
+        .filter(|bit| set & (1 << bit) != 0)
+        .map(|bit| {
+            CAPABILITY_NAMES
+                .get(bit)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| format!("CAP_{}", bit))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Logs the LSM label (SELinux or AppArmor) and the capabilities of a
+/// process, which explain why a kill can fail even when running as root.
+///
+/// # Arguments
+///
+/// * `process` - The process to describe.
+fn log_security_context(process: &procfs::process::Process) {
+    let pid = process.pid;
+
+    // AppArmor exposes its own attribute directory on newer kernels
+    let label = ["attr/apparmor/current", "attr/current"]
+        .iter()
+        .find_map(|attr| fs::read_to_string(format!("/proc/{}/{}", pid, attr)).ok())
+        .map(|label| label.trim_end_matches(['\0', '\n']).to_string())
+        .filter(|label| !label.is_empty());
+
+    if let Some(label) = label {
+        let lsm = if Path::new("/sys/fs/selinux").exists() {
+            "SELinux"
+        } else if Path::new("/sys/module/apparmor").exists() {
+            "AppArmor"
+        } else {
+            "LSM"
+        };
+        debug!("Process with PID {} has {} label '{}'", pid, lsm, label);
+    }
+
+    if let Ok(status) = process.status() {
+        debug!(
+            "Process with PID {} has effective capabilities: {}",
+            pid,
+            format_capabilities(status.capeff)
+        );
+        debug!(
+            "Process with PID {} has permitted capabilities: {}",
+            pid,
+            format_capabilities(status.capprm)
+        );
+        if let Some(capbnd) = status.capbnd {
+            debug!(
+                "Process with PID {} has bounding capabilities: {}",
+                pid,
+                format_capabilities(capbnd)
+            );
+        }
+    }
+}
+
+/// Finds the service supervising a process: the snap or flatpak sandbox it
+/// is confined in, or else the systemd unit (system or `--user`) it runs in.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+fn find_service(pid: i32) -> Option<Service> {
+    // Each line is `hierarchy-ID:controllers:path`
+    let cgroups = fs::read_to_string(format!("/proc/{}/cgroup", pid)).unwrap_or_default();
+    let paths: Vec<&str> = cgroups
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .collect();
+
+    find_sandbox(pid, &paths).or_else(|| paths.iter().find_map(|path| find_systemd_unit(path)))
+}
+
+/// Finds the snap or flatpak sandbox a process is confined in, from its
+/// cgroups or, for flatpak, the `/.flatpak-info` file mounted into it.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+/// * `cgroups` - The cgroup paths of the process.
+fn find_sandbox(pid: i32, cgroups: &[&str]) -> Option<Service> {
+    if let Some(service) = cgroups.iter().find_map(|path| find_sandbox_unit(path)) {
+        return Some(service);
+    }
+
+    // Fields are `ID parent-ID major:minor root mount-point ...`
+    let mountinfo = fs::read_to_string(format!("/proc/{}/mountinfo", pid)).ok()?;
+    let sandboxed = mountinfo
+        .lines()
+        .any(|line| line.split_whitespace().nth(4) == Some("/.flatpak-info"));
+    if !sandboxed {
+        return None;
+    }
+
+    let info = fs::read_to_string(format!("/proc/{}/root/.flatpak-info", pid)).ok()?;
+    info.lines()
+        .find_map(|line| line.strip_prefix("name="))
+        .map(|app_id| Service::new(ServiceKind::Flatpak, app_id.trim().to_string()))
+}
+
+/// Returns the ID of the container the process `pid` runs in, read from its
+/// cgroup.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn container_id(pid: Pid) -> Option<String> {
+    let cgroups = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+
+    cgroups
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .find_map(find_container_id)
+}
+
+/// Returns the ID of the Kubernetes container a process runs in, whose
+/// cgroup lives below the `kubepods` hierarchy of the kubelet.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+fn pod_container_id(pid: i32) -> Option<String> {
+    let cgroups = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+
+    cgroups
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .filter(|cgroup| cgroup.contains("kubepods"))
+        .find_map(find_container_id)
+}
+
+/// Finds the ID of the container a cgroup path belongs to, such as
+/// `/system.slice/docker-<id>.scope` with the systemd cgroup driver or
+/// `/docker/<id>` with cgroupfs.
+///
+/// # Arguments
+///
+/// * `cgroup` - The cgroup path of the process.
+pub fn find_container_id(cgroup: &str) -> Option<String> {
+    cgroup
+        .split(['/', '-', '.'])
+        .find(|segment| segment.len() == 64 && segment.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(str::to_string)
+}
+
+/// Finds the snap service or flatpak application a cgroup path belongs to.
+///
+/// Snapd runs the services of a snap in `snap.<snap>.<app>.service` units,
+/// and flatpak its applications in `app-flatpak-<app-id>-<n>.scope` units.
+/// Plain snap applications run in `snap.<snap>.<app>-<uuid>.scope` units
+/// that snapd cannot stop on their own, so they yield `None` and are killed
+/// as processes.
+///
+/// # Arguments
+///
+/// * `cgroup` - The cgroup path of the process.
+pub fn find_sandbox_unit(cgroup: &str) -> Option<Service> {
+    cgroup.split('/').find_map(|segment| {
+        if let Some(app) = segment
+            .strip_prefix("snap.")
+            .and_then(|unit| unit.strip_suffix(".service"))
+        {
+            return Some(Service::new(ServiceKind::Snap, app.to_string()));
+        }
+
+        let scope = segment
+            .strip_prefix("app-flatpak-")
+            .and_then(|scope| scope.strip_suffix(".scope"))?;
+        let (app_id, _) = scope.rsplit_once('-')?;
+        Some(Service::new(ServiceKind::Flatpak, app_id.to_string()))
+    })
+}
+
+/// Finds the systemd service unit a cgroup path belongs to.
+///
+/// System units live under `system.slice`, while units of a `systemd --user`
+/// instance are nested below its `user@<uid>.service` manager unit. Processes
+/// in scopes (login sessions, terminal apps) are not supervised and yield
+/// `None`.
+///
+/// # Arguments
+///
+/// * `cgroup` - The cgroup path of the process.
+pub fn find_systemd_unit(cgroup: &str) -> Option<Service> {
+    let segments: Vec<&str> = cgroup.split('/').collect();
+    let is_user_manager =
+        |segment: &str| segment.starts_with("user@") && segment.ends_with(".service");
+
+    let unit = segments
+        .iter()
+        .rev()
+        .find(|segment| segment.ends_with(".service") && !is_user_manager(segment))?;
+
+    match segments.iter().find(|segment| is_user_manager(segment)) {
+        Some(manager) => {
+            let uid = manager
+                .trim_start_matches("user@")
+                .trim_end_matches(".service")
+                .parse::<u32>()
+                .ok()?;
+            Some(Service::new(
+                ServiceKind::SystemdUser(uid),
+                unit.to_string(),
+            ))
+        }
+        None => Some(Service::new(ServiceKind::Systemd, unit.to_string())),
+    }
+}
+
+/// Checks whether a process is a systemd service manager.
+///
+/// Returns `Some(None)` for the system manager (PID 1) and `Some(Some(uid))`
+/// for the `systemd --user` instance of a user.
+///
+/// # Arguments
+///
+/// * `process` - The process to check.
+fn systemd_manager(process: &procfs::process::Process) -> Option<Option<u32>> {
+    let stat = process.stat().ok()?;
+    if stat.comm != "systemd" {
+        return None;
+    }
+
+    if process.pid == 1 {
+        Some(None)
+    } else {
+        process.uid().ok().map(Some)
+    }
+}
+
+/// Finds the systemd `.socket` unit listening on `port`, for sockets held by
+/// a service manager on behalf of a socket-activated service.
+///
+/// # Arguments
+///
+/// * `user` - The user ID owning the `systemd --user` instance, or `None` for
+///   the system manager.
+/// * `port` - Target port number.
+fn find_socket_unit(user: Option<u32>, port: u16) -> Option<Service> {
+    let sockets = systemctl(
+        user,
+        &[
+            "list-sockets",
+            "--all",
+            "--full",
+            "--no-legend",
+            "--no-pager",
+        ],
+    )
+    .ok()?;
+
+    // Each line is `LISTEN UNIT ACTIVATES...`, e.g. `[::]:22 ssh.socket ssh.service`
+    let unit = sockets.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let listen = fields.next()?;
+        let unit = fields.next()?;
+        let listen_port = listen.rsplit(':').next()?.parse::<u16>().ok()?;
+        (listen_port == port).then(|| unit.to_string())
+    })?;
+
+    let kind = match user {
+        Some(uid) => ServiceKind::SystemdUser(uid),
+        None => ServiceKind::Systemd,
+    };
+    Some(Service::new(kind, unit))
+}
+
+/// Leaves alone a systemd manager holding `port` without a matching socket
+/// unit, as killing it would take the whole system or user session down. The
+/// manager is recorded as skipped in `diagnostics`, so the port is not
+/// reported as free.
+///
+/// # Arguments
+///
+/// * `port` - The port held by the manager.
+/// * `pid` - The process ID of the manager.
+/// * `name` - The command line of the manager.
+/// * `diagnostics` - Collects the skipped manager.
+pub fn skip_systemd_manager(port: Port, pid: i32, name: String, diagnostics: &mut Diagnostics) {
+    warn!(
+        "Port {} is held by the systemd manager with PID {}, which will not be killed",
+        port, pid
+    );
+    diagnostics.skipped.push((KillableType::Process, name));
+}
+
 /// Finds the processes associated with the specified `port`.
 ///
+/// Processes that exit mid-scan are skipped. Processes whose file descriptors
+/// cannot be listed (typically those owned by other users when running
+/// unprivileged) are skipped as well and recorded in `diagnostics`. Sockets
+/// that turn out not to be referenced by any process are held by the kernel
+/// and are recorded in `diagnostics` too.
+///
 /// Returns a `Vec` of native processes.
 ///
 /// # Arguments
 ///
-/// * `inodes` - Target inodes
-pub fn find_target_processes(port: u16) -> Result<Vec<UnixProcess>, Error> {
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects the processes that could not be inspected
+pub fn find_target_processes(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, Error> {
     let mut target_pids: Vec<UnixProcess> = vec![];
-    let inodes = find_target_inodes(port);
-
-    for inode in inodes {
-        let processes = procfs::process::all_processes()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        for p in processes {
-            let process = p.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-            if let Ok(fds) = process.fd() {
-                for fd in fds {
-                    let fd = fd.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-                    if let FDTarget::Socket(sock_inode) = fd.target {
-                        if inode == sock_inode {
-                            let name = process
-                                .cmdline()
-                                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
-                                .join(" ");
-                            debug!("Found process '{}' with PID {}", name, process.pid());
-                            target_pids.push(UnixProcess::new(Pid::from_raw(process.pid), name));
-                        }
-                    }
+    if let Some(isolation) = describe_isolation() {
+        diagnostics.unsearched.push(format!(
+            "processes outside {}, whose sockets are not visible",
+            isolation
+        ));
+    }
+    let inodes = find_target_inodes(port, diagnostics);
+    let mut owned_inodes: HashSet<u64> = HashSet::new();
+
+    if inodes.is_empty() {
+        return Ok(target_pids);
+    }
+
+    // The eBPF resolver finds the owners of the sockets inside the kernel,
+    // sparing the walk through the descriptors of every other process
+    #[cfg(feature = "ebpf")]
+    let owners = match crate::ebpf::socket_owners(&inodes.keys().copied().collect()) {
+        Ok(owners) => Some(owners),
+        Err(e) => {
+            debug!("The eBPF resolver is unavailable, scanning /proc: {}", e);
+            None
+        }
+    };
+
+    let processes = procfs::process::all_processes().map_err(|e| match e {
+        ProcError::PermissionDenied(_) => Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("Failed to list processes: {}", e),
+        ),
+        _ => Error::other(format!("Failed to list processes: {}", e)),
+    })?;
+
+    for p in processes {
+        // The process may have exited since the directory was listed
+        let Ok(process) = p else {
+            continue;
+        };
+
+        #[cfg(feature = "ebpf")]
+        if owners
+            .as_ref()
+            .is_some_and(|owners| !owners.contains(&process.pid))
+        {
+            continue;
+        }
+
+        let fds = match process.fd() {
+            Ok(fds) => fds,
+            Err(ProcError::PermissionDenied(_)) => {
+                debug!("Could not inspect process with PID {}", process.pid);
+                diagnostics.uninspected.push(process.pid as u32);
+                continue;
+            }
+            Err(_) => continue,
+        };
+
+        // A process may hold several sockets on the port, e.g. one per IP
+        // version, and is reported once with all of them
+        let sockets: Vec<String> = fds
+            .flatten()
+            .filter_map(|fd| match fd.target {
+                FDTarget::Socket(inode) => inodes.get(&inode).map(|(_, table)| {
+                    owned_inodes.insert(inode);
+                    table.to_string()
+                }),
+                _ => None,
+            })
+            .collect();
+        if sockets.is_empty() {
+            continue;
+        }
+
+        let Some(cmdline) = command_line(&process) else {
+            debug!("Process with PID {} exited during the scan", process.pid);
+            continue;
+        };
+        let port_forward = parse_port_forward(&cmdline, port.number());
+        let name = match describe_ssh_forward(&cmdline, port.number())
+            .or_else(|| port_forward.as_ref().map(|forward| forward.describe()))
+        {
+            Some(tunnel) => {
+                debug!(
+                    "Process with PID {} runs '{}'",
+                    process.pid,
+                    cmdline.join(" ")
+                );
+                tunnel
+            }
+            None => cmdline.join(" "),
+        };
+        debug!(
+            "Found process '{}' with PID {} holding {}",
+            name,
+            process.pid(),
+            sockets.join(", ")
+        );
+        if log_enabled!(Level::Debug) {
+            log_security_context(&process);
+        }
+        let service = match systemd_manager(&process) {
+            Some(user) => match find_socket_unit(user, port.number()) {
+                Some(socket) => Some(socket),
+                None => {
+                    skip_systemd_manager(port, process.pid, name, diagnostics);
+                    continue;
                 }
+            },
+            None => find_service(process.pid),
+        };
+        if let Some(service) = &service {
+            debug!("Process with PID {} is managed by {}", process.pid, service);
+        }
+        let pod = match port_forward {
+            Some(forward) => forward.pod(),
+            None => pod_container_id(process.pid)
+                .and_then(|container_id| Pod::find_by_container(&container_id)),
+        };
+        if let Some(pod) = &pod {
+            debug!("Process with PID {} belongs to pod {}", process.pid, pod);
+        }
+        let proxy = parse_container_proxy(&cmdline);
+        if let Some(proxy) = &proxy {
+            debug!("Process with PID {} is the {}", process.pid, proxy);
+        }
+        target_pids.push(
+            UnixProcess::new(Pid::from_raw(process.pid), name)
+                .with_service(service)
+                .with_pod(pod)
+                .with_proxy(proxy)
+                .with_sockets(sockets),
+        );
+    }
+
+    // Without a complete picture of the processes, an unowned socket could
+    // just as well belong to one that could not be inspected
+    if diagnostics.uninspected.is_empty() {
+        for (inode, (protocol, _)) in inodes {
+            if !owned_inodes.contains(&inode) {
+                debug!("Socket with inode {} is not owned by any process", inode);
+                diagnostics.kernel_sockets.push(KernelSocket {
+                    protocol,
+                    subsystem: kernel_subsystem(protocol, port.number()),
+                });
             }
         }
     }
 
     Ok(target_pids)
 }
+
+/// Describes the isolation killport runs in, if it cannot see the sockets of
+/// the host: a container, or a network namespace other than that of init.
+fn describe_isolation() -> Option<&'static str> {
+    if Path::new("/.dockerenv").exists() || Path::new("/run/.containerenv").exists() {
+        return Some("the container killport runs in");
+    }
+
+    let own = fs::read_link("/proc/self/ns/net").ok()?;
+    let init = fs::read_link("/proc/1/ns/net").ok()?;
+    (own != init).then_some("the network namespace killport runs in")
+}
+
+/// Returns the command line of a process.
+///
+/// Zombies and kernel threads have no command line, so their short name is
+/// used instead. Returns `None` once the process has exited.
+///
+/// # Arguments
+///
+/// * `process` - The process.
+fn command_line(process: &procfs::process::Process) -> Option<Vec<String>> {
+    match process.cmdline() {
+        Ok(cmdline) if !cmdline.is_empty() => Some(cmdline),
+        _ => process.stat().ok().map(|stat| vec![stat.comm]),
+    }
+}
+
+/// Returns the parent of the process `pid`, with its name.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn parent_process(pid: Pid) -> Option<(Pid, String)> {
+    let ppid = procfs::process::Process::new(pid.as_raw())
+        .ok()?
+        .stat()
+        .ok()?
+        .ppid;
+    let name = procfs::process::Process::new(ppid).ok()?.stat().ok()?.comm;
+
+    Some((Pid::from_raw(ppid), name))
+}
+
+/// Returns true if the process `pid` is still running, as opposed to having
+/// exited, including as a zombie waiting to be reaped.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn is_process_alive(pid: Pid) -> bool {
+    procfs::process::Process::new(pid.as_raw())
+        .and_then(|process| process.stat())
+        .is_ok_and(|stat| stat.state != 'Z')
+}
+
+/// Returns the ID of the user owning the process `pid`.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn process_uid(pid: Pid) -> Option<u32> {
+    procfs::process::Process::new(pid.as_raw()).ok()?.uid().ok()
+}
+
+/// Finds the processes spawned by the process `pid`, directly or indirectly.
+///
+/// # Arguments
+///
+/// * `pid` - The parent process ID.
+pub fn find_child_processes(pid: Pid) -> Vec<UnixProcess> {
+    let Ok(processes) = procfs::process::all_processes() else {
+        return vec![];
+    };
+
+    let processes: HashMap<i32, procfs::process::Process> = processes
+        .flatten()
+        .map(|process| (process.pid, process))
+        .collect();
+    let parents: Vec<(i32, i32)> = processes
+        .values()
+        .filter_map(|process| Some((process.pid, process.stat().ok()?.ppid)))
+        .collect();
+
+    descendants(pid.as_raw(), &parents)
+        .into_iter()
+        .filter_map(|child| {
+            let name = command_line(processes.get(&child)?)?.join(" ");
+            debug!("Found child process '{}' with PID {}", name, child);
+            Some(UnixProcess::new(Pid::from_raw(child), name))
+        })
+        .collect()
+}
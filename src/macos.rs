@@ -1,76 +1,891 @@
-use crate::unix::UnixProcess;
+use crate::cli::Port;
+use crate::killport::{descendants, Diagnostics, KillableType};
+use crate::kubernetes::parse_port_forward;
+use crate::service::{run_command, Service, ServiceKind};
+use crate::socket::{parse_netstat_time_wait, OrphanSocket, Protocol};
+use crate::unix::{describe_ssh_forward, UnixProcess};
 
+use libproc::libproc::bsd_info::BSDInfo;
 use libproc::libproc::file_info::pidfdinfo;
-use libproc::libproc::file_info::{ListFDs, ProcFDType};
+use libproc::libproc::file_info::{ListFDs, ProcFDInfo, ProcFDType};
 use libproc::libproc::net_info::{SocketFDInfo, SocketInfoKind};
-use libproc::libproc::proc_pid::{listpidinfo, name};
+use libproc::libproc::proc_pid::{listpidinfo, name, pidinfo, pidpath};
 use libproc::processes::{pids_by_type, ProcFilter};
-use log::debug;
-use nix::unistd::Pid;
-use std::io;
+use log::{debug, info, log_enabled, warn, Level};
+use nix::errno::Errno;
+use nix::sys::signal::{kill, SigSet, Signal};
+use nix::unistd::{geteuid, Pid};
+use std::{
+    cell::OnceCell,
+    collections::{BTreeSet, HashMap},
+    ffi::CString,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    ptr,
+};
+
+/// pf anchor holding the blocking rules. Anchors below `com.apple` are
+/// evaluated by the default macOS pf configuration.
+const PF_ANCHOR: &str = "com.apple/killport";
+
+/// Protocol numbers from `<netinet/in.h>`.
+const IPPROTO_TCP: i32 = 6;
+const IPPROTO_UDP: i32 = 17;
+
+/// Address family of IPv6 sockets from `<sys/socket.h>`.
+const AF_INET6: i32 = 30;
+
+/// Sysctls listing the protocol control blocks of every TCP and UDP socket.
+const PCB_LISTS: [&str; 2] = ["net.inet.tcp.pcblist_n", "net.inet.udp.pcblist_n"];
+
+/// Size of `struct xinpgen`, which opens and closes a PCB list.
+const XINPGEN_SIZE: usize = 24;
+/// Record kinds from `<sys/socketvar.h>`.
+const XSO_SOCKET: u32 = 0x001;
+const XSO_INPCB: u32 = 0x010;
+/// Offset of `inp_lport` in `struct xinpcb_n`.
+const XINPCB_LPORT_OFFSET: usize = 18;
+/// Offset of `so_last_pid` in `struct xsocket_n`.
+const XSOCKET_LAST_PID_OFFSET: usize = 68;
 
 /// Finds the processes associated with the specified `port`.
 ///
+/// Both TCP and UDP sockets, over IPv4 and IPv6, are considered. The kernel
+/// PCB lists name the processes that last used each socket, so only those
+/// have their file descriptors inspected. When the lists are unavailable or
+/// point at no process still holding the socket (e.g. it was inherited
+/// across a fork), every process is inspected instead. When no process is
+/// found, the connections left in `TIME_WAIT` on the port are recorded in
+/// `diagnostics`, as they keep servers from binding it.
+///
 /// Returns a `Vec` of native processes.
 ///
 /// # Arguments
 ///
-/// * `port` - Target port number
-pub fn find_target_processes(port: u16) -> Result<Vec<UnixProcess>, io::Error> {
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects observations made during discovery
+pub fn find_target_processes(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, io::Error> {
+    let target_pids = find_socket_processes(port, diagnostics)?;
+    record_orphans(&target_pids, port, diagnostics);
+    Ok(target_pids)
+}
+
+/// Finds the processes associated with the specified `port` by inspecting
+/// every process, without consulting the PCB lists.
+///
+/// Returns a `Vec` of native processes.
+///
+/// # Arguments
+///
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects observations made during discovery
+pub fn walk_target_processes(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, io::Error> {
+    let target_pids = find_every_process(port, diagnostics)?;
+    record_orphans(&target_pids, port, diagnostics);
+    Ok(target_pids)
+}
+
+/// Records the connections left in `TIME_WAIT` on `port` when no process
+/// holds it.
+///
+/// # Arguments
+///
+/// * `target_pids` - The processes found on the port.
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects the orphaned sockets
+fn record_orphans(target_pids: &[UnixProcess], port: Port, diagnostics: &mut Diagnostics) {
+    if target_pids.is_empty() && port.carries(Protocol::Tcp) {
+        diagnostics
+            .orphans
+            .extend(find_time_wait_sockets(port.number()));
+    }
+}
+
+/// Finds the processes holding a socket bound to `port`, through the PCB
+/// lists when possible.
+///
+/// # Arguments
+///
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects observations made during discovery
+fn find_socket_processes(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, io::Error> {
+    match pcb_list_pids(port.number()) {
+        Ok(pids) if pids.is_empty() => return Ok(vec![]),
+        Ok(pids) => {
+            let skipped = diagnostics.skipped.len();
+            let uninspected = diagnostics.uninspected.len();
+            let target_pids = find_processes_in(pids.into_iter(), port, diagnostics)?;
+            if !target_pids.is_empty()
+                || diagnostics.skipped.len() > skipped
+                || diagnostics.uninspected.len() > uninspected
+            {
+                return Ok(target_pids);
+            }
+            debug!("No process found from the PCB lists, scanning every process");
+        }
+        Err(e) => debug!(
+            "Could not read the PCB lists, scanning every process: {}",
+            e
+        ),
+    }
+
+    find_every_process(port, diagnostics)
+}
+
+/// Finds the processes holding a socket bound to `port` among every process.
+///
+/// # Arguments
+///
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects observations made during discovery
+fn find_every_process(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, io::Error> {
+    match pids_by_type(ProcFilter::All) {
+        Ok(pids) => find_processes_in(pids.into_iter().map(|pid| pid as i32), port, diagnostics),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Finds the processes among `pids` holding a socket bound to `port`.
+///
+/// # Arguments
+///
+/// * `pids` - The processes to inspect.
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects the protected processes left alone and the
+///   processes that could not be inspected
+fn find_processes_in(
+    pids: impl Iterator<Item = i32>,
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<UnixProcess>, io::Error> {
     let mut target_pids: Vec<UnixProcess> = vec![];
+    let launchd_jobs = OnceCell::new();
+    let airplay_receiver =
+        Service::new(ServiceKind::AirPlayReceiver, "AirPlay Receiver".to_string());
 
-    if let Ok(procs) = pids_by_type(ProcFilter::All) {
-        for p in procs {
-            let pid = p as i32;
-            let fds = listpidinfo::<ListFDs>(pid, 1024); // Large enough to cover typical number of open files
-            if let Ok(fds) = fds {
-                for fd in fds {
-                    if let ProcFDType::Socket = fd.proc_fdtype.into() {
-                        if let Ok(socket) = pidfdinfo::<SocketFDInfo>(pid, fd.proc_fd) {
-                            // Correctly cast soi_kind to SocketInfoKind
-                            if let Ok(socket_kind) = SocketInfoKind::try_from(socket.psi.soi_kind) {
-                                match socket_kind {
-                                    SocketInfoKind::In | SocketInfoKind::Tcp => {
-                                        let local_port = unsafe {
-                                            match socket_kind {
-                                                SocketInfoKind::In => {
-                                                    socket.psi.soi_proto.pri_in.insi_lport as u16
-                                                }
-                                                SocketInfoKind::Tcp => {
-                                                    socket
-                                                        .psi
-                                                        .soi_proto
-                                                        .pri_tcp
-                                                        .tcpsi_ini
-                                                        .insi_lport
-                                                        as u16
-                                                }
-                                                _ => continue,
-                                            }
-                                        };
-                                        if u16::from_be(local_port) == port {
-                                            let process_name = name(pid).map_err(|e| {
-                                                io::Error::new(io::ErrorKind::Other, e)
-                                            })?;
-                                            debug!(
-                                                "Found process '{}' with PID {} listening on port {}",
-                                                process_name, pid, port
-                                            );
-                                            target_pids.push(UnixProcess::new(
-                                                Pid::from_raw(pid),
-                                                process_name,
-                                            ));
-                                        }
-                                    }
-                                    _ => (),
-                                }
-                            }
-                        }
-                    }
+    for pid in pids {
+        let fds = match list_fds(pid) {
+            Ok(fds) => fds,
+            Err(e) => {
+                // Only processes of other users are off limits, anything else
+                // has exited since it was listed
+                let alive = kill(Pid::from_raw(pid), None) != Err(Errno::ESRCH);
+                if alive && !geteuid().is_root() {
+                    debug!("Could not inspect process with PID {}: {}", pid, e);
+                    diagnostics.uninspected.push(pid as u32);
                 }
+                continue;
+            }
+        };
+
+        // A process may hold several sockets on the port, e.g. one per IP
+        // version, and is reported once with all of them
+        let sockets: Vec<String> = fds
+            .iter()
+            .filter(|fd| matches!(ProcFDType::from(fd.proc_fdtype), ProcFDType::Socket))
+            .filter_map(|fd| pidfdinfo::<SocketFDInfo>(pid, fd.proc_fd).ok())
+            .filter_map(|socket| match local_endpoint(&socket) {
+                Some((protocol, local_port))
+                    if local_port == port.number() && port.carries(protocol) =>
+                {
+                    Some(match socket.psi.soi_family {
+                        AF_INET6 => format!("{}6", protocol),
+                        _ => protocol.to_string(),
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        if sockets.is_empty() {
+            continue;
+        }
+
+        let mut process_name = name(pid).map_err(io::Error::other)?;
+        let path = pidpath(pid).ok();
+        if let Some(bundle) = path
+            .as_deref()
+            .and_then(|path| app_bundle_name(Path::new(path)))
+        {
+            process_name = format!("{} ({})", process_name, bundle);
+        }
+        debug!(
+            "Found process '{}' with PID {} listening on port {} ({})",
+            process_name,
+            pid,
+            port,
+            sockets.join(", ")
+        );
+        let arguments = process_arguments(pid);
+        if let Some(tunnel) = arguments
+            .as_deref()
+            .and_then(|arguments| describe_ssh_forward(arguments, port.number()))
+        {
+            process_name = tunnel;
+        }
+        let port_forward = arguments
+            .as_deref()
+            .and_then(|arguments| parse_port_forward(arguments, port.number()));
+        if let Some(forward) = &port_forward {
+            process_name = forward.describe();
+        }
+        if log_enabled!(Level::Info) {
+            if let Some(arguments) = &arguments {
+                let cwd = process_cwd(pid)
+                    .map(|cwd| cwd.display().to_string())
+                    .unwrap_or_else(|| "an unknown directory".to_string());
+                info!(
+                    "Process '{}' with PID {} was started as '{}' in {}",
+                    process_name,
+                    pid,
+                    arguments.join(" "),
+                    cwd
+                );
+            }
+            if let Some(architecture) = process_architecture(pid) {
+                info!(
+                    "Process '{}' with PID {} runs {}",
+                    process_name, pid, architecture
+                );
             }
         }
+        let service = if is_airplay_receiver(&process_name, port.number()) {
+            Some(&airplay_receiver)
+        } else {
+            launchd_jobs.get_or_init(launchd_jobs_by_pid).get(&pid)
+        };
+        if let Some(service) = service {
+            debug!("Process with PID {} is managed by {}", pid, service);
+        } else if is_system_protected(pid, path.as_deref()) {
+            warn!(
+                "Process '{}' with PID {} is a macOS system process protected by System Integrity Protection; it cannot be killed and launchd would relaunch it anyway",
+                process_name, pid
+            );
+            diagnostics
+                .skipped
+                .push((KillableType::Process, process_name));
+            continue;
+        }
+        target_pids.push(
+            UnixProcess::new(Pid::from_raw(pid), process_name)
+                .with_service(service.cloned())
+                .with_pod(port_forward.and_then(|forward| forward.pod()))
+                .with_sockets(sockets),
+        );
     }
 
     Ok(target_pids)
 }
+
+/// Returns the full command line of a process.
+///
+/// `KERN_PROCARGS2` yields the argument count, followed by the executable
+/// path, some NUL padding and the NUL separated arguments and environment.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+fn process_arguments(pid: i32) -> Option<Vec<String>> {
+    let mut mib = [libc::CTL_KERN, libc::KERN_ARGMAX];
+    let mut arg_max: libc::c_int = 0;
+    let mut size = std::mem::size_of::<libc::c_int>();
+    let result = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as libc::c_uint,
+            (&mut arg_max as *mut libc::c_int).cast(),
+            &mut size,
+            ptr::null_mut(),
+            0,
+        )
+    };
+    if result != 0 || arg_max <= 0 {
+        return None;
+    }
+
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
+    let mut buffer = vec![0u8; arg_max as usize];
+    let mut size = buffer.len();
+    let result = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as libc::c_uint,
+            buffer.as_mut_ptr().cast(),
+            &mut size,
+            ptr::null_mut(),
+            0,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    buffer.truncate(size);
+
+    let argc = i32::from_ne_bytes(buffer.get(..4)?.try_into().ok()?);
+    let mut fields = buffer[4..].split(|byte| *byte == 0);
+
+    // Skip the executable path and the padding after it
+    fields.next()?;
+    let arguments: Vec<String> = fields
+        .skip_while(|field| field.is_empty())
+        .take(argc.max(0) as usize)
+        .map(|field| String::from_utf8_lossy(field).into_owned())
+        .collect();
+
+    (!arguments.is_empty()).then_some(arguments)
+}
+
+/// Returns the current working directory of a process.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+fn process_cwd(pid: i32) -> Option<PathBuf> {
+    let mut info: libc::proc_vnodepathinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_vnodepathinfo>() as libc::c_int;
+    let result = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDVNODEPATHINFO,
+            0,
+            (&mut info as *mut libc::proc_vnodepathinfo).cast(),
+            size,
+        )
+    };
+    if result != size {
+        return None;
+    }
+
+    let path: Vec<u8> = info
+        .pvi_cdir
+        .vip_path
+        .iter()
+        .flatten()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+
+    (!path.is_empty()).then(|| PathBuf::from(String::from_utf8_lossy(&path).into_owned()))
+}
+
+/// Returns the architecture a process runs as, telling apart x86_64
+/// processes translated by Rosetta on Apple silicon.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+fn process_architecture(pid: i32) -> Option<&'static str> {
+    /// Size of `struct kinfo_proc` and offset of `kp_proc.p_flag` within it.
+    const KINFO_PROC_SIZE: usize = 648;
+    const P_FLAG_OFFSET: usize = 32;
+    /// `P_TRANSLATED` from `<sys/proc.h>`.
+    const P_TRANSLATED: i32 = 0x0002_0000;
+
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROC, libc::KERN_PROC_PID, pid];
+    let mut buffer = [0u8; KINFO_PROC_SIZE];
+    let mut size: libc::size_t = KINFO_PROC_SIZE;
+    let result = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as libc::c_uint,
+            buffer.as_mut_ptr().cast(),
+            &mut size,
+            ptr::null_mut(),
+            0,
+        )
+    };
+    if result != 0 || size < P_FLAG_OFFSET + 4 {
+        return None;
+    }
+
+    let flags = i32::from_ne_bytes(buffer[P_FLAG_OFFSET..P_FLAG_OFFSET + 4].try_into().ok()?);
+    if flags & P_TRANSLATED != 0 {
+        return Some("x86_64 under Rosetta");
+    }
+
+    // The sysctl only exists on Apple silicon
+    let apple_silicon = sysctl_bytes("hw.optional.arm64")
+        .is_ok_and(|value| value.first().is_some_and(|value| *value == 1));
+
+    Some(if apple_silicon {
+        "natively on arm64"
+    } else {
+        "natively on x86_64"
+    })
+}
+
+/// Returns true if the process runs a macOS system binary, which System
+/// Integrity Protection shields from signals even when running as root.
+///
+/// Platform binaries are identified from the code signing status of the
+/// process, falling back to the SIP protected locations of its executable.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+/// * `path` - The path of the executable, when known.
+fn is_system_protected(pid: i32, path: Option<&str>) -> bool {
+    /// `CS_OPS_STATUS` and `CS_PLATFORM_BINARY` from `<kern/cs_blobs.h>`.
+    const CS_OPS_STATUS: u32 = 0;
+    const CS_PLATFORM_BINARY: u32 = 0x0400_0000;
+    /// Directories protected by System Integrity Protection.
+    const PROTECTED_DIRS: [&str; 5] = [
+        "/System/",
+        "/usr/bin/",
+        "/usr/sbin/",
+        "/usr/libexec/",
+        "/sbin/",
+    ];
+
+    extern "C" {
+        fn csops(
+            pid: libc::pid_t,
+            ops: u32,
+            useraddr: *mut libc::c_void,
+            usersize: libc::size_t,
+        ) -> libc::c_int;
+    }
+
+    let mut flags: u32 = 0;
+    let result = unsafe {
+        csops(
+            pid,
+            CS_OPS_STATUS,
+            (&mut flags as *mut u32).cast(),
+            std::mem::size_of::<u32>(),
+        )
+    };
+    if result == 0 {
+        return flags & CS_PLATFORM_BINARY != 0;
+    }
+
+    path.is_some_and(|path| PROTECTED_DIRS.iter().any(|dir| path.starts_with(dir)))
+}
+
+/// Returns true if the process is Control Center serving AirPlay Receiver,
+/// which listens on ports 5000 and 7000 since macOS Monterey.
+///
+/// # Arguments
+///
+/// * `process_name` - The name of the process bound to the port.
+/// * `port` - Target port number
+fn is_airplay_receiver(process_name: &str, port: u16) -> bool {
+    matches!(port, 5000 | 7000) && process_name.starts_with("ControlCenter")
+}
+
+/// Returns the name of the application bundle an executable belongs to,
+/// e.g. "Docker" for `/Applications/Docker.app/Contents/MacOS/com.docker.backend`.
+///
+/// Helpers nested in other bundles (`Foo.app/Contents/Frameworks/Helper.app`)
+/// are reported under the outermost application, which is what users know.
+///
+/// # Arguments
+///
+/// * `executable` - The path of the executable.
+fn app_bundle_name(executable: &Path) -> Option<String> {
+    executable
+        .ancestors()
+        .filter(|dir| dir.extension().is_some_and(|extension| extension == "app"))
+        .last()
+        .and_then(|bundle| bundle.file_stem())
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Lists the file descriptors of a process.
+///
+/// The buffer is sized from the number of files the process has open, with
+/// some room for files opened in the meantime, so busy processes are not
+/// truncated.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+fn list_fds(pid: i32) -> Result<Vec<ProcFDInfo>, String> {
+    let info = pidinfo::<BSDInfo>(pid, 0)?;
+    let open_files = info.pbi_nfiles as usize;
+
+    listpidinfo::<ListFDs>(pid, open_files + open_files / 8 + 16)
+}
+
+/// Returns the running launchd jobs of the current domain by PID: the system
+/// domain when running as root, the GUI domain of the user otherwise.
+///
+/// Jobs are listed with `launchctl list`, which prints the PID, last exit
+/// status and label of every job, with `-` as the PID of jobs not running.
+fn launchd_jobs_by_pid() -> HashMap<i32, Service> {
+    let uid = geteuid();
+    let kind = if uid.is_root() {
+        ServiceKind::Launchd
+    } else {
+        ServiceKind::LaunchdGui(uid.as_raw())
+    };
+
+    let output = match run_command("launchctl", &["list"]) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Could not list launchd jobs: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse::<i32>().ok()?;
+            let label = fields.nth(1)?;
+            // brew services names its jobs after the formula
+            let service = match label.strip_prefix("homebrew.mxcl.") {
+                Some(formula) => Service::new(ServiceKind::Homebrew, formula.to_string()),
+                None => Service::new(kind, label.to_string()),
+            };
+            Some((pid, service))
+        })
+        .collect()
+}
+
+/// Finds the TCP connections on local port `port` left in `TIME_WAIT`.
+///
+/// They no longer belong to any process, so they are only listed by netstat.
+///
+/// # Arguments
+///
+/// * `port` - Target port number
+fn find_time_wait_sockets(port: u16) -> Vec<OrphanSocket> {
+    match run_command("netstat", &["-an", "-p", "tcp"]) {
+        Ok(output) => parse_netstat_time_wait(&output, port),
+        Err(e) => {
+            debug!("Could not list the TCP connections: {}", e);
+            vec![]
+        }
+    }
+}
+
+/// Returns the processes that last used a TCP or UDP socket bound to `port`,
+/// according to the kernel PCB lists.
+///
+/// # Arguments
+///
+/// * `port` - Target port number
+fn pcb_list_pids(port: u16) -> Result<BTreeSet<i32>, io::Error> {
+    let mut pids = BTreeSet::new();
+
+    for list in PCB_LISTS {
+        let buffer = sysctl_bytes(list)?;
+        pids.extend(parse_pcb_list(&buffer, port));
+    }
+
+    Ok(pids)
+}
+
+/// Parses a `pcblist_n` buffer, returning the PIDs of the sockets bound to
+/// `port`.
+///
+/// The buffer holds a `struct xinpgen` header and trailer around a sequence
+/// of 64-bit aligned records, each starting with its length and kind. Every
+/// socket is described by an `xinpcb_n` record followed by an `xsocket_n`
+/// record and some buffer statistics.
+///
+/// # Arguments
+///
+/// * `buffer` - The raw sysctl output.
+/// * `port` - Target port number
+fn parse_pcb_list(buffer: &[u8], port: u16) -> Vec<i32> {
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = buffer.get(offset..offset + 4)?;
+        Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+    };
+
+    let mut pids = vec![];
+    let mut offset = XINPGEN_SIZE;
+    let mut matches_port = false;
+
+    while offset + XINPGEN_SIZE < buffer.len() {
+        let (Some(len), Some(kind)) = (read_u32(offset), read_u32(offset + 4)) else {
+            break;
+        };
+        if len == 0 {
+            break;
+        }
+
+        match kind {
+            XSO_INPCB => {
+                matches_port = buffer
+                    .get(offset + XINPCB_LPORT_OFFSET..offset + XINPCB_LPORT_OFFSET + 2)
+                    .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) == port)
+                    .unwrap_or(false);
+            }
+            XSO_SOCKET if matches_port => {
+                if let Some(pid) = read_u32(offset + XSOCKET_LAST_PID_OFFSET) {
+                    if pid != 0 {
+                        pids.push(pid as i32);
+                    }
+                }
+                matches_port = false;
+            }
+            _ => {}
+        }
+
+        // Records are padded to 64-bit boundaries
+        offset += (len as usize + 7) & !7;
+    }
+
+    pids
+}
+
+/// Reads a sysctl by name into a byte buffer.
+///
+/// # Arguments
+///
+/// * `name` - The sysctl name, e.g. `net.inet.tcp.pcblist_n`.
+fn sysctl_bytes(name: &str) -> Result<Vec<u8>, io::Error> {
+    let name = CString::new(name).map_err(io::Error::other)?;
+    let mut size: libc::size_t = 0;
+
+    // The list may grow between the size query and the read, retry a few times
+    for _ in 0..3 {
+        let result = unsafe {
+            libc::sysctlbyname(
+                name.as_ptr(),
+                ptr::null_mut(),
+                &mut size,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Leave room for sockets opened in the meantime
+        size += size / 8;
+        let mut buffer = vec![0u8; size];
+        let result = unsafe {
+            libc::sysctlbyname(
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if result == 0 {
+            buffer.truncate(size);
+            return Ok(buffer);
+        }
+
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ENOMEM) {
+            return Err(error);
+        }
+    }
+
+    Err(io::Error::other("PCB list kept growing while being read"))
+}
+
+/// Returns the protocol and local port of an internet socket.
+///
+/// TCP sockets are reported with the `Tcp` kind once they carry TCP state,
+/// while UDP sockets (and TCP sockets without it) use the generic `In` kind,
+/// so the protocol is taken from the socket itself.
+///
+/// # Arguments
+///
+/// * `socket` - The socket information of a file descriptor.
+fn local_endpoint(socket: &SocketFDInfo) -> Option<(Protocol, u16)> {
+    let protocol = match socket.psi.soi_protocol {
+        IPPROTO_TCP => Protocol::Tcp,
+        IPPROTO_UDP => Protocol::Udp,
+        _ => return None,
+    };
+
+    let local_port = unsafe {
+        match SocketInfoKind::from(socket.psi.soi_kind) {
+            SocketInfoKind::In => socket.psi.soi_proto.pri_in.insi_lport,
+            SocketInfoKind::Tcp => socket.psi.soi_proto.pri_tcp.tcpsi_ini.insi_lport,
+            _ => return None,
+        }
+    };
+
+    Some((protocol, u16::from_be(local_port as u16)))
+}
+
+/// Blocks new connections to `ports` with pf until killport is interrupted,
+/// then removes the rules again.
+///
+/// This keeps an auto-restarting agent from serving the ports while it is
+/// being tracked down. The rules live in their own anchor and pf is enabled
+/// through a reference token, so the existing firewall setup is left as is.
+/// Requires root.
+///
+/// # Arguments
+///
+/// * `ports` - The ports to block.
+pub fn block_ports_until_interrupted(ports: &[Port]) -> Result<(), io::Error> {
+    let ports_list = ports
+        .iter()
+        .map(Port::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let rules: String = ports
+        .iter()
+        .map(|port| {
+            let protocols = match port.protocol() {
+                Some(protocol) => protocol.to_string(),
+                None => "{ tcp udp }".to_string(),
+            };
+            format!(
+                "block drop quick proto {} from any to any port {}\n",
+                protocols, port
+            )
+        })
+        .collect();
+
+    // Hold back termination signals first, so the rules are always removed
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    signals.add(Signal::SIGHUP);
+    signals.thread_block()?;
+
+    let enabled = pfctl(&["-E"], None)?;
+    let token = enabled
+        .lines()
+        .find_map(|line| line.strip_prefix("Token : "))
+        .map(|token| token.trim().to_string());
+
+    let result = pfctl(&["-a", PF_ANCHOR, "-f", "-"], Some(&rules)).and_then(|_| {
+        println!(
+            "Blocking new connections to port {} with pf, press Ctrl-C to unblock",
+            ports_list
+        );
+        signals.wait().map_err(io::Error::from)
+    });
+
+    debug!("Removing the pf rules of anchor {}", PF_ANCHOR);
+    pfctl(&["-a", PF_ANCHOR, "-F", "rules"], None)?;
+    if let Some(token) = token {
+        pfctl(&["-X", &token], None)?;
+    }
+
+    let signal = result?;
+    debug!("Unblocked the ports after receiving {}", signal);
+    println!("Unblocked port {}", ports_list);
+
+    Ok(())
+}
+
+/// Runs `pfctl`, returning its combined output as pf reports most of its
+/// results, such as the enable token, on standard error.
+///
+/// # Arguments
+///
+/// * `args` - The arguments to pass to `pfctl`.
+/// * `input` - Rules to feed on standard input.
+fn pfctl(args: &[&str], input: Option<&str>) -> Result<String, io::Error> {
+    debug!("Running pfctl {}", args.join(" "));
+
+    let mut child = Command::new("pfctl")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to run pfctl: {}", e)))?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    let combined = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "pfctl {} failed: {}",
+            args.join(" "),
+            combined.trim()
+        )));
+    }
+
+    Ok(combined)
+}
+
+/// Returns the parent of the process `pid`, with its name.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn parent_process(pid: Pid) -> Option<(Pid, String)> {
+    let ppid = pidinfo::<BSDInfo>(pid.as_raw(), 0).ok()?.pbi_ppid as i32;
+    let name = name(ppid).ok()?;
+
+    Some((Pid::from_raw(ppid), name))
+}
+
+/// Returns true if the process `pid` is still running, as opposed to having
+/// exited, including as a zombie waiting to be reaped.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn is_process_alive(pid: Pid) -> bool {
+    // SZOMB from sys/proc.h
+    const ZOMBIE_STATUS: u32 = 5;
+
+    pidinfo::<BSDInfo>(pid.as_raw(), 0).is_ok_and(|info| info.pbi_status != ZOMBIE_STATUS)
+}
+
+/// Returns the ID of the user owning the process `pid`.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn process_uid(pid: Pid) -> Option<u32> {
+    Some(pidinfo::<BSDInfo>(pid.as_raw(), 0).ok()?.pbi_uid)
+}
+
+/// Finds the processes spawned by the process `pid`, directly or indirectly.
+///
+/// # Arguments
+///
+/// * `pid` - The parent process ID.
+pub fn find_child_processes(pid: Pid) -> Vec<UnixProcess> {
+    let Ok(pids) = pids_by_type(ProcFilter::All) else {
+        return vec![];
+    };
+
+    let parents: Vec<(i32, i32)> = pids
+        .into_iter()
+        .filter_map(|child| {
+            let info = pidinfo::<BSDInfo>(child as i32, 0).ok()?;
+            Some((child as i32, info.pbi_ppid as i32))
+        })
+        .collect();
+
+    descendants(pid.as_raw(), &parents)
+        .into_iter()
+        .filter_map(|child| {
+            let name = name(child).ok()?;
+            debug!("Found child process '{}' with PID {}", name, child);
+            Some(UnixProcess::new(Pid::from_raw(child), name))
+        })
+        .collect()
+}
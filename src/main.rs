@@ -6,23 +6,41 @@
 
 use clap::Parser;
 use clap_verbosity_flag::LevelFilter;
-use log::error;
-use std::io::Write;
+use log::{error, warn};
+use std::env;
+use std::io::{Error, ErrorKind, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::process::exit;
+use std::thread;
 
-use killport::cli::{service_descriptors, KillPortArgs};
-use killport::killport::{Killport, KillportOperations};
+use killport::action_log;
+use killport::cli::{service_descriptors, KillPortArgs, Port};
+use killport::docker::{self, TlsFiles};
+#[cfg(any(unix, windows))]
+use killport::elevate;
+use killport::killport::{Diagnostics, KillOptions, KillableType, Killport, KillportOperations};
+use killport::socket::{describe_kernel_sockets, describe_orphans};
+use killport::summary::{action_verbs, PortSummary, RunSummary, TargetSummary};
+use killport::timings;
 
 fn main() {
+    // A bug is reported as such rather than with a backtrace, which reads
+    // like a crash; bugs on a port fail that port below
+    panic::set_hook(Box::new(|info| {
+        eprintln!(
+            "killport ran into a bug: {}; please report it at https://github.com/jkfran/killport/issues",
+            info
+        );
+    }));
+
     // Parse command-line arguments
     let args = KillPortArgs::parse();
 
-    // Set up logging environment
+    // Set up logging environment, which is off when quieted twice
     let log_level = args
         .verbose
         .log_level()
-        .map(|level| level.to_level_filter())
-        .unwrap();
+        .map_or(LevelFilter::Off, |level| level.to_level_filter());
 
     env_logger::builder()
         .format(move |buf, record| {
@@ -48,32 +66,333 @@ fn main() {
     let (service_type_singular, _service_type_plural) = service_descriptors(args.mode);
 
     // Create an instance of Killport
-    let killport = Killport;
+    let killport = Killport::new();
+    let options = KillOptions::from(&args);
+
+    if !args.backend.is_available() {
+        error!(
+            "The {} backend is not available on this platform; run killport without --backend to use the platform's own",
+            args.backend
+        );
+        exit(1);
+    }
+
+    if args.block {
+        #[cfg(target_os = "macos")]
+        if !args.dry_run && !elevate::is_elevated() {
+            if args.sudo {
+                error!("{}", elevate::relaunch_elevated());
+            } else {
+                error!("Blocking ports with pf requires root; run killport with sudo, or pass --sudo to do it automatically");
+            }
+            exit(1);
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        {
+            error!("Blocking ports is only supported on macOS");
+            exit(1);
+        }
+    }
+
+    if args.show_audit {
+        let Some(path) = action_log::audit_log_path() else {
+            error!("The audit log could not be located, as the home directory is unknown");
+            exit(1);
+        };
+        match action_log::read_audit_log(&path, &args.ports) {
+            Ok(entries) => {
+                for entry in entries {
+                    println!("{}", action_log::describe_audit_entry(&entry));
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                println!("No actions recorded in {}", path.display());
+            }
+            Err(err) => {
+                error!("{}", err);
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.timings {
+        timings::enable();
+    }
+
+    if args.audit {
+        let opened = action_log::audit_log_path()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    ErrorKind::NotFound,
+                    "The audit log could not be located, as the home directory is unknown",
+                )
+            })
+            .and_then(|path| action_log::set_audit_log(&path, env::args().collect()));
+        if let Err(err) = opened {
+            error!("{}", err);
+            exit(1);
+        }
+    }
+
+    if let Some(log_file) = &args.log_file {
+        if let Err(err) = action_log::set_log_file(log_file) {
+            error!("{}", err);
+            exit(1);
+        }
+    }
+
+    if args.syslog {
+        #[cfg(unix)]
+        if let Err(err) = action_log::set_system_log() {
+            error!("Failed to connect to the system log: {}", err);
+            exit(1);
+        }
+
+        #[cfg(not(unix))]
+        {
+            error!("Logging to syslog is only supported on Unix; use --event-log on Windows");
+            exit(1);
+        }
+    }
+
+    docker::set_tls_files(TlsFiles {
+        ca: args.docker_tls_ca.clone(),
+        cert: args.docker_tls_cert.clone(),
+        key: args.docker_tls_key.clone(),
+    });
+
+    #[cfg(windows)]
+    {
+        killport::windows::set_exit_code(args.exit_code);
+        killport::windows::set_event_log(args.event_log);
+    }
+
+    // Attempt to kill processes listening on specified ports. The targets
+    // on every port are looked up concurrently, so their container runtime
+    // requests overlap, then acted on one port at a time in the order given
+    let found: Vec<_> = thread::scope(|scope| {
+        let (killport, options) = (&killport, &options);
+        let handles: Vec<_> = args
+            .ports
+            .iter()
+            .map(|&port| {
+                scope.spawn(move || {
+                    let mut diagnostics = Diagnostics::default();
+                    let result = killport.find_target_killables(port, options, &mut diagnostics);
+                    (port, diagnostics, result)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .zip(&args.ports)
+            .map(|(handle, &port)| {
+                handle.join().unwrap_or_else(|_| {
+                    let error = Error::other(format!("Handling port {} failed on a bug", port));
+                    (port, Diagnostics::default(), Err(error))
+                })
+            })
+            .collect()
+    });
 
-    // Attempt to kill processes listening on specified ports
-    for port in args.ports {
-        match killport.kill_service_by_port(port, args.signal.clone(), args.mode, args.dry_run) {
+    // Processes that could not be inspected may hold any of the ports, so
+    // the elevated run takes over before anything is acted on
+    #[cfg(any(unix, windows))]
+    if args.sudo
+        && found.iter().any(|(_, diagnostics, result)| {
+            !diagnostics.uninspected.is_empty()
+                || matches!(result, Err(err) if err.kind() == ErrorKind::PermissionDenied)
+        })
+        && !elevate::is_elevated()
+    {
+        error!("{}", elevate::relaunch_elevated());
+        exit(1);
+    }
+
+    let outcomes: Vec<_> = found
+        .into_iter()
+        .map(|(port, mut diagnostics, result)| {
+            let result = result.and_then(|targets| {
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    killport.act_on_targets(port, targets, &options, &mut diagnostics)
+                }))
+                .unwrap_or_else(|_| {
+                    Err(Error::other(format!(
+                        "Handling port {} failed on a bug",
+                        port
+                    )))
+                })
+            });
+
+            #[cfg(any(unix, windows))]
+            if matches!(&result, Err(err) if err.kind() == ErrorKind::PermissionDenied)
+                && args.sudo
+                && !elevate::is_elevated()
+            {
+                error!("{}", elevate::relaunch_elevated());
+                exit(1);
+            }
+
+            (port, diagnostics, result)
+        })
+        .collect();
+
+    let summary = RunSummary {
+        dry_run: args.dry_run,
+        ports: outcomes
+            .iter()
+            .map(|(port, diagnostics, result)| PortSummary {
+                port: port.number(),
+                targets: result
+                    .iter()
+                    .flatten()
+                    .map(|target| TargetSummary {
+                        target_type: target.target_type.clone(),
+                        name: target.name.to_string(),
+                        action: action_verbs(&target.target_type, args.container_action).0,
+                        sockets: target.sockets.clone(),
+                    })
+                    .collect(),
+                skipped: diagnostics.skipped.clone(),
+                notes: describe_kernel_sockets(*port, &diagnostics.kernel_sockets)
+                    .into_iter()
+                    .chain(describe_orphans(*port, &diagnostics.orphans))
+                    .collect(),
+                uninspected: diagnostics.uninspected.len(),
+                error: result.as_ref().err().map(|err| err.to_string()),
+            })
+            .collect(),
+    };
+    // A summary written to stdout replaces the usual output, so it can be
+    // parsed as is
+    let report = args.summary.is_none() || args.summary_file.is_some();
+
+    // A port failing does not keep the others from being handled, the
+    // failures are reported as they come and make up the exit code
+    let mut failed_ports: Vec<Port> = vec![];
+    let mut permission_denied_failure = false;
+
+    for (port, diagnostics, result) in outcomes {
+        let permission_denied = !diagnostics.uninspected.is_empty()
+            || matches!(&result, Err(err) if err.kind() == ErrorKind::PermissionDenied);
+
+        if !diagnostics.uninspected.is_empty() {
+            warn!(
+                "{} processes could not be inspected (run with sudo)",
+                diagnostics.uninspected.len()
+            );
+        }
+
+        match result {
             Ok(killed_services) => {
                 if killed_services.is_empty() {
-                    println!("No {} found using port {}", service_type_singular, port);
+                    let explanations: Vec<String> =
+                        describe_kernel_sockets(port, &diagnostics.kernel_sockets)
+                            .into_iter()
+                            .chain(describe_orphans(port, &diagnostics.orphans))
+                            .collect();
+
+                    // Processes that could not be inspected may well hold the
+                    // port, so nothing is claimed about them
+                    if explanations.is_empty()
+                        && diagnostics.skipped.is_empty()
+                        && diagnostics.uninspected.is_empty()
+                        && report
+                    {
+                        println!("No {} found using port {}", service_type_singular, port);
+                    }
+                    for line in explanations.into_iter().filter(|_| report) {
+                        println!("{}", line);
+                    }
+                    if args.why && report {
+                        for line in diagnostics.explain(port) {
+                            println!("{}", line);
+                        }
+                    }
                 } else {
-                    for (killable_type, name) in killed_services {
+                    for target in killed_services.into_iter().filter(|_| report) {
+                        let (verb, done) = action_verbs(&target.target_type, args.container_action);
                         let action = if args.dry_run {
-                            "Would kill"
+                            format!("Would {}", verb)
                         } else {
-                            "Successfully killed"
+                            format!("Successfully {}", done)
+                        };
+                        let relation = match target.target_type {
+                            KillableType::Volume | KillableType::NetworkEndpoint => {
+                                "of the container on"
+                            }
+                            _ => "listening on",
+                        };
+                        // Only a target holding several sockets, e.g. on both
+                        // IPv4 and IPv6, has them listed
+                        let sockets = match target.sockets.len() {
+                            0 | 1 => String::new(),
+                            count => format!(
+                                " ({} sockets: {})",
+                                count,
+                                target.socket_kinds().join(", ")
+                            ),
                         };
                         println!(
-                            "{} {} '{}' listening on port {}",
-                            action, killable_type, name, port
+                            "{} {} '{}' {} port {}{}",
+                            action, target.target_type, target.name, relation, port, sockets
                         );
                     }
                 }
             }
             Err(err) => {
-                error!("{}", err);
-                exit(1);
+                if args.ports.len() > 1 {
+                    error!("Port {}: {}", port, err);
+                } else {
+                    error!("{}", err);
+                }
+                failed_ports.push(port);
+                permission_denied_failure |= permission_denied;
+            }
+        }
+    }
+
+    #[cfg(any(unix, windows))]
+    if permission_denied_failure {
+        error!("{}", elevate::escalation_hint());
+    }
+
+    for line in timings::describe_timings(&timings::timings()) {
+        eprintln!("{}", line);
+    }
+
+    if args.summary.is_some() {
+        if let Err(err) = summary.write(args.summary_file.as_deref()) {
+            error!("{}", err);
+            exit(1);
+        }
+    }
+
+    if !failed_ports.is_empty() {
+        if args.ports.len() > 1 {
+            let ports: Vec<String> = failed_ports.iter().map(Port::to_string).collect();
+            error!(
+                "{} of {} ports failed: {}",
+                failed_ports.len(),
+                args.ports.len(),
+                ports.join(", ")
+            );
+        }
+        exit(summary.exit_code());
+    }
+
+    #[cfg(target_os = "macos")]
+    if args.block {
+        if args.dry_run {
+            for port in args.ports.iter().filter(|_| report) {
+                println!("Would block new connections to port {}", port);
             }
+        } else if let Err(err) = killport::macos::block_ports_until_interrupted(&args.ports) {
+            error!("{}", err);
+            exit(1);
         }
     }
 }
@@ -0,0 +1,99 @@
+use crate::service::run_command;
+use crate::signal::KillportSignal;
+use log::debug;
+use std::io::Error;
+use std::time::Duration;
+
+/// A podman pod, whose infra container holds the published ports of every
+/// container in the pod.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PodmanPod {
+    pub name: String,
+}
+
+impl PodmanPod {
+    /// Finds the pod a container is the infra container of.
+    ///
+    /// Podman names infra containers `<pod ID>-infra`, so other containers
+    /// are not looked up. Returns `None` when podman is not installed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The container name.
+    /// * `id` - The container ID.
+    pub fn find_by_infra_container(name: &str, id: &str) -> Option<Self> {
+        if !name.ends_with("-infra") {
+            return None;
+        }
+
+        let filter = format!("id={}", id);
+        let output = match run_command(
+            "podman",
+            &[
+                "ps",
+                "--all",
+                "--filter",
+                &filter,
+                "--format",
+                "{{.PodName}}",
+            ],
+        ) {
+            Ok(output) => output,
+            Err(e) => {
+                debug!("Failed to look up the pod of container {}: {}", name, e);
+                return None;
+            }
+        };
+
+        output
+            .lines()
+            .map(str::trim)
+            .find(|pod| !pod.is_empty())
+            .map(|pod| PodmanPod {
+                name: pod.to_string(),
+            })
+    }
+
+    /// Kill every container of the pod through podman.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - A enum value representing the signal type.
+    pub fn kill_pod(&self, signal: KillportSignal) -> Result<(), Error> {
+        run_command(
+            "podman",
+            &[
+                "pod",
+                "kill",
+                "--signal",
+                signal.container_signal()?,
+                &self.name,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Stop the pod through podman, then remove it if asked to.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Time given to the containers to stop before they are
+    ///   killed, or podman's default when `None`.
+    /// * `remove` - Remove the pod once stopped.
+    pub fn stop_pod(&self, timeout: Option<Duration>, remove: bool) -> Result<(), Error> {
+        let timeout = timeout.map(|timeout| timeout.as_secs().to_string());
+        let mut args = vec!["pod", "stop"];
+        if let Some(timeout) = &timeout {
+            args.extend(["--time", timeout]);
+        }
+        args.push(&self.name);
+        run_command("podman", &args)?;
+
+        if remove {
+            run_command("podman", &["pod", "rm", "--force", &self.name])?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Service managers that supervise targets and may restart them once killed.
+
+use log::debug;
+#[cfg(not(windows))]
+use std::io::ErrorKind;
+use std::{fmt, io::Error, process::Command};
+
+/// Kind of service manager supervising a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceKind {
+    /// A snap package, confined and supervised by snapd.
+    Snap,
+    /// A flatpak application sandbox.
+    Flatpak,
+    /// A systemd system unit.
+    Systemd,
+    /// A unit of the `systemd --user` instance of the given user ID.
+    SystemdUser(u32),
+    /// A launchd job of the system domain.
+    Launchd,
+    /// A launchd job of the GUI domain of the given user ID.
+    LaunchdGui(u32),
+    /// A Homebrew formula run by `brew services`, itself a launchd job.
+    Homebrew,
+    /// A service registered with the Windows Service Control Manager.
+    WindowsService,
+    /// The macOS AirPlay Receiver, served by Control Center.
+    AirPlayReceiver,
+    /// A `netsh interface portproxy` rule, served by the IP Helper service.
+    PortProxy,
+}
+
+impl fmt::Display for ServiceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ServiceKind::Snap => "snap",
+            ServiceKind::Flatpak => "flatpak",
+            ServiceKind::Systemd => "systemd",
+            ServiceKind::SystemdUser(_) => "systemd --user",
+            ServiceKind::Launchd => "launchd",
+            ServiceKind::LaunchdGui(_) => "launchd gui",
+            ServiceKind::Homebrew => "brew services",
+            ServiceKind::WindowsService => "Windows service",
+            ServiceKind::AirPlayReceiver => "Control Center",
+            ServiceKind::PortProxy => "netsh portproxy",
+        })
+    }
+}
+
+/// A service supervising a target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    pub kind: ServiceKind,
+    /// Name of the service as understood by its manager, e.g. the snap
+    /// `<snap>.<app>`, the flatpak application ID, the systemd unit, the
+    /// launchd job label, the Homebrew formula, the Windows service name or
+    /// the portproxy rule.
+    pub name: String,
+}
+
+impl Service {
+    pub fn new(kind: ServiceKind, name: String) -> Self {
+        Self { kind, name }
+    }
+
+    /// Stops the service through its manager, so it is not restarted.
+    ///
+    /// Stopping a systemd `.socket` unit also stops the units it activates.
+    pub fn stop(&self) -> Result<(), Error> {
+        match self.kind {
+            ServiceKind::Snap => run_command("snap", &["stop", &self.name]).map(|_| ()),
+            ServiceKind::Flatpak => run_command("flatpak", &["kill", &self.name]).map(|_| ()),
+            ServiceKind::Systemd => stop_unit(None, &self.name),
+            ServiceKind::SystemdUser(uid) => stop_unit(Some(uid), &self.name),
+            ServiceKind::Launchd => {
+                run_command("launchctl", &["bootout", &format!("system/{}", self.name)]).map(|_| ())
+            }
+            ServiceKind::LaunchdGui(uid) => run_command(
+                "launchctl",
+                &["bootout", &format!("gui/{}/{}", uid, self.name)],
+            )
+            .map(|_| ()),
+            ServiceKind::Homebrew => {
+                run_command("brew", &["services", "stop", &self.name]).map(|_| ())
+            }
+            #[cfg(windows)]
+            ServiceKind::WindowsService => crate::windows::stop_service(&self.name),
+            #[cfg(not(windows))]
+            ServiceKind::WindowsService => Err(Error::new(
+                ErrorKind::Unsupported,
+                "Windows services can only be stopped on Windows",
+            )),
+            ServiceKind::AirPlayReceiver => {
+                run_command(
+                    "defaults",
+                    &[
+                        "-currentHost",
+                        "write",
+                        "com.apple.controlcenter",
+                        "AirplayRecieverEnabled",
+                        "-bool",
+                        "false",
+                    ],
+                )?;
+                // Control Center is relaunched by launchd and picks up the setting
+                run_command("killall", &["ControlCenter"]).map(|_| ())
+            }
+            #[cfg(windows)]
+            ServiceKind::PortProxy => crate::windows::delete_portproxy_rule(&self.name),
+            #[cfg(not(windows))]
+            ServiceKind::PortProxy => Err(Error::new(
+                ErrorKind::Unsupported,
+                "Portproxy rules can only be deleted on Windows",
+            )),
+        }
+    }
+
+    /// Explains why the process found on the port is left alone unless the
+    /// service is stopped, either because signaling it is harmful or futile.
+    pub fn keep_process_reason(&self) -> Option<&'static str> {
+        if self.holds_socket() {
+            return Some("on behalf of a socket-activated service; pass --stop-service to stop the socket and the units it activates");
+        }
+
+        match self.kind {
+            ServiceKind::AirPlayReceiver => Some("which relaunches immediately when killed; turn off AirPlay Receiver in System Settings > General > AirDrop & Handoff, or pass --stop-service to turn it off"),
+            ServiceKind::PortProxy => Some("through the IP Helper service, which serves every portproxy rule; pass --stop-service to delete the rule"),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the service manager itself holds the socket, as with
+    /// systemd socket activation. The process found on the port is then the
+    /// manager, which must never be signaled.
+    pub fn holds_socket(&self) -> bool {
+        matches!(
+            self.kind,
+            ServiceKind::Systemd | ServiceKind::SystemdUser(_)
+        ) && self.name.ends_with(".socket")
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.kind)
+    }
+}
+
+/// Stops a systemd unit, together with the units it activates when it is a
+/// `.socket` unit.
+///
+/// # Arguments
+///
+/// * `user` - The user ID owning the `systemd --user` instance, or `None` for
+///   the system manager.
+/// * `unit` - The unit name.
+fn stop_unit(user: Option<u32>, unit: &str) -> Result<(), Error> {
+    let mut units = vec![unit.to_string()];
+
+    if unit.ends_with(".socket") {
+        let triggers = systemctl(user, &["show", "--property=Triggers", "--value", unit])?;
+        units.extend(triggers.split_whitespace().map(str::to_string));
+    }
+
+    let mut args = vec!["stop"];
+    args.extend(units.iter().map(String::as_str));
+    systemctl(user, &args).map(|_| ())
+}
+
+/// Runs `systemctl` against the system manager, or against the
+/// `systemd --user` instance of the given user through its session bus.
+///
+/// When running as another user (typically root through sudo) the user
+/// manager is reached with `--machine=<user>@`.
+///
+/// # Arguments
+///
+/// * `user` - The user ID owning the `systemd --user` instance, or `None` for
+///   the system manager.
+/// * `args` - The arguments to pass to `systemctl`.
+pub(crate) fn systemctl(user: Option<u32>, args: &[&str]) -> Result<String, Error> {
+    let Some(uid) = user else {
+        return run_command("systemctl", args);
+    };
+
+    #[cfg(unix)]
+    {
+        use nix::unistd::{geteuid, Uid, User};
+
+        if geteuid().as_raw() != uid {
+            let user = User::from_uid(Uid::from_raw(uid))
+                .ok()
+                .flatten()
+                .ok_or_else(|| Error::other(format!("Unknown user ID {}", uid)))?;
+            let machine = format!("--machine={}@", user.name);
+            let mut user_args = vec!["--user", machine.as_str()];
+            user_args.extend_from_slice(args);
+            return run_command("systemctl", &user_args);
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = uid;
+
+    let mut user_args = vec!["--user"];
+    user_args.extend_from_slice(args);
+    run_command("systemctl", &user_args)
+}
+
+/// Runs a service manager command, turning a non-zero exit into an error.
+///
+/// Returns the standard output of the command.
+///
+/// # Arguments
+///
+/// * `program` - The program to run.
+/// * `args` - The arguments to pass to the program.
+pub(crate) fn run_command(program: &str, args: &[&str]) -> Result<String, Error> {
+    debug!("Running {} {}", program, args.join(" "));
+
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| Error::new(e.kind(), format!("Failed to run {}: {}", program, e)))?;
+
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -1,6 +1,10 @@
 //! Wrapper around signals for platforms that they are not supported on
 
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::Display,
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
 
 #[cfg(unix)]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,7 +13,198 @@ pub struct KillportSignal(pub nix::sys::signal::Signal);
 /// On a platform where we don't have the proper signals enum
 #[cfg(not(unix))]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct KillportSignal(pub String);
+pub struct KillportSignal(pub Signal);
+
+/// Signals accepted on a platform without native signals, named the same as
+/// the `nix` signals used on Unix. Each platform decides how to carry them
+/// out, and rejects the ones it has no equivalent for when killing.
+#[cfg(not(unix))]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    SIGHUP,
+    SIGINT,
+    SIGQUIT,
+    SIGILL,
+    SIGTRAP,
+    SIGABRT,
+    SIGBUS,
+    SIGFPE,
+    SIGKILL,
+    SIGUSR1,
+    SIGSEGV,
+    SIGUSR2,
+    SIGPIPE,
+    SIGALRM,
+    SIGTERM,
+    SIGCHLD,
+    SIGCONT,
+    SIGSTOP,
+    SIGTSTP,
+    SIGTTIN,
+    SIGTTOU,
+    SIGURG,
+    SIGXCPU,
+    SIGXFSZ,
+    SIGVTALRM,
+    SIGPROF,
+    SIGWINCH,
+    SIGIO,
+    SIGPWR,
+    SIGSYS,
+    /// Ctrl+Break, only meaningful on Windows
+    SIGBREAK,
+}
+
+#[cfg(not(unix))]
+impl Signal {
+    const ALL: [Signal; 31] = [
+        Signal::SIGHUP,
+        Signal::SIGINT,
+        Signal::SIGQUIT,
+        Signal::SIGILL,
+        Signal::SIGTRAP,
+        Signal::SIGABRT,
+        Signal::SIGBUS,
+        Signal::SIGFPE,
+        Signal::SIGKILL,
+        Signal::SIGUSR1,
+        Signal::SIGSEGV,
+        Signal::SIGUSR2,
+        Signal::SIGPIPE,
+        Signal::SIGALRM,
+        Signal::SIGTERM,
+        Signal::SIGCHLD,
+        Signal::SIGCONT,
+        Signal::SIGSTOP,
+        Signal::SIGTSTP,
+        Signal::SIGTTIN,
+        Signal::SIGTTOU,
+        Signal::SIGURG,
+        Signal::SIGXCPU,
+        Signal::SIGXFSZ,
+        Signal::SIGVTALRM,
+        Signal::SIGPROF,
+        Signal::SIGWINCH,
+        Signal::SIGIO,
+        Signal::SIGPWR,
+        Signal::SIGSYS,
+        Signal::SIGBREAK,
+    ];
+}
+
+#[cfg(not(unix))]
+impl Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Signals of Linux with their numbers. Container runtimes run Linux
+/// containers and accept these names, whatever the host platform is.
+const LINUX_SIGNALS: [(&str, i32); 31] = [
+    ("SIGHUP", 1),
+    ("SIGINT", 2),
+    ("SIGQUIT", 3),
+    ("SIGILL", 4),
+    ("SIGTRAP", 5),
+    ("SIGABRT", 6),
+    ("SIGBUS", 7),
+    ("SIGFPE", 8),
+    ("SIGKILL", 9),
+    ("SIGUSR1", 10),
+    ("SIGSEGV", 11),
+    ("SIGUSR2", 12),
+    ("SIGPIPE", 13),
+    ("SIGALRM", 14),
+    ("SIGTERM", 15),
+    ("SIGSTKFLT", 16),
+    ("SIGCHLD", 17),
+    ("SIGCONT", 18),
+    ("SIGSTOP", 19),
+    ("SIGTSTP", 20),
+    ("SIGTTIN", 21),
+    ("SIGTTOU", 22),
+    ("SIGURG", 23),
+    ("SIGXCPU", 24),
+    ("SIGXFSZ", 25),
+    ("SIGVTALRM", 26),
+    ("SIGPROF", 27),
+    ("SIGWINCH", 28),
+    ("SIGIO", 29),
+    ("SIGPWR", 30),
+    ("SIGSYS", 31),
+];
+
+impl KillportSignal {
+    /// Returns SIGTERM, which asks the target to shut down cleanly.
+    pub fn terminate() -> Self {
+        #[cfg(unix)]
+        return KillportSignal(nix::sys::signal::Signal::SIGTERM);
+
+        #[cfg(not(unix))]
+        KillportSignal(Signal::SIGTERM)
+    }
+
+    /// Returns SIGINT, which PostgreSQL takes as a fast shutdown.
+    pub fn interrupt() -> Self {
+        #[cfg(unix)]
+        return KillportSignal(nix::sys::signal::Signal::SIGINT);
+
+        #[cfg(not(unix))]
+        KillportSignal(Signal::SIGINT)
+    }
+
+    /// Returns SIGKILL, which ends the target without giving it a chance to
+    /// clean up.
+    pub fn kill() -> Self {
+        #[cfg(unix)]
+        return KillportSignal(nix::sys::signal::Signal::SIGKILL);
+
+        #[cfg(not(unix))]
+        KillportSignal(Signal::SIGKILL)
+    }
+
+    /// Returns true if the signal is SIGKILL, which cannot be handled.
+    pub fn is_kill(&self) -> bool {
+        self.to_string() == "SIGKILL"
+    }
+
+    /// Returns true if the signal is meant to end the target, unlike e.g.
+    /// SIGHUP, which many servers reload on, or SIGSTOP.
+    pub fn ends_process(&self) -> bool {
+        ["SIGKILL", "SIGTERM", "SIGINT", "SIGQUIT"].contains(&self.to_string().as_str())
+    }
+
+    /// Returns the names of the signals accepted on this platform.
+    pub fn names() -> Vec<String> {
+        #[cfg(unix)]
+        return nix::sys::signal::Signal::iterator()
+            .map(|signal| signal.as_str().to_string())
+            .collect();
+
+        #[cfg(not(unix))]
+        Signal::ALL.iter().map(Signal::to_string).collect()
+    }
+
+    /// Returns the name of the signal as accepted by container runtimes,
+    /// failing for signals Linux containers have no equivalent for, such as
+    /// SIGINFO of macOS or SIGBREAK of Windows.
+    pub fn container_signal(&self) -> Result<&'static str, Error> {
+        let name = self.to_string();
+
+        LINUX_SIGNALS
+            .iter()
+            .find(|(linux_name, _)| *linux_name == name)
+            .map(|(linux_name, _)| *linux_name)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} has no equivalent for Linux containers", name),
+                )
+            })
+    }
+}
 
 impl Display for KillportSignal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -20,16 +215,122 @@ impl Display for KillportSignal {
 impl FromStr for KillportSignal {
     type Err = std::io::Error;
 
+    /// Parses a signal given by name, with or without the `SIG` prefix and
+    /// in any case, or by number, e.g. "SIGTERM", "term" or "15". A leading
+    /// dash is allowed, as in `kill -TERM` or `kill -15`.
     fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let input = value;
+        let value = value.trim();
+        let value = value.strip_prefix('-').unwrap_or(value).to_uppercase();
+        let name = if value.starts_with("SIG") {
+            value
+        } else {
+            format!("SIG{}", value)
+        };
+
+        let unknown = |name: &str| {
+            let suggestions = match suggest_signals(name).as_slice() {
+                [] => String::new(),
+                [signal] => format!(", did you mean {}?", signal),
+                signals => format!(", did you mean one of {}?", signals.join(", ")),
+            };
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unknown signal {}{} Signals are given by name, with or without SIG, or by number, e.g. SIGTERM, term or 15, and are one of {}",
+                    input,
+                    if suggestions.is_empty() { "." } else { &suggestions },
+                    KillportSignal::names().join(", ")
+                ),
+            )
+        };
+
         #[cfg(unix)]
         {
-            let signal = nix::sys::signal::Signal::from_str(value)?;
-            Ok(KillportSignal(signal))
+            let signal = match name["SIG".len()..].parse::<i32>() {
+                Ok(number) => nix::sys::signal::Signal::try_from(number),
+                Err(_) => nix::sys::signal::Signal::from_str(&name),
+            };
+            signal.map(KillportSignal).map_err(|_| unknown(&name))
         }
 
         #[cfg(not(unix))]
         {
-            Ok(KillportSignal(value.to_string()))
+            // Numbers follow Linux, as Windows has no signal numbers of its own
+            let name = match name["SIG".len()..].parse::<i32>() {
+                Ok(number) => LINUX_SIGNALS
+                    .iter()
+                    .find(|(_, linux_number)| *linux_number == number)
+                    .map(|(linux_name, _)| linux_name.to_string())
+                    .ok_or_else(|| unknown(&name))?,
+                Err(_) => name,
+            };
+
+            Signal::ALL
+                .into_iter()
+                .find(|signal| signal.to_string() == name)
+                .map(KillportSignal)
+                .ok_or_else(|| unknown(&name))
         }
     }
 }
+
+/// Returns the names of the signals closest to `name`, as long as they are
+/// at most two edits away.
+///
+/// # Arguments
+///
+/// * `name` - The unknown signal name, in upper case with the SIG prefix.
+pub fn suggest_signals(name: &str) -> Vec<String> {
+    let candidates: Vec<(usize, String)> = KillportSignal::names()
+        .into_iter()
+        .map(|signal| (edit_distance(name, &signal), signal))
+        .filter(|(distance, _)| *distance <= 2)
+        .collect();
+    let Some(closest) = candidates.iter().map(|(distance, _)| *distance).min() else {
+        return vec![];
+    };
+
+    candidates
+        .into_iter()
+        .filter(|(distance, _)| *distance == closest)
+        .map(|(_, signal)| signal)
+        .collect()
+}
+
+/// Returns the edit distance between two strings, i.e. the number of
+/// characters to insert, delete or replace, or adjacent characters to swap,
+/// to turn one into the other.
+///
+/// # Arguments
+///
+/// * `a` - The first string.
+/// * `b` - The second string.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i characters of a
+    // and the first j characters of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replace = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = replace
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
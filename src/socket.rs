@@ -0,0 +1,330 @@
+//! Socket level details surfaced by the platform backends.
+
+use crate::cli::Port;
+use std::{
+    fmt,
+    io::{Error, ErrorKind},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Transport protocol of a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Sctp,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+            Protocol::Sctp => "sctp",
+        })
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = Error;
+
+    /// Parses a protocol by name, in any case, e.g. "tcp" or "UDP".
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "tcp" => Ok(Protocol::Tcp),
+            "udp" => Ok(Protocol::Udp),
+            "sctp" => Ok(Protocol::Sctp),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown protocol '{}'", value),
+            )),
+        }
+    }
+}
+
+/// Connection state of a socket, following the TCP state machine.
+///
+/// UDP sockets only ever report `Established` (connected) or `Close` (bound).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketState {
+    Established,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+}
+
+impl SocketState {
+    /// Short explanation of why a socket in this state can outlive its process.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            SocketState::TimeWait => {
+                "the connection was closed and the kernel keeps it around briefly to absorb delayed packets; no process holds it, so there is nothing to kill and it clears on its own, while a server can bind the port in the meantime by setting SO_REUSEADDR"
+            }
+            SocketState::FinWait1
+            | SocketState::FinWait2
+            | SocketState::Closing
+            | SocketState::LastAck => {
+                "the connection is shutting down and is waiting for the remote peer to finish closing it"
+            }
+            _ => "the socket is not attached to any process and is managed by the kernel",
+        }
+    }
+}
+
+impl fmt::Display for SocketState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SocketState::Established => "ESTABLISHED",
+            SocketState::SynSent => "SYN_SENT",
+            SocketState::SynRecv => "SYN_RECV",
+            SocketState::FinWait1 => "FIN_WAIT1",
+            SocketState::FinWait2 => "FIN_WAIT2",
+            SocketState::TimeWait => "TIME_WAIT",
+            SocketState::Close => "CLOSE",
+            SocketState::CloseWait => "CLOSE_WAIT",
+            SocketState::LastAck => "LAST_ACK",
+            SocketState::Listen => "LISTEN",
+            SocketState::Closing => "CLOSING",
+        })
+    }
+}
+
+/// A socket bound to the port that is not owned by any process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanSocket {
+    pub protocol: Protocol,
+    pub state: SocketState,
+    /// Time left until the kernel releases the socket, when known.
+    pub remaining: Option<Duration>,
+}
+
+/// A socket bound to the port by the kernel itself rather than by a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelSocket {
+    pub protocol: Protocol,
+    /// The kernel subsystem most likely holding the socket, when it can be guessed.
+    pub subsystem: Option<&'static str>,
+}
+
+/// A process holding sockets, as listed by a command rather than read from
+/// the system directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketHolder {
+    pub pid: u32,
+    pub name: String,
+    /// The sockets held on the port, e.g. "tcp6".
+    pub sockets: Vec<String>,
+}
+
+impl SocketHolder {
+    /// Adds a socket held on the port, unless one of the same protocol and
+    /// IP version was already added.
+    ///
+    /// # Arguments
+    ///
+    /// * `protocol` - The protocol of the socket.
+    /// * `ipv6` - Whether the socket is an IPv6 one.
+    pub fn add_socket(&mut self, protocol: Protocol, ipv6: bool) {
+        let socket = match ipv6 {
+            true => format!("{}6", protocol),
+            false => protocol.to_string(),
+        };
+        if !self.sockets.contains(&socket) {
+            self.sockets.push(socket);
+        }
+    }
+}
+
+/// Parses the output of `procfiles -n`, as on AIX, returning every process
+/// listed along with the sockets it holds on `port`, if any.
+///
+/// Processes are headed by a line like `  3932192 : /usr/sbin/sshd`, and
+/// each socket is described by its type, e.g. `SOCK_STREAM`, then its local
+/// address, e.g. `sockname: AF_INET 0.0.0.0  port: 22`.
+///
+/// # Arguments
+///
+/// * `output` - The procfiles output.
+/// * `port` - The port, possibly restricted to a protocol.
+pub fn parse_procfiles(output: &str, port: Port) -> Vec<SocketHolder> {
+    let mut holders: Vec<SocketHolder> = vec![];
+    let mut protocol = None;
+
+    for line in output.lines().map(str::trim) {
+        let header = line.split_once(" : ").and_then(|(pid, name)| {
+            Some((pid.trim().parse::<u32>().ok()?, name.trim().to_string()))
+        });
+
+        if let Some((pid, name)) = header {
+            holders.push(SocketHolder {
+                pid,
+                name,
+                sockets: vec![],
+            });
+            protocol = None;
+        } else if line.contains(": S_IF") {
+            // The next file descriptor, which may not be a socket
+            protocol = None;
+        } else if line.starts_with("SOCK_STREAM") {
+            protocol = Some(Protocol::Tcp);
+        } else if line.starts_with("SOCK_DGRAM") {
+            protocol = Some(Protocol::Udp);
+        } else if let Some(address) = line.strip_prefix("sockname:") {
+            let local_port = address
+                .rsplit_once("port:")
+                .and_then(|(_, local_port)| local_port.trim().parse::<u16>().ok());
+            let (Some(holder), Some(protocol), Some(local_port)) =
+                (holders.last_mut(), protocol, local_port)
+            else {
+                continue;
+            };
+            if local_port == port.number() && port.carries(protocol) {
+                holder.add_socket(protocol, address.trim_start().starts_with("AF_INET6"));
+            }
+        }
+    }
+
+    holders
+}
+
+/// Parses the output of BSD `netstat -an -p tcp`, as on macOS, returning
+/// the connections on local port `port` left in `TIME_WAIT`.
+///
+/// # Arguments
+///
+/// * `output` - The netstat output, e.g. a line
+///   `tcp4  0  0  127.0.0.1.8080  127.0.0.1.52345  TIME_WAIT`.
+/// * `port` - The local port of the connections.
+pub fn parse_netstat_time_wait(output: &str, port: u16) -> Vec<OrphanSocket> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [protocol, _, _, local, _, "TIME_WAIT"] = fields.as_slice() else {
+                return None;
+            };
+            // Addresses end with their port after a dot, e.g. "*.8080"
+            let local_port = local.rsplit('.').next()?.parse::<u16>().ok()?;
+            (protocol.starts_with("tcp") && local_port == port).then_some(OrphanSocket {
+                protocol: Protocol::Tcp,
+                state: SocketState::TimeWait,
+                remaining: None,
+            })
+        })
+        .collect()
+}
+
+/// Describes the kernel-owned sockets found on `port`, one line per protocol
+/// and subsystem combination.
+///
+/// # Arguments
+///
+/// * `port` - The port the sockets are bound to.
+/// * `sockets` - The sockets held by the kernel.
+pub fn describe_kernel_sockets(port: Port, sockets: &[KernelSocket]) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for socket in sockets {
+        let line = match socket.subsystem {
+            Some(subsystem) => format!(
+                "Port {} ({}) is held by the kernel ({}), not by a process, so there is nothing to kill; stop or reconfigure that subsystem to release it",
+                port, socket.protocol, subsystem
+            ),
+            None => format!(
+                "Port {} ({}) is not held by any visible process; it most likely belongs to a kernel subsystem or to a process in another PID namespace, so there is nothing to kill here",
+                port, socket.protocol
+            ),
+        };
+
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    }
+
+    lines
+}
+
+/// Describes the orphaned sockets found on `port`, one line per protocol and
+/// state combination.
+///
+/// # Arguments
+///
+/// * `port` - The port the sockets are bound to.
+/// * `orphans` - The sockets without an owning process.
+pub fn describe_orphans(port: Port, orphans: &[OrphanSocket]) -> Vec<String> {
+    let mut groups: Vec<(Protocol, SocketState, usize, Option<Duration>)> = Vec::new();
+
+    for orphan in orphans {
+        match groups
+            .iter_mut()
+            .find(|(protocol, state, _, _)| *protocol == orphan.protocol && *state == orphan.state)
+        {
+            Some((_, _, count, remaining)) => {
+                *count += 1;
+                *remaining = (*remaining).max(orphan.remaining);
+            }
+            None => groups.push((orphan.protocol, orphan.state, 1, orphan.remaining)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(protocol, state, count, remaining)| {
+            let sockets = if count == 1 { "socket" } else { "sockets" };
+            let release = match remaining {
+                Some(remaining) => format!(
+                    ", released automatically in {}s",
+                    remaining.as_secs_f64().ceil() as u64
+                ),
+                None => String::new(),
+            };
+            format!(
+                "Port {} has {} {} {} in {} with no owning process{}: {}",
+                port,
+                count,
+                protocol,
+                sockets,
+                state,
+                release,
+                state.explanation()
+            )
+        })
+        .collect()
+}
+
+/// Splits an extended socket table, as returned by `GetExtendedTcpTable` and
+/// `GetExtendedUdpTable` on Windows, into its rows.
+///
+/// The table starts with its number of rows as a native-endian `u32`, and
+/// its rows start at `rows_offset`. Only the rows `table` holds in full are
+/// returned, whatever the count says, so a short buffer is never read past
+/// its end.
+///
+/// # Arguments
+///
+/// * `table` - The bytes of the table.
+/// * `rows_offset` - The offset of the first row in the table.
+/// * `row_size` - The size of a row.
+pub fn extended_table_rows(
+    table: &[u8],
+    rows_offset: usize,
+    row_size: usize,
+) -> impl Iterator<Item = &[u8]> {
+    let count = table
+        .get(..4)
+        .and_then(|count| count.try_into().ok())
+        .map(u32::from_ne_bytes)
+        .unwrap_or(0) as usize;
+    let rows = table.get(rows_offset..).unwrap_or_default();
+    let held = rows.len().checked_div(row_size).unwrap_or(0);
+
+    rows.chunks_exact(row_size.max(1)).take(count.min(held))
+}
@@ -0,0 +1,178 @@
+use crate::cli::ContainerAction;
+use crate::killport::KillableType;
+use serde_json::json;
+use std::fs;
+use std::io::{Error, Write};
+use std::path::Path;
+
+/// A target acted on, or that would be in a dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetSummary {
+    pub target_type: KillableType,
+    pub name: String,
+    /// The action taken on the target, e.g. "kill" or "stop".
+    pub action: &'static str,
+    /// The sockets the target held on the port, e.g. "tcp6".
+    pub sockets: Vec<String>,
+}
+
+/// The result of the run on one port.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortSummary {
+    pub port: u16,
+    /// The targets acted on.
+    pub targets: Vec<TargetSummary>,
+    /// Targets found on the port but deliberately left alone.
+    pub skipped: Vec<(KillableType, String)>,
+    /// Explanations of why nothing was found, such as sockets held by the
+    /// kernel.
+    pub notes: Vec<String>,
+    /// Number of processes whose sockets could not be inspected.
+    pub uninspected: usize,
+    /// The error the port failed with, if any.
+    pub error: Option<String>,
+}
+
+impl PortSummary {
+    /// Returns the status of the port: "error", "nothing_found", "skipped"
+    /// when every target found was left alone, or "done" ("dry_run" when
+    /// nothing was actually done).
+    ///
+    /// # Arguments
+    ///
+    /// * `dry_run` - Whether the run was a dry run.
+    pub fn status(&self, dry_run: bool) -> &'static str {
+        match (&self.error, self.targets.is_empty(), dry_run) {
+            (Some(_), _, _) => "error",
+            (None, true, _) if !self.skipped.is_empty() => "skipped",
+            (None, true, _) => "nothing_found",
+            (None, false, true) => "dry_run",
+            (None, false, false) => "done",
+        }
+    }
+}
+
+/// The results of a run on every port, for CI steps and other tools.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    pub dry_run: bool,
+    /// The result on each port, in the order they were given.
+    pub ports: Vec<PortSummary>,
+}
+
+impl RunSummary {
+    /// Returns the exit code of the run: 1 if any port failed, else 0.
+    pub fn exit_code(&self) -> i32 {
+        match self.ports.iter().any(|port| port.error.is_some()) {
+            true => 1,
+            false => 0,
+        }
+    }
+
+    /// Explains the exit code of the run, naming the first port that failed.
+    pub fn exit_reason(&self) -> String {
+        match self
+            .ports
+            .iter()
+            .find_map(|port| port.error.as_ref().map(|error| (port.port, error)))
+        {
+            Some((port, error)) => format!("port {} failed: {}", port, error),
+            None if self.dry_run => "dry run; nothing was done".to_string(),
+            None => "every port was handled".to_string(),
+        }
+    }
+
+    /// Returns the summary as a JSON document.
+    pub fn to_json(&self) -> serde_json::Value {
+        let count = |status| {
+            self.ports
+                .iter()
+                .filter(|port| port.status(self.dry_run) == status)
+                .count()
+        };
+        let ports: Vec<_> = self
+            .ports
+            .iter()
+            .map(|port| {
+                json!({
+                    "port": port.port,
+                    "status": port.status(self.dry_run),
+                    "targets": port.targets.iter().map(|target| json!({
+                        "type": target.target_type.to_string(),
+                        "name": target.name,
+                        "action": target.action,
+                        "sockets": target.sockets,
+                    })).collect::<Vec<_>>(),
+                    "skipped": port.skipped.iter().map(|(target_type, name)| json!({
+                        "type": target_type.to_string(),
+                        "name": name,
+                    })).collect::<Vec<_>>(),
+                    "notes": port.notes,
+                    "uninspected": port.uninspected,
+                    "error": port.error,
+                })
+            })
+            .collect();
+
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "dry_run": self.dry_run,
+            "ports": ports,
+            "counts": {
+                "ports": self.ports.len(),
+                "targets": self.ports.iter().map(|port| port.targets.len()).sum::<usize>(),
+                "done": count("done") + count("dry_run"),
+                "nothing_found": count("nothing_found"),
+                "skipped": count("skipped"),
+                "failed": count("error"),
+            },
+            "exit_code": self.exit_code(),
+            "exit_reason": self.exit_reason(),
+        })
+    }
+
+    /// Writes the summary as a JSON document to a file, or to stdout when no
+    /// file is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to write the summary to.
+    pub fn write(&self, path: Option<&Path>) -> Result<(), Error> {
+        let document = serde_json::to_string_pretty(&self.to_json()).map_err(Error::other)?;
+        match path {
+            Some(path) => fs::write(path, document + "\n").map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Failed to write summary to {}: {}", path.display(), e),
+                )
+            }),
+            None => writeln!(std::io::stdout(), "{}", document),
+        }
+    }
+}
+
+/// Returns the verb of the action taken on a target and its past participle,
+/// e.g. ("stop", "stopped").
+///
+/// # Arguments
+///
+/// * `killable_type` - The type of the target.
+/// * `container_action` - The action taken on containers.
+pub fn action_verbs(
+    killable_type: &KillableType,
+    container_action: ContainerAction,
+) -> (&'static str, &'static str) {
+    match (killable_type, container_action) {
+        (KillableType::Service, _)
+        | (KillableType::Container | KillableType::PodmanPod, ContainerAction::Stop) => {
+            ("stop", "stopped")
+        }
+        (KillableType::Container | KillableType::PodmanPod, ContainerAction::Rm) => {
+            ("remove", "removed")
+        }
+        (KillableType::Pod, _) => ("delete", "deleted"),
+        (KillableType::Volume, _) => ("remove", "removed"),
+        (KillableType::NetworkEndpoint, _) => ("disconnect", "disconnected"),
+        _ => ("kill", "killed"),
+    }
+}
@@ -0,0 +1,100 @@
+use crate::cli::Port;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Time spent in each phase of the run, if timings are collected.
+static TIMINGS: Mutex<Option<Vec<Timing>>> = Mutex::new(None);
+
+/// Time spent in one phase on one port, over every time it ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub port: Port,
+    /// The phase, e.g. "find processes".
+    pub phase: &'static str,
+    pub elapsed: Duration,
+    /// Number of times the phase ran.
+    pub runs: u32,
+}
+
+/// Starts collecting the time spent in each phase.
+pub fn enable() {
+    *TIMINGS.lock().unwrap_or_else(PoisonError::into_inner) = Some(vec![]);
+}
+
+/// Runs `f` as a phase on `port`, adding the time it takes to the phase
+/// when timings are collected.
+///
+/// # Arguments
+///
+/// * `port` - The port the phase runs on.
+/// * `phase` - The name of the phase, e.g. "probe docker".
+/// * `f` - The work of the phase.
+pub fn time<T>(port: Port, phase: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(port, phase, start.elapsed());
+    result
+}
+
+/// Adds `elapsed` to the time spent in a phase on `port`, if timings are
+/// collected.
+///
+/// # Arguments
+///
+/// * `port` - The port the phase ran on.
+/// * `phase` - The name of the phase.
+/// * `elapsed` - The time the phase took.
+pub fn record(port: Port, phase: &'static str, elapsed: Duration) {
+    let mut timings = TIMINGS.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(timings) = timings.as_mut() else {
+        return;
+    };
+
+    match timings
+        .iter_mut()
+        .find(|timing| timing.port == port && timing.phase == phase)
+    {
+        Some(timing) => {
+            timing.elapsed += elapsed;
+            timing.runs += 1;
+        }
+        None => timings.push(Timing {
+            port,
+            phase,
+            elapsed,
+            runs: 1,
+        }),
+    }
+}
+
+/// Returns the time spent in each phase, in the order the phases first ran,
+/// or nothing if timings are not collected.
+pub fn timings() -> Vec<Timing> {
+    TIMINGS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Describes the time spent in each phase, one line per phase, e.g.
+/// "port 8080: probe docker took 12.3ms".
+///
+/// # Arguments
+///
+/// * `timings` - The time spent in each phase.
+pub fn describe_timings(timings: &[Timing]) -> Vec<String> {
+    timings
+        .iter()
+        .map(|timing| {
+            let runs = match timing.runs {
+                1 => String::new(),
+                runs => format!(" over {} runs", runs),
+            };
+            format!(
+                "port {}: {} took {:.1?}{}",
+                timing.port, timing.phase, timing.elapsed, runs
+            )
+        })
+        .collect()
+}
@@ -1,9 +1,20 @@
+use crate::docker::ContainerProxy;
 use crate::killport::{Killable, KillableType};
+use crate::kubernetes::Pod;
+#[cfg(target_os = "linux")]
+use crate::linux::{
+    container_id, find_child_processes, is_process_alive, parent_process, process_uid,
+};
+#[cfg(target_os = "macos")]
+use crate::macos::{find_child_processes, is_process_alive, parent_process, process_uid};
+use crate::service::Service;
 use crate::signal::KillportSignal;
-use log::info;
+use log::{debug, info};
+use nix::errno::Errno;
 use nix::sys::signal::kill;
-use nix::unistd::Pid;
+use nix::unistd::{getpid, getsid, Pid, Uid, User};
 use std::io::Error;
+use std::time::{Duration, Instant};
 
 /// Process type shared amongst unix-like operating systems
 #[derive(Debug)]
@@ -11,11 +22,51 @@ pub struct UnixProcess {
     /// System native process ID.
     pid: Pid,
     name: String,
+    /// Service supervising the process, if any.
+    service: Option<Service>,
+    /// Kubernetes pod the process runs in or forwards a port to, if any.
+    pod: Option<Pod>,
+    /// Container the process stands in for, if it is a docker-proxy or
+    /// containerd shim.
+    proxy: Option<ContainerProxy>,
+    /// Sockets the process holds on the port, e.g. "tcp6".
+    sockets: Vec<String>,
 }
 
 impl UnixProcess {
     pub fn new(pid: Pid, name: String) -> Self {
-        Self { pid, name }
+        Self {
+            pid,
+            name,
+            service: None,
+            pod: None,
+            proxy: None,
+            sockets: vec![],
+        }
+    }
+
+    /// Attaches the service supervising the process.
+    pub fn with_service(mut self, service: Option<Service>) -> Self {
+        self.service = service;
+        self
+    }
+
+    /// Attaches the Kubernetes pod the process runs in or forwards a port to.
+    pub fn with_pod(mut self, pod: Option<Pod>) -> Self {
+        self.pod = pod;
+        self
+    }
+
+    /// Attaches the container the process stands in for.
+    pub fn with_proxy(mut self, proxy: Option<ContainerProxy>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Attaches the sockets the process holds on the port.
+    pub fn with_sockets(mut self, sockets: Vec<String>) -> Self {
+        self.sockets = sockets;
+        self
     }
 }
 
@@ -28,15 +79,28 @@ impl Killable for UnixProcess {
     fn kill(&self, signal: KillportSignal) -> Result<bool, Error> {
         info!("Killing process '{}' with PID {}", self.name, self.pid);
 
-        kill(self.pid, signal.0).map(|_| true).map_err(|e| {
-            Error::new(
-                std::io::ErrorKind::Other,
+        match kill(self.pid, signal.0) {
+            Ok(()) => Ok(true),
+            // A process that is already gone, e.g. a child that exited along
+            // with its parent, has nothing left to kill
+            Err(Errno::ESRCH) => {
+                info!(
+                    "Process '{}' with PID {} has already exited",
+                    self.name, self.pid
+                );
+                Ok(false)
+            }
+            Err(e) => Err(Error::new(
+                Error::from(e).kind(),
                 format!(
-                    "Failed to kill process '{}' with PID {}: {}",
-                    self.name, self.pid, e
+                    "Failed to kill process '{}' with PID {} owned by {}: {}",
+                    self.name,
+                    self.pid,
+                    self.get_owner().as_deref().unwrap_or("an unknown user"),
+                    e
                 ),
-            )
-        })
+            )),
+        }
     }
 
     /// Returns the type of the killable target.
@@ -48,7 +112,7 @@ impl Killable for UnixProcess {
     /// # Returns
     ///
     /// * `String` - A string that describes the type of the killable target. For a `UnixProcess` it will return "process",
-    /// and for a `DockerContainer` it will return "container".
+    ///   and for a `DockerContainer` it will return "container".
     fn get_type(&self) -> KillableType {
         KillableType::Process
     }
@@ -56,4 +120,251 @@ impl Killable for UnixProcess {
     fn get_name(&self) -> String {
         self.name.to_string()
     }
+
+    fn get_service(&self) -> Option<Service> {
+        self.service.clone()
+    }
+
+    fn get_children(&self) -> Vec<Box<dyn Killable>> {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        return find_child_processes(self.pid)
+            .into_iter()
+            .map(|child| Box::new(child) as Box<dyn Killable>)
+            .collect();
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        vec![]
+    }
+
+    fn wait(&self, timeout: Duration) -> Result<bool, Error> {
+        // Timeouts too long to be added to the current time never expire
+        let deadline = Instant::now().checked_add(timeout);
+
+        loop {
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            let alive = is_process_alive(self.pid);
+            #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+            let alive = kill(self.pid, None) != Err(Errno::ESRCH);
+
+            if !alive {
+                return Ok(true);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            }
+            std::thread::sleep(WAIT_INTERVAL);
+        }
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        return process_uid(self.pid)
+            .and_then(|uid| User::from_uid(Uid::from_raw(uid)).ok().flatten())
+            .map(|user| user.name);
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        None
+    }
+
+    fn get_pod(&self) -> Option<Pod> {
+        self.pod.clone()
+    }
+
+    fn get_container_proxy(&self) -> Option<ContainerProxy> {
+        self.proxy.clone()
+    }
+
+    fn get_pid(&self) -> Option<u32> {
+        Some(self.pid.as_raw() as u32)
+    }
+
+    fn get_sockets(&self) -> Vec<String> {
+        self.sockets.clone()
+    }
+
+    fn get_container_id(&self) -> Option<String> {
+        #[cfg(target_os = "linux")]
+        return container_id(self.pid);
+
+        #[cfg(not(target_os = "linux"))]
+        None
+    }
+
+    fn get_parents(&self, depth: usize) -> Vec<Box<dyn Killable>> {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        return find_parent_processes(self.pid, depth)
+            .into_iter()
+            .map(|parent| Box::new(parent) as Box<dyn Killable>)
+            .collect();
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        {
+            let _ = depth;
+            vec![]
+        }
+    }
+}
+
+/// Interval at which a killed process is checked for having exited.
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Init systems and the daemons starting login sessions, which are never
+/// killed as someone's parent.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const PROTECTED_PARENTS: [&str; 5] = ["init", "systemd", "launchd", "sshd", "login"];
+
+/// Finds up to `depth` processes the process `pid` was spawned by, closest
+/// first.
+///
+/// The walk stops at PID 1, at session leaders such as the shell of a
+/// terminal, at [`PROTECTED_PARENTS`] and at the processes killport itself
+/// runs under.
+///
+/// # Arguments
+///
+/// * `pid` - The process to collect parents for.
+/// * `depth` - The number of parent levels to collect.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn find_parent_processes(pid: Pid, depth: usize) -> Vec<UnixProcess> {
+    let mut own_tree = vec![getpid()];
+    while let Some((parent, _)) = own_tree
+        .last()
+        .and_then(|&current| parent_process(current))
+        .filter(|(parent, _)| parent.as_raw() > 1 && !own_tree.contains(parent))
+    {
+        own_tree.push(parent);
+    }
+
+    let mut parents: Vec<UnixProcess> = vec![];
+    let mut current = pid;
+
+    while parents.len() < depth {
+        let Some((parent, name)) = parent_process(current) else {
+            break;
+        };
+
+        if parent.as_raw() <= 1
+            || own_tree.contains(&parent)
+            || getsid(Some(parent)) == Ok(parent)
+            || PROTECTED_PARENTS.contains(&name.as_str())
+        {
+            debug!(
+                "Not killing parent process '{}' with PID {} of PID {}",
+                name, parent, current
+            );
+            break;
+        }
+
+        debug!(
+            "Found parent process '{}' with PID {} of PID {}",
+            name, parent, current
+        );
+        parents.push(UnixProcess::new(parent, name));
+        current = parent;
+    }
+
+    parents
+}
+
+/// Describes an ssh process that listens on `port` because of a port forward,
+/// e.g. "ssh tunnel to db.internal:5432".
+///
+/// Local (`-L`) and dynamic (`-D`) forwards are read from the ssh client
+/// command line. Remote (`-R`) forwards are held on the server by the
+/// per-connection `sshd: user@...` process, whose command line carries no
+/// forwarding details.
+///
+/// # Arguments
+///
+/// * `args` - The command line of the process.
+/// * `port` - The port the process listens on.
+pub fn describe_ssh_forward(args: &[String], port: u16) -> Option<String> {
+    // Options of the ssh client that take an argument
+    const OPTIONS_WITH_ARGUMENT: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+    let program = args.first()?;
+
+    if let Some(session) = program.strip_prefix("sshd: ") {
+        // Only the per-connection `sshd: user@tty` processes; the daemon
+        // itself, e.g. `sshd: /usr/sbin/sshd -D [listener] ...`, holds the
+        // ssh port rather than a forward
+        let session = session.trim();
+        if session.starts_with('/') || session.contains("[listener]") || !session.contains('@') {
+            return None;
+        }
+        return Some(format!("ssh remote forward of session '{}'", session));
+    }
+
+    if program.rsplit('/').next() != Some("ssh") {
+        return None;
+    }
+
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        let Some(flags) = arg.strip_prefix('-') else {
+            continue;
+        };
+
+        for (index, flag) in flags.char_indices() {
+            if !OPTIONS_WITH_ARGUMENT.contains(flag) {
+                continue;
+            }
+
+            // The argument is either attached to the flag or the next word
+            let attached = &flags[index + flag.len_utf8()..];
+            let value = if attached.is_empty() {
+                rest.next().map(String::as_str)
+            } else {
+                Some(attached)
+            };
+
+            if let Some(description) = value.and_then(|spec| describe_forward(flag, spec, port)) {
+                return Some(description);
+            }
+            break;
+        }
+    }
+
+    None
+}
+
+/// Describes a single `-L` or `-D` forwarding spec if it listens on `port`.
+///
+/// # Arguments
+///
+/// * `flag` - The ssh option the spec was given to.
+/// * `spec` - The forwarding spec, e.g. `[bind_address:]port:host:hostport`.
+/// * `port` - The port the process listens on.
+fn describe_forward(flag: char, spec: &str, port: u16) -> Option<String> {
+    // Split on colons, keeping bracketed IPv6 addresses intact
+    let mut fields: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut bracketed = false;
+    for (index, c) in spec.char_indices() {
+        match c {
+            '[' => bracketed = true,
+            ']' => bracketed = false,
+            ':' if !bracketed => {
+                fields.push(&spec[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&spec[start..]);
+
+    let is_port = |value: &str| value.parse::<u16>().ok() == Some(port);
+
+    match (flag, fields.as_slice()) {
+        ('D', [listen] | [_, listen]) if is_port(listen) => {
+            Some("ssh SOCKS proxy (dynamic forward)".to_string())
+        }
+        ('L', [listen, host, host_port] | [_, listen, host, host_port]) if is_port(listen) => {
+            Some(format!("ssh tunnel to {}:{}", host, host_port))
+        }
+        ('L', [listen, socket] | [_, listen, socket]) if is_port(listen) => {
+            Some(format!("ssh tunnel to {}", socket))
+        }
+        _ => None,
+    }
 }
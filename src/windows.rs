@@ -1,31 +1,75 @@
-use crate::killport::{Killable, KillableType};
-use log::info;
+use crate::cli::Port;
+use crate::killport::{descendants, Diagnostics, Killable, KillableType};
+use crate::service::{run_command, Service, ServiceKind};
+use crate::signal::{KillportSignal, Signal};
+use crate::socket::{extended_table_rows, KernelSocket, OrphanSocket, Protocol, SocketState};
+use log::{debug, info, warn};
 use std::{
-    alloc::{alloc, dealloc, Layout},
+    cell::RefCell,
     collections::{HashMap, HashSet},
     ffi::c_void,
+    fmt::Display,
     io::{Error, ErrorKind, Result},
-    ptr::addr_of,
+    mem::{offset_of, size_of},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     slice,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
 };
+use windows_sys::core::{GUID, HRESULT, PCWSTR, PWSTR};
 use windows_sys::Win32::{
     Foundation::{
-        CloseHandle, GetLastError, BOOL, ERROR_INSUFFICIENT_BUFFER, FALSE, HANDLE,
-        INVALID_HANDLE_VALUE, NO_ERROR, WIN32_ERROR,
+        CloseHandle, GetLastError, BOOL, ERROR_ACCESS_DENIED, ERROR_INSUFFICIENT_BUFFER,
+        ERROR_INVALID_PARAMETER, ERROR_MORE_DATA, ERROR_SERVICE_DOES_NOT_EXIST, FALSE, FILETIME,
+        HANDLE, HWND, INVALID_HANDLE_VALUE, LPARAM, NO_ERROR, TRUE, WAIT_OBJECT_0, WAIT_TIMEOUT,
+        WIN32_ERROR,
     },
     NetworkManagement::IpHelper::{
-        GetExtendedTcpTable, GetExtendedUdpTable, MIB_TCP6ROW_OWNER_MODULE,
-        MIB_TCP6TABLE_OWNER_MODULE, MIB_TCPROW_OWNER_MODULE, MIB_TCPTABLE_OWNER_MODULE,
-        MIB_UDP6ROW_OWNER_MODULE, MIB_UDP6TABLE_OWNER_MODULE, MIB_UDPROW_OWNER_MODULE,
-        MIB_UDPTABLE_OWNER_MODULE, TCP_TABLE_OWNER_MODULE_ALL, UDP_TABLE_OWNER_MODULE,
+        GetExtendedTcpTable, GetExtendedUdpTable, GetOwnerModuleFromTcp6Entry,
+        GetOwnerModuleFromTcpEntry, GetOwnerModuleFromUdp6Entry, GetOwnerModuleFromUdpEntry,
+        MIB_TCP6ROW_OWNER_MODULE, MIB_TCP6TABLE_OWNER_MODULE, MIB_TCPROW_OWNER_MODULE,
+        MIB_TCPTABLE_OWNER_MODULE, MIB_TCP_STATE, MIB_TCP_STATE_CLOSE_WAIT, MIB_TCP_STATE_CLOSING,
+        MIB_TCP_STATE_ESTAB, MIB_TCP_STATE_FIN_WAIT1, MIB_TCP_STATE_FIN_WAIT2,
+        MIB_TCP_STATE_LAST_ACK, MIB_TCP_STATE_LISTEN, MIB_TCP_STATE_SYN_RCVD,
+        MIB_TCP_STATE_SYN_SENT, MIB_TCP_STATE_TIME_WAIT, MIB_UDP6ROW_OWNER_MODULE,
+        MIB_UDP6TABLE_OWNER_MODULE, MIB_UDPROW_OWNER_MODULE, MIB_UDPTABLE_OWNER_MODULE,
+        TCPIP_OWNER_MODULE_BASIC_INFO, TCPIP_OWNER_MODULE_INFO_BASIC,
+        TCPIP_OWNER_MODULE_INFO_CLASS, TCP_TABLE_OWNER_MODULE_ALL, UDP_TABLE_OWNER_MODULE,
     },
     Networking::WinSock::{AF_INET, AF_INET6},
+    Security::{
+        GetTokenInformation, LookupAccountSidW, TokenUser, SID_NAME_USE, TOKEN_QUERY, TOKEN_USER,
+    },
+    Storage::Packaging::Appx::{GetPackageFamilyName, GetPackageFullName},
     System::{
+        Com::{CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED},
+        Console::{
+            AttachConsole, FreeConsole, GenerateConsoleCtrlEvent, GetConsoleProcessList,
+            SetConsoleCtrlHandler, ATTACH_PARENT_PROCESS, CTRL_BREAK_EVENT, CTRL_C_EVENT,
+        },
         Diagnostics::ToolHelp::{
-            CreateToolhelp32Snapshot, Process32First, Process32Next, PROCESSENTRY32,
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
             TH32CS_SNAPPROCESS,
         },
-        Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE},
+        EventLog::{
+            DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_INFORMATION_TYPE,
+        },
+        Services::{
+            CloseServiceHandle, ControlService, EnumServicesStatusExW, OpenSCManagerW,
+            OpenServiceW, QueryServiceStatus, ENUM_SERVICE_STATUS_PROCESSW, SC_ENUM_PROCESS_INFO,
+            SC_MANAGER_CONNECT, SC_MANAGER_ENUMERATE_SERVICE, SERVICE_ACTIVE, SERVICE_CONTROL_STOP,
+            SERVICE_QUERY_STATUS, SERVICE_STATUS, SERVICE_STOP, SERVICE_STOPPED, SERVICE_WIN32,
+        },
+        Threading::{
+            GetProcessTimes, OpenProcess, OpenProcessToken, QueryFullProcessImageNameW,
+            TerminateProcess, WaitForSingleObject, INFINITE, PROCESS_NAME_WIN32,
+            PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE, PROCESS_TERMINATE,
+        },
+    },
+    UI::Shell::PackageDebugSettings,
+    UI::WindowsAndMessaging::{
+        EnumWindows, GetWindow, GetWindowThreadProcessId, IsWindowVisible, PostMessageW, GW_OWNER,
+        WM_CLOSE,
     },
 };
 
@@ -34,7 +78,42 @@ use windows_sys::Win32::{
 pub struct WindowsProcess {
     pid: u32,
     name: String,
-    parent: Option<Box<WindowsProcess>>,
+    /// Service hosted by the process, if any.
+    service: Option<Service>,
+    /// Whether the process runs inside the default WSL distribution, in
+    /// which case `pid` is its Linux process ID.
+    in_wsl: bool,
+    /// Package the process belongs to, if it is a packaged (UWP or MSIX) app.
+    package: Option<Package>,
+    /// Port the process was found on, or that of the target it was found
+    /// through for children and parents.
+    port: Option<u16>,
+    /// Sockets the process holds on the port, e.g. "tcp6".
+    sockets: Vec<String>,
+    /// Handle of the process once it was terminated, kept to wait on its
+    /// exit without the process ID being reused in the meantime.
+    handle: RefCell<Option<ProcessHandle>>,
+}
+
+/// Owned process handle, closed when dropped
+#[derive(Debug)]
+struct ProcessHandle(HANDLE);
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Package identity of a packaged (UWP or MSIX) app
+#[derive(Debug, Clone)]
+struct Package {
+    /// Package family name, e.g. `Microsoft.WindowsTerminal_8wekyb3d8bbwe`
+    family_name: String,
+    /// Package full name, including version and architecture
+    full_name: String,
 }
 
 impl WindowsProcess {
@@ -42,92 +121,1272 @@ impl WindowsProcess {
         Self {
             pid,
             name,
-            parent: None,
+            service: None,
+            in_wsl: false,
+            package: None,
+            port: None,
+            sockets: vec![],
+            handle: RefCell::new(None),
+        }
+    }
+
+    /// Creates a process running inside the default WSL distribution.
+    pub fn new_in_wsl(pid: u32, name: String) -> Self {
+        Self {
+            in_wsl: true,
+            ..Self::new(pid, name)
+        }
+    }
+
+    /// Attaches the service hosted by the process.
+    pub fn with_service(mut self, service: Option<Service>) -> Self {
+        self.service = service;
+        self
+    }
+
+    /// Attaches the sockets the process holds on the port.
+    pub fn with_sockets(mut self, sockets: Vec<String>) -> Self {
+        self.sockets = sockets;
+        self
+    }
+}
+
+/// Finds the processes associated with the specified `port`.
+///
+/// Returns a `Vec` of native processes.
+///
+/// # Arguments
+///
+/// * `port` - Target port, possibly restricted to a protocol
+/// * `diagnostics` - Collects observations made during discovery
+pub fn find_target_processes(
+    port: Port,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<WindowsProcess>> {
+    let lookup_table: ProcessLookupTable = ProcessLookupTable::create()?;
+    let mut entries: Vec<SocketEntry> = Vec::new();
+    let mut pids: HashSet<u32> = HashSet::new();
+
+    let processes = unsafe {
+        if port.carries(Protocol::Tcp) {
+            // Find sockets in the TCP IPv4 table
+            use_extended_table::<MIB_TCPTABLE_OWNER_MODULE>(port.number(), &mut entries)?;
+
+            // Find sockets in the TCP IPv6 table
+            use_extended_table::<MIB_TCP6TABLE_OWNER_MODULE>(port.number(), &mut entries)?;
+        }
+
+        if port.carries(Protocol::Udp) {
+            // Find sockets in the UDP IPv4 table
+            use_extended_table::<MIB_UDPTABLE_OWNER_MODULE>(port.number(), &mut entries)?;
+
+            // Find sockets in the UDP IPv6 table
+            use_extended_table::<MIB_UDP6TABLE_OWNER_MODULE>(port.number(), &mut entries)?;
+        }
+
+        for entry in &entries {
+            match &entry.module {
+                Some(module) => debug!(
+                    "Found socket {} owned by {} in PID {}",
+                    entry, module, entry.pid
+                ),
+                None => debug!("Found socket {} owned by PID {}", entry, entry.pid),
+            }
+
+            match entry.pid {
+                // Kernel mode listeners (HTTP.SYS, SMB) are reported under the System process
+                SYSTEM_PID => {
+                    let socket = KernelSocket {
+                        protocol: entry.protocol,
+                        subsystem: Some(kernel_subsystem(port.number())),
+                    };
+                    if !diagnostics.kernel_sockets.contains(&socket) {
+                        diagnostics.kernel_sockets.push(socket);
+                    }
+                }
+                // Connections outliving their process, such as those in
+                // TIME_WAIT, are reported under the System Idle Process
+                IDLE_PID => diagnostics.orphans.push(OrphanSocket {
+                    protocol: entry.protocol,
+                    state: entry.state,
+                    remaining: None,
+                }),
+                pid => {
+                    pids.insert(pid);
+                }
+            }
+        }
+
+        let mut processes: Vec<WindowsProcess> = Vec::with_capacity(pids.len());
+        let services = if pids.is_empty() {
+            HashMap::new()
+        } else {
+            services_by_pid()
+        };
+
+        let own_tree = lookup_table.own_tree();
+
+        for pid in pids {
+            let mut process_name = lookup_table
+                .process_names
+                .get(&pid)
+                .cloned()
+                .unwrap_or_else(|| "Unknown".to_string());
+
+            match process_image_path(pid) {
+                Some(path) => debug!("Found process {}:{} running {}", process_name, pid, path),
+                None => debug!("Found process {}:{}", process_name, pid),
+            }
+            if let Some(owner) = process_owner(pid) {
+                debug!("Process {}:{} is owned by {}", process_name, pid, owner);
+            }
+
+            // Killing the terminal or shell killport runs in would take
+            // killport and the user's session down with it
+            if own_tree.contains(&pid) {
+                warn!(
+                    "Process {}:{} on port {} runs the terminal killport was started from, so it will not be killed",
+                    process_name, pid, port
+                );
+                diagnostics
+                    .skipped
+                    .push((KillableType::Process, process_name));
+                continue;
+            }
+
+            let module = entries
+                .iter()
+                .filter(|entry| entry.pid == pid)
+                .find_map(|entry| entry.module.as_deref());
+
+            // Shared service hosts run several services, told apart by the
+            // module the socket is owned by
+            let service = match services.get(&pid).map(Vec::as_slice) {
+                // The IP Helper service listens for every portproxy rule, the
+                // rule is what holds the port
+                Some(names) if names.iter().any(|name| is_ip_helper(name)) => {
+                    find_portproxy_service(port.number())
+                }
+                Some([name]) => Some(Service::new(ServiceKind::WindowsService, name.clone())),
+                Some(names) => {
+                    debug!(
+                        "Process {}:{} hosts the services {}",
+                        process_name,
+                        pid,
+                        names.join(", ")
+                    );
+                    match names
+                        .iter()
+                        .find(|name| module.is_some_and(|module| module.eq_ignore_ascii_case(name)))
+                    {
+                        // e.g. "Dnscache (svchost.exe)", so the shared host is
+                        // not mistaken for the owner of the port
+                        Some(name) => {
+                            process_name = format!("{} ({})", name, process_name);
+                            Some(Service::new(ServiceKind::WindowsService, name.clone()))
+                        }
+                        None => None,
+                    }
+                }
+                None => None,
+            };
+
+            // Ports of WSL 2 distributions are forwarded by a relay on the
+            // host, which serves every forwarded port at once
+            if is_wsl_relay(&process_name) {
+                let wsl_processes = find_wsl_processes(port.number());
+                if wsl_processes.is_empty() {
+                    warn!(
+                        "Port {} is forwarded from WSL by {}:{}, which will not be killed as that would break every forwarded port; no listener could be found inside WSL",
+                        port, process_name, pid
+                    );
+                    diagnostics
+                        .skipped
+                        .push((KillableType::Process, process_name));
+                }
+                processes.extend(wsl_processes.into_iter().map(|process| WindowsProcess {
+                    port: Some(port.number()),
+                    ..process
+                }));
+                continue;
+            }
+
+            // A process may hold several sockets on the port, e.g. one per
+            // IP version, and is reported once with all of them
+            let sockets = entries
+                .iter()
+                .filter(|entry| entry.pid == pid)
+                .map(|entry| match entry.local.is_ipv6() {
+                    true => format!("{}6", entry.protocol),
+                    false => entry.protocol.to_string(),
+                })
+                .collect();
+            let mut process = WindowsProcess::new(pid, process_name)
+                .with_service(service)
+                .with_sockets(sockets);
+            process.package = process_package(pid);
+            process.port = Some(port.number());
+
+            processes.push(process);
+        }
+
+        // Hyper-V NAT static mappings are served by the kernel without a
+        // socket of their own
+        if processes.is_empty() {
+            for mapping in find_nat_static_mappings(port.number()) {
+                warn!(
+                    "Port {} is forwarded by Hyper-V NAT static mapping {}; remove it with `Remove-NetNatStaticMapping -StaticMappingID <ID>`",
+                    port, mapping
+                );
+                diagnostics.skipped.push((KillableType::Service, mapping));
+            }
+        }
+
+        // Binding fails on ports reserved by Windows even though no socket
+        // holds them
+        if entries.is_empty() {
+            for protocol in [Protocol::Tcp, Protocol::Udp]
+                .into_iter()
+                .filter(|&protocol| port.carries(protocol))
+            {
+                if let Some((start, end)) = find_excluded_port_range(port.number(), protocol) {
+                    info!(
+                        "Port {} ({}) is in the excluded port range {}-{}",
+                        port, protocol, start, end
+                    );
+                    diagnostics.kernel_sockets.push(KernelSocket {
+                        protocol,
+                        subsystem: Some(EXCLUDED_PORT_RANGE),
+                    });
+                }
+            }
+        }
+
+        processes
+    };
+
+    Ok(processes)
+}
+
+/// Process ID of the System process, which owns the sockets opened by kernel
+/// mode drivers
+const SYSTEM_PID: u32 = 4;
+
+/// Process ID of the System Idle Process, which owns the connections whose
+/// process has exited
+const IDLE_PID: u32 = 0;
+
+/// A socket bound to the target port, as read from the extended TCP and UDP
+/// tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketEntry {
+    /// ID of the process owning the socket
+    pub pid: u32,
+    pub protocol: Protocol,
+    pub local: SocketAddr,
+    /// Peer of a connected TCP socket
+    pub remote: Option<SocketAddr>,
+    pub state: SocketState,
+    /// Module owning the socket, which for services run by a shared service
+    /// host is the name of the service
+    pub module: Option<String>,
+}
+
+impl Display for SocketEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.protocol, self.local)?;
+        if let Some(remote) = self.remote {
+            write!(f, " -> {}", remote)?;
+        }
+        write!(f, " ({})", self.state)
+    }
+}
+
+/// Maps the `MIB_TCP_STATE` of a TCP table row to a socket state
+///
+/// # Arguments
+///
+/// * `state` - The row state
+pub fn tcp_state(state: u32) -> SocketState {
+    match state as MIB_TCP_STATE {
+        MIB_TCP_STATE_LISTEN => SocketState::Listen,
+        MIB_TCP_STATE_SYN_SENT => SocketState::SynSent,
+        MIB_TCP_STATE_SYN_RCVD => SocketState::SynRecv,
+        MIB_TCP_STATE_ESTAB => SocketState::Established,
+        MIB_TCP_STATE_FIN_WAIT1 => SocketState::FinWait1,
+        MIB_TCP_STATE_FIN_WAIT2 => SocketState::FinWait2,
+        MIB_TCP_STATE_CLOSE_WAIT => SocketState::CloseWait,
+        MIB_TCP_STATE_CLOSING => SocketState::Closing,
+        MIB_TCP_STATE_LAST_ACK => SocketState::LastAck,
+        MIB_TCP_STATE_TIME_WAIT => SocketState::TimeWait,
+        // MIB_TCP_STATE_CLOSED and MIB_TCP_STATE_DELETE_TCB
+        _ => SocketState::Close,
+    }
+}
+
+/// Converts an address and port, both in network byte order as stored in
+/// the table rows, to a socket address
+///
+/// # Arguments
+///
+/// * `address` - The IP address
+/// * `port` - The port, in the low 16 bits
+fn socket_address(address: impl Into<IpAddr>, port: u32) -> SocketAddr {
+    SocketAddr::new(address.into(), (port as u16).to_be())
+}
+
+/// Guesses which kernel mode driver owns a socket reported under the System
+/// process, based on the well-known ports of each driver
+///
+/// # Arguments
+///
+/// * `port` - Target port number
+fn kernel_subsystem(port: u16) -> &'static str {
+    match port {
+        445 => "SMB server, srv2.sys",
+        137..=139 => "NetBIOS over TCP/IP, netbt.sys",
+        _ => "HTTP.SYS, see `netsh http show servicestate`",
+    }
+}
+
+impl Killable for WindowsProcess {
+    fn kill(&self, signal: KillportSignal) -> Result<bool> {
+        if self.in_wsl {
+            kill_wsl_process(self, &signal)?;
+            return Ok(true);
+        }
+
+        // Reject signals without a Windows equivalent before killing anything
+        console_ctrl_event(&signal)?;
+
+        unsafe {
+            // The image and owner can no longer be looked up once it is gone
+            let audit = EVENT_LOG
+                .load(Ordering::Relaxed)
+                .then(|| (process_image_path(self.pid), process_owner(self.pid)));
+
+            kill_process(self, &signal)?;
+
+            if let Some((path, owner)) = audit {
+                report_kill_event(self, path, owner, &signal);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn get_type(&self) -> KillableType {
+        KillableType::Process
+    }
+
+    fn get_name(&self) -> String {
+        match &self.package {
+            Some(package) => format!("{} ({})", self.name, package.family_name),
+            None => self.name.to_string(),
+        }
+    }
+
+    fn get_service(&self) -> Option<Service> {
+        self.service.clone()
+    }
+
+    fn wait(&self, timeout: Duration) -> Result<bool> {
+        // Processes that shut down gracefully were already waited on, and
+        // those that were gone already have nothing to wait for
+        let Some(handle) = self.handle.borrow_mut().take() else {
+            return Ok(true);
+        };
+
+        // INFINITE is u32::MAX, so longer timeouts stop just short of it
+        let timeout_ms = timeout.as_millis().min(u128::from(INFINITE - 1)) as u32;
+        Ok(unsafe { WaitForSingleObject(handle.0, timeout_ms) } == WAIT_OBJECT_0)
+    }
+
+    fn get_pid(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+
+    fn get_sockets(&self) -> Vec<String> {
+        self.sockets.clone()
+    }
+
+    fn get_owner(&self) -> Option<String> {
+        if self.in_wsl {
+            return None;
+        }
+
+        unsafe { process_owner(self.pid) }
+    }
+
+    fn get_children(&self) -> Vec<Box<dyn Killable>> {
+        if self.in_wsl {
+            return vec![];
+        }
+
+        let Ok(lookup_table) = ProcessLookupTable::create() else {
+            return vec![];
+        };
+
+        find_child_processes(&lookup_table, self.pid)
+            .into_iter()
+            .map(|child| {
+                Box::new(WindowsProcess {
+                    port: self.port,
+                    ..child
+                }) as Box<dyn Killable>
+            })
+            .collect()
+    }
+
+    fn get_parents(&self, depth: usize) -> Vec<Box<dyn Killable>> {
+        if self.in_wsl {
+            return vec![];
+        }
+
+        let Ok(lookup_table) = ProcessLookupTable::create() else {
+            return vec![];
+        };
+
+        find_parent_processes(&lookup_table, self.pid, depth)
+            .into_iter()
+            .map(|parent| {
+                Box::new(WindowsProcess {
+                    port: self.port,
+                    ..parent
+                }) as Box<dyn Killable>
+            })
+            .collect()
+    }
+}
+
+/// Shells and terminals, which are never killed as someone's parent
+const SHELL_PROCESSES: [&str; 7] = [
+    "cmd.exe",
+    "powershell.exe",
+    "pwsh.exe",
+    "WindowsTerminal.exe",
+    "OpenConsole.exe",
+    "conhost.exe",
+    "bash.exe",
+];
+
+/// Returns true if the process is a critical system process, a shell or a
+/// terminal, which are never killed for being related to a target
+///
+/// # Arguments
+///
+/// * `name` - The process name
+fn is_protected_process(name: &str) -> bool {
+    CRITICAL_PROCESSES
+        .iter()
+        .chain(SHELL_PROCESSES.iter())
+        .any(|protected| protected.eq_ignore_ascii_case(name))
+}
+
+/// Processes the system cannot run without, which are never killed as
+/// someone's children
+const CRITICAL_PROCESSES: [&str; 9] = [
+    "System",
+    "smss.exe",
+    "csrss.exe",
+    "wininit.exe",
+    "winlogon.exe",
+    "services.exe",
+    "lsass.exe",
+    "svchost.exe",
+    "explorer.exe",
+];
+
+/// Finds the processes spawned by the process `pid`, directly or indirectly,
+/// from a snapshot of the running processes
+///
+/// Windows reuses process IDs and keeps the parent ID of a process after its
+/// parent exited, so a process only counts as a child when it was created
+/// after its parent. Critical system processes are always left out.
+///
+/// # Arguments
+///
+/// * `lookup_table` - The snapshot of the running processes
+/// * `pid` - The parent process ID
+fn find_child_processes(lookup_table: &ProcessLookupTable, pid: u32) -> Vec<WindowsProcess> {
+    let creation_times: HashMap<u32, u64> = lookup_table
+        .process_parents
+        .keys()
+        .filter_map(|&pid| Some((pid, unsafe { process_creation_time(pid) }?)))
+        .collect();
+
+    // A target may have spawned the terminal killport runs in, which is cut
+    // off from the tree together with everything started from it
+    let own_tree = lookup_table.own_tree();
+
+    let parents: Vec<(u32, u32)> = lookup_table
+        .process_parents
+        .iter()
+        .map(|(&child, &parent)| (child, parent))
+        .filter(|(child, _)| !own_tree.contains(child))
+        .filter(
+            |(child, parent)| match (creation_times.get(child), creation_times.get(parent)) {
+                (Some(child), Some(parent)) => child >= parent,
+                _ => true,
+            },
+        )
+        .collect();
+
+    descendants(pid, &parents)
+        .into_iter()
+        .filter_map(|child| {
+            let name = lookup_table.process_names.get(&child)?.clone();
+            if CRITICAL_PROCESSES
+                .iter()
+                .any(|critical| critical.eq_ignore_ascii_case(&name))
+            {
+                return None;
+            }
+            debug!("Found child process {}:{}", name, child);
+            Some(WindowsProcess::new(child, name))
+        })
+        .collect()
+}
+
+/// Returns the creation time of a process, in 100ns intervals since 1601
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+unsafe fn process_creation_time(pid: u32) -> Option<u64> {
+    let handle: HANDLE = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+    if handle == 0 {
+        return None;
+    }
+
+    let mut creation: FILETIME = std::mem::zeroed();
+    let mut exit: FILETIME = std::mem::zeroed();
+    let mut kernel: FILETIME = std::mem::zeroed();
+    let mut user: FILETIME = std::mem::zeroed();
+    let result = GetProcessTimes(handle, &mut creation, &mut exit, &mut kernel, &mut user);
+    CloseHandle(handle);
+
+    (result != FALSE)
+        .then(|| (u64::from(creation.dwHighDateTime) << 32) | u64::from(creation.dwLowDateTime))
+}
+
+/// Returns the full path of the executable image of a process
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+unsafe fn process_image_path(pid: u32) -> Option<String> {
+    let handle: HANDLE = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+    if handle == 0 {
+        return None;
+    }
+
+    // Paths may exceed MAX_PATH with long path support enabled
+    let mut path = vec![0u16; 32768];
+    let mut length = path.len() as u32;
+    let result =
+        QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut length);
+    CloseHandle(handle);
+
+    if result == FALSE {
+        return None;
+    }
+    path.get(..length as usize).map(String::from_utf16_lossy)
+}
+
+/// Returns the account owning a process as `DOMAIN\\name`, e.g.
+/// `NT AUTHORITY\\SYSTEM`
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+unsafe fn process_owner(pid: u32) -> Option<String> {
+    let handle: HANDLE = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+    if handle == 0 {
+        return None;
+    }
+
+    let mut token: HANDLE = 0;
+    let opened = OpenProcessToken(handle, TOKEN_QUERY, &mut token);
+    CloseHandle(handle);
+    if opened == FALSE {
+        return None;
+    }
+
+    // The SID the token user points to is stored after the struct, so the
+    // buffer is sized by the first call
+    let mut length: u32 = 0;
+    GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut length);
+    let mut buffer = vec![0u64; (length as usize).div_ceil(8)];
+    let result = GetTokenInformation(
+        token,
+        TokenUser,
+        buffer.as_mut_ptr().cast(),
+        length,
+        &mut length,
+    );
+    CloseHandle(token);
+    if result == FALSE {
+        return None;
+    }
+    let sid = (*buffer.as_ptr().cast::<TOKEN_USER>()).User.Sid;
+
+    let mut name = [0u16; 256];
+    let mut name_length = name.len() as u32;
+    let mut domain = [0u16; 256];
+    let mut domain_length = domain.len() as u32;
+    let mut sid_use: SID_NAME_USE = 0;
+    if LookupAccountSidW(
+        std::ptr::null(),
+        sid,
+        name.as_mut_ptr(),
+        &mut name_length,
+        domain.as_mut_ptr(),
+        &mut domain_length,
+        &mut sid_use,
+    ) == FALSE
+    {
+        return None;
+    }
+
+    let name = String::from_utf16_lossy(name.get(..name_length as usize)?);
+    let domain = String::from_utf16_lossy(domain.get(..domain_length as usize)?);
+    if domain.is_empty() {
+        Some(name)
+    } else {
+        Some(format!("{}\\{}", domain, name))
+    }
+}
+
+/// Returns the package identity of a packaged (UWP or MSIX) app process,
+/// `None` for processes without one
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+unsafe fn process_package(pid: u32) -> Option<Package> {
+    let handle: HANDLE = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+    if handle == 0 {
+        return None;
+    }
+
+    let family_name = package_name(handle, GetPackageFamilyName);
+    let full_name = package_name(handle, GetPackageFullName);
+    CloseHandle(handle);
+
+    Some(Package {
+        family_name: family_name?,
+        full_name: full_name?,
+    })
+}
+
+/// Reads a package name of a process with `GetPackageFamilyName` or
+/// `GetPackageFullName`
+///
+/// # Arguments
+///
+/// * `handle` - Handle to the process
+/// * `query` - The function reading the name
+unsafe fn package_name(
+    handle: HANDLE,
+    query: unsafe extern "system" fn(HANDLE, *mut u32, PWSTR) -> WIN32_ERROR,
+) -> Option<String> {
+    // Processes without a package fail with APPMODEL_ERROR_NO_PACKAGE
+    let mut length: u32 = 0;
+    if query(handle, &mut length, std::ptr::null_mut()) != ERROR_INSUFFICIENT_BUFFER {
+        return None;
+    }
+
+    let mut name = vec![0u16; length as usize];
+    if query(handle, &mut length, name.as_mut_ptr()) != NO_ERROR {
+        return None;
+    }
+
+    // The length includes the terminating null
+    Some(String::from_utf16_lossy(
+        &name[..length.saturating_sub(1) as usize],
+    ))
+}
+
+/// IID of the IPackageDebugSettings interface
+const IID_PACKAGE_DEBUG_SETTINGS: GUID = GUID::from_u128(0xf27c3930_8029_4ad1_94e3_3dba417810c1);
+
+/// Leading entries of the IPackageDebugSettings vtable, up to
+/// TerminateAllProcesses
+#[repr(C)]
+struct PackageDebugSettingsVtbl {
+    query_interface: usize,
+    add_ref: usize,
+    release: unsafe extern "system" fn(*mut c_void) -> u32,
+    enable_debugging: usize,
+    disable_debugging: usize,
+    suspend: usize,
+    resume: usize,
+    terminate_all_processes: unsafe extern "system" fn(*mut c_void, PCWSTR) -> HRESULT,
+}
+
+/// Terminates every process of a packaged app through the package manager,
+/// which is allowed even when its AppContainer denies PROCESS_TERMINATE
+///
+/// # Arguments
+///
+/// * `package` - The package
+unsafe fn terminate_package(package: &Package) -> Result<()> {
+    let initialized = CoInitializeEx(std::ptr::null(), COINIT_MULTITHREADED as u32) >= 0;
+
+    let mut settings: *mut c_void = std::ptr::null_mut();
+    let mut result: HRESULT = CoCreateInstance(
+        &PackageDebugSettings,
+        std::ptr::null_mut(),
+        CLSCTX_ALL,
+        &IID_PACKAGE_DEBUG_SETTINGS,
+        &mut settings,
+    );
+
+    if result >= 0 {
+        let vtable = &**(settings as *const *const PackageDebugSettingsVtbl);
+        let full_name: Vec<u16> = package
+            .full_name
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect();
+
+        result = (vtable.terminate_all_processes)(settings, full_name.as_ptr());
+        (vtable.release)(settings);
+    }
+
+    if initialized {
+        CoUninitialize();
+    }
+
+    if result < 0 {
+        return Err(Error::other(format!(
+            "Failed to terminate package {}: {:#x}",
+            package.full_name, result
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns true if the process relays ports from WSL 2 distributions to the
+/// host
+///
+/// # Arguments
+///
+/// * `name` - The process name
+fn is_wsl_relay(name: &str) -> bool {
+    name.eq_ignore_ascii_case("wslrelay.exe")
+}
+
+/// Finds the processes listening on `port` inside the default WSL
+/// distribution
+///
+/// The listeners are looked up as root with `ss`, so the processes of every
+/// user of the distribution are visible.
+///
+/// # Arguments
+///
+/// * `port` - Target port number
+fn find_wsl_processes(port: u16) -> Vec<WindowsProcess> {
+    let filter = format!("sport = :{}", port);
+    let output = match run_command("wsl.exe", &["-u", "root", "-e", "ss", "-Hltunp", &filter]) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Failed to list the listeners inside WSL: {}", e);
+            return vec![];
+        }
+    };
+
+    parse_ss_processes(&output)
+        .into_iter()
+        .map(|(pid, name)| {
+            debug!("Found process '{}' with PID {} inside WSL", name, pid);
+            WindowsProcess::new_in_wsl(pid, format!("{} (WSL)", name))
+        })
+        .collect()
+}
+
+/// Extracts the processes from the `users:(("name",pid=1,fd=2),...)` column
+/// of `ss -p` output, each process once
+///
+/// # Arguments
+///
+/// * `output` - The output of `ss`
+pub fn parse_ss_processes(output: &str) -> Vec<(u32, String)> {
+    let mut processes: Vec<(u32, String)> = vec![];
+
+    for user in output
+        .split("((")
+        .skip(1)
+        .flat_map(|users| users.split("),("))
+    {
+        let mut fields = user.split(',');
+        let Some(name) = fields.next().map(|name| name.trim_matches('"')) else {
+            continue;
+        };
+        let Some(pid) = fields
+            .find_map(|field| field.strip_prefix("pid="))
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        if !processes.iter().any(|(known, _)| *known == pid) {
+            processes.push((pid, name.to_string()));
+        }
+    }
+
+    processes
+}
+
+/// Sends a signal to a process inside the default WSL distribution
+///
+/// # Arguments
+///
+/// * `process` - The process
+/// * `signal` - The requested signal
+fn kill_wsl_process(process: &WindowsProcess, signal: &KillportSignal) -> Result<()> {
+    info!(
+        "Killing process {}:{} inside WSL",
+        process.get_name(),
+        process.pid
+    );
+
+    let signal = signal.to_string();
+    let signal = signal.trim_start_matches("SIG");
+    run_command(
+        "wsl.exe",
+        &[
+            "-u",
+            "root",
+            "-e",
+            "kill",
+            "-s",
+            signal,
+            &process.pid.to_string(),
+        ],
+    )
+    .map(|_| ())
+}
+
+/// Returns true if the service is the IP Helper service, which serves the
+/// netsh portproxy rules
+///
+/// # Arguments
+///
+/// * `name` - The service name
+fn is_ip_helper(name: &str) -> bool {
+    name.eq_ignore_ascii_case("iphlpsvc")
+}
+
+/// Registry key holding the netsh portproxy rules
+const PORTPROXY_KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Services\PortProxy";
+
+/// A `netsh interface portproxy` rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortProxyRule {
+    /// Address families the rule forwards between, e.g. `v4tov4`
+    pub kind: String,
+    pub listen_address: String,
+    pub listen_port: u16,
+    /// Address and port the connections are forwarded to
+    pub connect: String,
+}
+
+impl PortProxyRule {
+    /// Returns the name identifying the rule to `delete_portproxy_rule`,
+    /// e.g. `v4tov4 0.0.0.0:8080`
+    pub fn name(&self) -> String {
+        format!(
+            "{} {}",
+            self.kind,
+            format_endpoint(&self.listen_address, &self.listen_port.to_string())
+        )
+    }
+}
+
+/// Finds the portproxy rule listening on `port` and returns it as the
+/// service holding the port
+///
+/// # Arguments
+///
+/// * `port` - Target port number
+fn find_portproxy_service(port: u16) -> Option<Service> {
+    let output = match run_command("reg", &["query", PORTPROXY_KEY, "/s"]) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Failed to read the portproxy rules: {}", e);
+            return None;
+        }
+    };
+
+    let mut rules = parse_portproxy_rules(&output)
+        .into_iter()
+        .filter(|rule| rule.listen_port == port);
+    let rule = rules.next()?;
+    for other in rules {
+        debug!(
+            "Portproxy rule {} also listens on port {}",
+            other.name(),
+            port
+        );
+    }
+
+    info!(
+        "Port {} is forwarded to {} by portproxy rule {}",
+        port,
+        rule.connect,
+        rule.name()
+    );
+    Some(Service::new(ServiceKind::PortProxy, rule.name()))
+}
+
+/// Parses the portproxy rules from `reg query` output of the portproxy key
+///
+/// Each rule is a value named `<listen address>/<listen port>` holding
+/// `<connect address>/<connect port>`, under the `<kind>\tcp` subkey.
+///
+/// # Arguments
+///
+/// * `output` - The output of `reg query <key> /s`
+pub fn parse_portproxy_rules(output: &str) -> Vec<PortProxyRule> {
+    let mut rules = vec![];
+    let mut kind: Option<&str> = None;
+
+    for line in output.lines() {
+        if line.starts_with("HKEY_") {
+            kind = line
+                .split('\\')
+                .skip_while(|segment| !segment.eq_ignore_ascii_case("PortProxy"))
+                .nth(1);
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(kind), [name, "REG_SZ", data]) = (kind, fields.as_slice()) else {
+            continue;
+        };
+        let (Some((listen_address, listen_port)), Some((connect_address, connect_port))) =
+            (name.rsplit_once('/'), data.rsplit_once('/'))
+        else {
+            continue;
+        };
+        let Ok(listen_port) = listen_port.parse::<u16>() else {
+            continue;
+        };
+
+        rules.push(PortProxyRule {
+            kind: kind.to_string(),
+            listen_address: listen_address.to_string(),
+            listen_port,
+            connect: format_endpoint(connect_address, connect_port),
+        });
+    }
+
+    rules
+}
+
+/// Formats an address and port, bracketing IPv6 addresses
+///
+/// # Arguments
+///
+/// * `address` - The address
+/// * `port` - The port
+fn format_endpoint(address: &str, port: &str) -> String {
+    if address.contains(':') {
+        format!("[{}]:{}", address, port)
+    } else {
+        format!("{}:{}", address, port)
+    }
+}
+
+/// Deletes a portproxy rule
+///
+/// # Arguments
+///
+/// * `name` - The rule name, as returned by `PortProxyRule::name`
+pub fn delete_portproxy_rule(name: &str) -> Result<()> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid portproxy rule '{}'", name),
+        )
+    };
+
+    let (kind, endpoint) = name.split_once(' ').ok_or_else(invalid)?;
+    let (address, port) = endpoint.rsplit_once(':').ok_or_else(invalid)?;
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+
+    run_command(
+        "netsh",
+        &[
+            "interface",
+            "portproxy",
+            "delete",
+            kind,
+            &format!("listenport={}", port),
+            &format!("listenaddress={}", address),
+        ],
+    )
+    .map(|_| ())
+}
+
+/// Explanation of ports reserved in an excluded port range
+const EXCLUDED_PORT_RANGE: &str = "an excluded port range, usually reserved by Hyper-V or WinNAT for WSL 2 and Docker Desktop; see `netsh interface ipv4 show excludedportrange`, restarting the winnat service often releases it";
+
+/// Finds the excluded port range reserving `port` for `protocol`
+///
+/// # Arguments
+///
+/// * `port` - Target port number
+/// * `protocol` - The protocol, TCP or UDP
+fn find_excluded_port_range(port: u16, protocol: Protocol) -> Option<(u16, u16)> {
+    let protocol = format!("protocol={}", protocol);
+    let output = match run_command(
+        "netsh",
+        &["interface", "ipv4", "show", "excludedportrange", &protocol],
+    ) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Failed to list the excluded port ranges: {}", e);
+            return None;
         }
-    }
+    };
+
+    parse_excluded_port_ranges(&output)
+        .into_iter()
+        .find(|(start, end)| (*start..=*end).contains(&port))
 }
 
-/// Finds the processes associated with the specified `port`.
+/// Parses the ranges listed by `netsh interface ipv4 show excludedportrange`
 ///
-/// Returns a `Vec` of native processes.
+/// # Arguments
+///
+/// * `output` - The output of netsh
+pub fn parse_excluded_port_ranges(output: &str) -> Vec<(u16, u16)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let start = fields.next()?.parse::<u16>().ok()?;
+            let end = fields.next()?.parse::<u16>().ok()?;
+            Some((start, end))
+        })
+        .collect()
+}
+
+/// Describes the Hyper-V NAT static mappings forwarding `port`, e.g.
+/// `3 (tcp to 172.20.1.2:80)`
 ///
 /// # Arguments
 ///
 /// * `port` - Target port number
-pub fn find_target_processes(port: u16) -> Result<Vec<WindowsProcess>> {
-    let lookup_table: ProcessLookupTable = ProcessLookupTable::create()?;
-    let mut pids: HashSet<u32> = HashSet::new();
+fn find_nat_static_mappings(port: u16) -> Vec<String> {
+    let script = format!(
+        "Get-NetNatStaticMapping -ErrorAction SilentlyContinue | Where-Object ExternalPort -eq {} | ForEach-Object {{ \"$($_.StaticMappingID) ($($_.Protocol) to $($_.InternalIPAddress):$($_.InternalPort))\".ToLower() }}",
+        port
+    );
+
+    match run_command(
+        "powershell",
+        &["-NoProfile", "-NonInteractive", "-Command", &script],
+    ) {
+        Ok(output) => output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(e) => {
+            debug!("Failed to list the Hyper-V NAT static mappings: {}", e);
+            vec![]
+        }
+    }
+}
 
-    let processes = unsafe {
-        // Find processes in the TCP IPv4 table
-        use_extended_table::<MIB_TCPTABLE_OWNER_MODULE>(port, &mut pids)?;
+/// Time given to a service to stop after the stop request
+const SERVICE_STOP_TIMEOUT_MS: u32 = 30000;
 
-        // Find processes in the TCP IPv6 table
-        use_extended_table::<MIB_TCP6TABLE_OWNER_MODULE>(port, &mut pids)?;
+/// Returns the names of the running Windows services by the ID of the
+/// process hosting them
+pub fn services_by_pid() -> HashMap<u32, Vec<String>> {
+    unsafe {
+        let mut services: HashMap<u32, Vec<String>> = HashMap::new();
 
-        // Find processes in the UDP IPv4 table
-        use_extended_table::<MIB_UDPTABLE_OWNER_MODULE>(port, &mut pids)?;
+        let manager = OpenSCManagerW(
+            std::ptr::null(),
+            std::ptr::null(),
+            SC_MANAGER_CONNECT | SC_MANAGER_ENUMERATE_SERVICE,
+        );
+        if manager == 0 {
+            debug!(
+                "Failed to connect to the service control manager: {:#x}",
+                GetLastError()
+            );
+            return services;
+        }
 
-        // Find processes in the UDP IPv6 table
-        use_extended_table::<MIB_UDP6TABLE_OWNER_MODULE>(port, &mut pids)?;
+        // Entries hold pointers into the buffer, so it must be suitably aligned
+        let mut buffer: Vec<u64> = vec![];
+        let mut needed: u32 = 0;
+        let mut returned: u32 = 0;
+        let mut resume: u32 = 0;
+
+        loop {
+            let result = EnumServicesStatusExW(
+                manager,
+                SC_ENUM_PROCESS_INFO,
+                SERVICE_WIN32,
+                SERVICE_ACTIVE,
+                buffer.as_mut_ptr().cast(),
+                (buffer.len() * std::mem::size_of::<u64>()) as u32,
+                &mut needed,
+                &mut returned,
+                &mut resume,
+                std::ptr::null(),
+            );
+            let more_data = result == FALSE && GetLastError() == ERROR_MORE_DATA;
+            if result == FALSE && !more_data {
+                debug!("Failed to enumerate the services: {:#x}", GetLastError());
+                break;
+            }
 
-        let mut processes: Vec<WindowsProcess> = Vec::with_capacity(pids.len());
+            let entries = slice::from_raw_parts(
+                buffer.as_ptr().cast::<ENUM_SERVICE_STATUS_PROCESSW>(),
+                returned as usize,
+            );
+            for entry in entries {
+                services
+                    .entry(entry.ServiceStatusProcess.dwProcessId)
+                    .or_default()
+                    .push(wide_to_string(entry.lpServiceName));
+            }
 
-        for pid in pids {
-            let process_name = lookup_table
-                .process_names
-                .get(&pid)
-                .cloned()
-                .unwrap_or_else(|| "Unknown".to_string());
+            if !more_data {
+                break;
+            }
+            // Continue from the resume handle with a buffer large enough for the rest
+            buffer = vec![0u64; (needed as usize).div_ceil(std::mem::size_of::<u64>())];
+        }
 
-            let mut process = WindowsProcess::new(pid, process_name);
+        CloseServiceHandle(manager);
 
-            // Resolve the process parents
-            lookup_process_parents(&lookup_table, &mut process)?;
+        services
+    }
+}
 
-            processes.push(process);
-        }
+/// Reads a NUL terminated UTF-16 string
+///
+/// # Arguments
+///
+/// * `value` - Pointer to the string
+unsafe fn wide_to_string(value: *const u16) -> String {
+    if value.is_null() {
+        return String::new();
+    }
 
-        processes
-    };
+    let mut length = 0;
+    while *value.add(length) != 0 {
+        length += 1;
+    }
 
-    Ok(processes)
+    String::from_utf16_lossy(slice::from_raw_parts(value, length))
 }
 
-impl Killable for WindowsProcess {
-    fn kill(&self, _signal: crate::signal::KillportSignal) -> Result<bool> {
-        let mut killed = false;
-        let mut next = Some(self);
-        while let Some(current) = next {
-            unsafe {
-                kill_process(current)?;
-            }
+/// Stops a Windows service through the service control manager, waiting
+/// for it to stop
+///
+/// # Arguments
+///
+/// * `name` - The service name
+pub fn stop_service(name: &str) -> Result<()> {
+    let wide_name: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
 
-            killed = true;
-            next = current.parent.as_ref().map(|value| value.as_ref());
+    unsafe {
+        let manager = OpenSCManagerW(std::ptr::null(), std::ptr::null(), SC_MANAGER_CONNECT);
+        if manager == 0 {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "Failed to connect to the service control manager: {:#x}",
+                    GetLastError()
+                ),
+            ));
         }
 
-        Ok(killed)
-    }
+        let service = OpenServiceW(
+            manager,
+            wide_name.as_ptr(),
+            SERVICE_STOP | SERVICE_QUERY_STATUS,
+        );
+        if service == 0 {
+            let error = GetLastError();
+            CloseServiceHandle(manager);
+            let kind = match error {
+                ERROR_SERVICE_DOES_NOT_EXIST => ErrorKind::NotFound,
+                _ => ErrorKind::PermissionDenied,
+            };
+            return Err(Error::new(
+                kind,
+                format!("Failed to open service {}: {:#x}", name, error),
+            ));
+        }
 
-    fn get_type(&self) -> KillableType {
-        KillableType::Process
-    }
+        let mut status: SERVICE_STATUS = std::mem::zeroed();
+        let result = if ControlService(service, SERVICE_CONTROL_STOP, &mut status) == FALSE {
+            Err(Error::other(format!(
+                "Failed to stop service {}: {:#x}",
+                name,
+                GetLastError()
+            )))
+        } else {
+            // Services report their progress while stopping
+            let mut waited = 0;
+            while status.dwCurrentState != SERVICE_STOPPED && waited < SERVICE_STOP_TIMEOUT_MS {
+                std::thread::sleep(std::time::Duration::from_millis(250));
+                waited += 250;
+                if QueryServiceStatus(service, &mut status) == FALSE {
+                    break;
+                }
+            }
 
-    fn get_name(&self) -> String {
-        self.name.to_string()
+            if status.dwCurrentState == SERVICE_STOPPED {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    ErrorKind::TimedOut,
+                    format!("Service {} did not stop in time", name),
+                ))
+            }
+        };
+
+        CloseServiceHandle(service);
+        CloseServiceHandle(manager);
+
+        result
     }
 }
 
 /// Checks if there is a running process with the provided pid
 ///
+/// The process handle is waited on without a timeout rather than searching a
+/// snapshot of every process. A process that cannot be opened for another
+/// reason than not existing is assumed to be running.
+///
 /// # Arguments
 ///
 /// * `pid` - The process ID to search for
-fn is_process_running(pid: u32) -> Result<bool> {
-    let mut snapshot = WindowsProcessesSnapshot::create()?;
-    let is_running = snapshot.any(|entry| entry.th32ProcessID == pid);
-    Ok(is_running)
+unsafe fn is_process_running(pid: u32) -> bool {
+    let handle: HANDLE = OpenProcess(PROCESS_SYNCHRONIZE, FALSE, pid);
+    if handle == 0 {
+        return GetLastError() != ERROR_INVALID_PARAMETER;
+    }
+
+    let is_running = WaitForSingleObject(handle, 0) == WAIT_TIMEOUT;
+    CloseHandle(handle);
+
+    is_running
 }
 
 /// Lookup table for finding the names and parents for
@@ -140,6 +1399,23 @@ pub struct ProcessLookupTable {
 }
 
 impl ProcessLookupTable {
+    /// Returns killport's own process together with its ancestors, such as
+    /// the shell and terminal it was started from
+    pub fn own_tree(&self) -> HashSet<u32> {
+        let mut tree: HashSet<u32> = HashSet::new();
+        let mut current = std::process::id();
+
+        // Guard against cycles from reused process IDs
+        while tree.insert(current) {
+            match self.process_parents.get(&current) {
+                Some(&parent) if parent != 0 => current = parent,
+                _ => break,
+            }
+        }
+
+        tree
+    }
+
     pub fn create() -> Result<Self> {
         let mut process_names: HashMap<u32, String> = HashMap::new();
         let mut process_parents: HashMap<u32, u32> = HashMap::new();
@@ -156,33 +1432,33 @@ impl ProcessLookupTable {
     }
 }
 
-/// Finds any parent processes of the provided process, adding
-/// the process to the list of parents
-///
-/// WARNING - This worked in the previous versions because the implementation
-/// was flawwed and didn't properly look up the tree of parents, trying to kill
-/// all of the parents causes problems since you'll end up killing explorer.exe
-/// or some other windows sys process. This has been disabled (Depth of 0) but
-/// may be enabled in a future release
-///
+/// Finds up to `depth` processes the provided process was spawned by,
+/// closest first
 ///
+/// Killing every parent would end up killing explorer.exe or some other
+/// windows sys process, so the walk always stops at shells, terminals,
+/// critical system processes, the [`PARENT_DENYLIST`] and the processes
+/// killport itself runs under. It also stops at a parent ID that was reused
+/// by a process created after the child.
 ///
 /// # Arguments
 ///
-/// * `process` - The process to collect parents for
-fn lookup_process_parents(
+/// * `lookup_table` - The snapshot of the running processes
+/// * `pid` - The process to collect parents for
+/// * `depth` - The number of parent levels to collect
+fn find_parent_processes(
     lookup_table: &ProcessLookupTable,
-    process: &mut WindowsProcess,
-) -> Result<()> {
-    const MAX_PARENT_DEPTH: u8 = 0;
-
-    let mut current_procces = process;
-    let mut depth = 0;
-
-    while let Some(&parent_pid) = lookup_table.process_parents.get(&current_procces.pid) {
-        if depth == MAX_PARENT_DEPTH {
+    pid: u32,
+    depth: usize,
+) -> Vec<WindowsProcess> {
+    let own_tree = lookup_table.own_tree();
+    let mut parents: Vec<WindowsProcess> = vec![];
+    let mut current = pid;
+
+    while parents.len() < depth {
+        let Some(&parent_pid) = lookup_table.process_parents.get(&current) else {
             break;
-        }
+        };
 
         let process_name = lookup_table
             .process_names
@@ -190,34 +1466,69 @@ fn lookup_process_parents(
             .cloned()
             .unwrap_or_else(|| "Unknown".to_string());
 
-        // Add the new parent process
-        let parent = current_procces
-            .parent
-            .insert(Box::new(WindowsProcess::new(parent_pid, process_name)));
+        if own_tree.contains(&parent_pid)
+            || is_protected_process(&process_name)
+            || PARENT_DENYLIST
+                .iter()
+                .any(|denied| denied.eq_ignore_ascii_case(&process_name))
+        {
+            debug!(
+                "Not killing parent process {}:{} of PID {}",
+                process_name, parent_pid, current
+            );
+            break;
+        }
+
+        let created_in_order = unsafe {
+            match (
+                process_creation_time(current),
+                process_creation_time(parent_pid),
+            ) {
+                (Some(child), Some(parent)) => child >= parent,
+                _ => false,
+            }
+        };
+        if !created_in_order {
+            break;
+        }
 
-        current_procces = parent;
-        depth += 1
+        debug!(
+            "Found parent process {}:{} of PID {}",
+            process_name, parent_pid, current
+        );
+        parents.push(WindowsProcess::new(parent_pid, process_name));
+        current = parent_pid;
     }
 
-    Ok(())
+    parents
 }
 
+/// Processes hosting the desktop and system services, which are never
+/// killed as someone's parent
+const PARENT_DENYLIST: [&str; 4] = [
+    "explorer.exe",
+    "services.exe",
+    "winlogon.exe",
+    "svchost.exe",
+];
+
 /// Parses the name from a process entry, falls back to "Unknown"
-/// for invalid names
+/// for empty names
 ///
 /// # Arguments
 ///
 /// * `entry` - The process entry
-fn get_process_entry_name(entry: &PROCESSENTRY32) -> String {
-    let name_chars = entry
+fn get_process_entry_name(entry: &PROCESSENTRY32W) -> String {
+    let length = entry
         .szExeFile
         .iter()
-        .copied()
-        .take_while(|value| *value != 0)
-        .collect();
+        .position(|value| *value == 0)
+        .unwrap_or(entry.szExeFile.len());
 
-    let name = String::from_utf8(name_chars);
-    name.unwrap_or_else(|_| "Unknown".to_string())
+    match String::from_utf16_lossy(&entry.szExeFile[..length]) {
+        name if name.is_empty() => "Unknown".to_string(),
+        name => name,
+    }
 }
 
 /// Snapshot of the running windows processes that can be iterated to find
@@ -229,7 +1540,7 @@ pub struct WindowsProcessesSnapshot {
     /// Handle to the snapshot
     handle: HANDLE,
     /// The memory for reading process entries
-    entry: PROCESSENTRY32,
+    entry: PROCESSENTRY32W,
     /// State of reading
     state: SnapshotState,
 }
@@ -253,15 +1564,15 @@ impl WindowsProcessesSnapshot {
         // Ensure we got a valid handle
         if handle == INVALID_HANDLE_VALUE {
             let error: WIN32_ERROR = unsafe { GetLastError() };
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!("Failed to get handle to processes: {:#x}", error),
-            ));
+            return Err(Error::other(format!(
+                "Failed to get handle to processes: {:#x}",
+                error
+            )));
         }
 
         // Allocate the memory to use for the entries
-        let mut entry: PROCESSENTRY32 = unsafe { std::mem::zeroed() };
-        entry.dwSize = std::mem::size_of::<PROCESSENTRY32>() as u32;
+        let mut entry: PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+        entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
 
         Ok(Self {
             handle,
@@ -272,13 +1583,13 @@ impl WindowsProcessesSnapshot {
 }
 
 impl Iterator for WindowsProcessesSnapshot {
-    type Item = PROCESSENTRY32;
+    type Item = PROCESSENTRY32W;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.state {
             SnapshotState::First => {
                 // Process the first entry
-                if unsafe { Process32First(self.handle, &mut self.entry) } == FALSE {
+                if unsafe { Process32FirstW(self.handle, &mut self.entry) } == FALSE {
                     self.state = SnapshotState::End;
                     return None;
                 }
@@ -288,7 +1599,7 @@ impl Iterator for WindowsProcessesSnapshot {
             }
             SnapshotState::Next => {
                 // Process the next entry
-                if unsafe { Process32Next(self.handle, &mut self.entry) } == FALSE {
+                if unsafe { Process32NextW(self.handle, &mut self.entry) } == FALSE {
                     self.state = SnapshotState::End;
                     return None;
                 }
@@ -309,44 +1620,367 @@ impl Drop for WindowsProcessesSnapshot {
     }
 }
 
+/// Time given to a process to exit after a graceful shutdown request before
+/// it is terminated
+const GRACEFUL_TIMEOUT_MS: u32 = 5000;
+
+/// Time given to a console control event sent by killport to reach killport
+/// itself, before it stops ignoring such events
+const CTRL_EVENT_DELIVERY_MS: u64 = 100;
+
+/// Exit code given to terminated processes, nonzero so that supervisors
+/// watching exit codes do not mistake the termination for a clean exit
+static EXIT_CODE: AtomicU32 = AtomicU32::new(1);
+
+/// Sets the exit code given to the processes terminated from now on
+///
+/// # Arguments
+///
+/// * `code` - The exit code
+pub fn set_exit_code(code: u32) {
+    EXIT_CODE.store(code, Ordering::Relaxed);
+}
+
+/// Whether terminated processes are recorded in the Application Event Log
+static EVENT_LOG: AtomicBool = AtomicBool::new(false);
+
+/// Source name the events are recorded under
+const EVENT_SOURCE: &str = "killport";
+
+/// Event ID of a process killed by killport
+const KILL_EVENT_ID: u32 = 1;
+
+/// Enables recording the processes killed from now on in the Application
+/// Event Log
+///
+/// # Arguments
+///
+/// * `enabled` - Whether to record the killed processes
+pub fn set_event_log(enabled: bool) {
+    EVENT_LOG.store(enabled, Ordering::Relaxed);
+}
+
+/// Records a killed process in the Application Event Log, warning instead of
+/// failing the kill if the event cannot be written
+///
+/// # Arguments
+///
+/// * `process` - The killed process
+/// * `path` - The executable image of the process, if known
+/// * `owner` - The account owning the process, if known
+/// * `signal` - The signal the process was killed with
+unsafe fn report_kill_event(
+    process: &WindowsProcess,
+    path: Option<String>,
+    owner: Option<String>,
+    signal: &KillportSignal,
+) {
+    let message = format!(
+        "killport killed process {} with PID {} using {}\r\nImage: {}\r\nPort: {}\r\nUser: {}",
+        process.get_name(),
+        process.pid,
+        signal,
+        path.as_deref().unwrap_or("unknown"),
+        process
+            .port
+            .map_or_else(|| "unknown".to_string(), |port| port.to_string()),
+        owner.as_deref().unwrap_or("unknown"),
+    );
+
+    let source: Vec<u16> = EVENT_SOURCE
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let event_log = RegisterEventSourceW(std::ptr::null(), source.as_ptr());
+    if event_log == 0 {
+        warn!(
+            "Failed to open the Application Event Log: {:#x}",
+            GetLastError()
+        );
+        return;
+    }
+
+    let wide_message: Vec<u16> = message.encode_utf16().chain(std::iter::once(0)).collect();
+    let strings: [PCWSTR; 1] = [wide_message.as_ptr()];
+    let result = ReportEventW(
+        event_log,
+        EVENTLOG_INFORMATION_TYPE,
+        0,
+        KILL_EVENT_ID,
+        std::ptr::null_mut(),
+        strings.len() as u16,
+        0,
+        strings.as_ptr(),
+        std::ptr::null(),
+    );
+    if result == FALSE {
+        warn!(
+            "Failed to record the kill of process {}:{} in the Application Event Log: {:#x}",
+            process.get_name(),
+            process.pid,
+            GetLastError()
+        );
+    }
+    DeregisterEventSource(event_log);
+}
+
+/// Maps a signal to the console control event asking a console process to
+/// shut down, `None` for SIGKILL which terminates the process outright
+///
+/// The other signals have no equivalent on Windows and are rejected.
+///
+/// # Arguments
+///
+/// * `signal` - The requested signal
+pub fn console_ctrl_event(signal: &KillportSignal) -> Result<Option<u32>> {
+    match signal.0 {
+        Signal::SIGKILL => Ok(None),
+        Signal::SIGINT => Ok(Some(CTRL_C_EVENT)),
+        Signal::SIGTERM | Signal::SIGBREAK | Signal::SIGQUIT | Signal::SIGHUP => {
+            Ok(Some(CTRL_BREAK_EVENT))
+        }
+        _ => Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "{} has no equivalent for Windows processes, use SIGKILL, SIGTERM, SIGINT, SIGBREAK, SIGQUIT or SIGHUP",
+                signal
+            ),
+        )),
+    }
+}
+
+/// Sends a console control event to the console of a process, the same as
+/// pressing Ctrl+C or Ctrl+Break in its window
+///
+/// The event reaches every process attached to that console, such as the
+/// shell the target was started from, which is why the console killport
+/// itself runs in is never sent one: it would reach the user's own shell
+/// and whatever else runs in the terminal
+///
+/// Returns false if the process has no console to attach to, such as GUI
+/// processes and services, or shares the console of killport
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+/// * `event` - The console control event
+unsafe fn send_console_ctrl_event(pid: u32, event: u32) -> bool {
+    if console_processes().contains(&pid) {
+        debug!(
+            "Not sending a console control event to process {}, as it shares the console of killport",
+            pid
+        );
+        return false;
+    }
+
+    // Killport attaches to the console and so receives the event as well.
+    // Unlike ignoring Ctrl+C, which child processes would inherit, a handler
+    // also covers Ctrl+Break
+    SetConsoleCtrlHandler(Some(ignore_ctrl_event), TRUE);
+
+    // A process can only be attached to a single console at a time
+    FreeConsole();
+
+    let sent = AttachConsole(pid) != FALSE && {
+        let sent = GenerateConsoleCtrlEvent(event, 0) != FALSE;
+        FreeConsole();
+        sent
+    };
+
+    // Get back the console of the terminal killport runs in
+    AttachConsole(ATTACH_PARENT_PROCESS);
+
+    // The event is handled on a thread of its own, which may only start
+    // after it was sent
+    std::thread::sleep(std::time::Duration::from_millis(CTRL_EVENT_DELIVERY_MS));
+    SetConsoleCtrlHandler(Some(ignore_ctrl_event), FALSE);
+
+    sent
+}
+
+/// Handles the console control events sent by killport, which reach it too,
+/// so that it keeps running
+///
+/// # Arguments
+///
+/// * `_event` - The console control event
+unsafe extern "system" fn ignore_ctrl_event(_event: u32) -> BOOL {
+    TRUE
+}
+
+/// Returns the IDs of the processes attached to the console killport runs
+/// in, empty if it has none
+pub fn console_processes() -> Vec<u32> {
+    let mut pids: Vec<u32> = vec![0; 64];
+    loop {
+        let count = unsafe { GetConsoleProcessList(pids.as_mut_ptr(), pids.len() as u32) } as usize;
+
+        // The IDs are only stored if they all fit
+        if count <= pids.len() {
+            pids.truncate(count);
+            return pids;
+        }
+        pids.resize(count, 0);
+    }
+}
+
+/// Asks a GUI process to close by posting `WM_CLOSE` to its top-level
+/// windows, the same as clicking their close button
+///
+/// Returns false if the process has no visible top-level window
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+unsafe fn close_windows(pid: u32) -> bool {
+    let windows = top_level_windows(pid);
+    for &hwnd in &windows {
+        PostMessageW(hwnd, WM_CLOSE, 0, 0);
+    }
+
+    !windows.is_empty()
+}
+
+/// Returns true if a process shows a top-level window, which it is asked to
+/// close by signals other than SIGKILL
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+pub fn has_window(pid: u32) -> bool {
+    unsafe { !top_level_windows(pid).is_empty() }
+}
+
+/// Returns the visible top-level windows of a process
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+unsafe fn top_level_windows(pid: u32) -> Vec<HWND> {
+    struct Search {
+        pid: u32,
+        windows: Vec<HWND>,
+    }
+
+    unsafe extern "system" fn collect_window(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam as *mut Search);
+        let mut owner_pid: u32 = 0;
+        GetWindowThreadProcessId(hwnd, &mut owner_pid);
+
+        // Owned windows such as dialogs close along with their owner
+        if owner_pid == search.pid
+            && IsWindowVisible(hwnd) != FALSE
+            && GetWindow(hwnd, GW_OWNER) == 0
+        {
+            search.windows.push(hwnd);
+        }
+
+        TRUE
+    }
+
+    let mut search = Search {
+        pid,
+        windows: vec![],
+    };
+    EnumWindows(Some(collect_window), &mut search as *mut Search as LPARAM);
+
+    search.windows
+}
+
+/// Waits for a process to exit, returning true if it did within the timeout
+///
+/// # Arguments
+///
+/// * `pid` - The process ID
+/// * `timeout_ms` - How long to wait for, in milliseconds
+unsafe fn wait_for_exit(pid: u32, timeout_ms: u32) -> bool {
+    let handle: HANDLE = OpenProcess(PROCESS_SYNCHRONIZE, FALSE, pid);
+    if handle == 0 {
+        return false;
+    }
+
+    let exited = WaitForSingleObject(handle, timeout_ms) == WAIT_OBJECT_0;
+    CloseHandle(handle);
+
+    exited
+}
+
 /// Kills a process with the provided process ID
 ///
+/// Signals other than SIGKILL first ask the process to shut down gracefully,
+/// by closing the windows of GUI processes or otherwise through a console
+/// control event, SIGINT as Ctrl+C and the others as Ctrl+Break. The process
+/// is terminated if that fails or times out.
+///
 /// # Arguments
 ///
 /// * `process` - The process
-unsafe fn kill_process(process: &WindowsProcess) -> Result<()> {
+/// * `signal` - The requested signal
+unsafe fn kill_process(process: &WindowsProcess, signal: &KillportSignal) -> Result<()> {
+    if let Some(event) = console_ctrl_event(signal)? {
+        info!(
+            "Asking process {}:{} to shut down with {}",
+            process.get_name(),
+            process.pid,
+            signal
+        );
+
+        let requested = close_windows(process.pid) || send_console_ctrl_event(process.pid, event);
+        if requested && wait_for_exit(process.pid, GRACEFUL_TIMEOUT_MS) {
+            return Ok(());
+        }
+
+        info!(
+            "Process {}:{} did not shut down gracefully, terminating it",
+            process.get_name(),
+            process.pid
+        );
+    }
+
     info!("Killing process {}:{}", process.get_name(), process.pid);
 
-    // Open the process handle with intent to terminate
-    let handle: HANDLE = OpenProcess(PROCESS_TERMINATE, FALSE, process.pid);
+    // Open the process handle with intent to terminate, and to wait on
+    let handle: HANDLE = OpenProcess(PROCESS_TERMINATE | PROCESS_SYNCHRONIZE, FALSE, process.pid);
     if handle == 0 {
+        let error: WIN32_ERROR = GetLastError();
+
         // If the process just isn't running we can ignore the error
-        if !is_process_running(process.pid)? {
+        if !is_process_running(process.pid) {
             return Ok(());
         }
 
-        let error: WIN32_ERROR = GetLastError();
+        // Packaged apps may only be terminated as a whole
+        if let (ERROR_ACCESS_DENIED, Some(package)) = (error, &process.package) {
+            info!(
+                "Terminating the processes of package {} instead",
+                package.full_name
+            );
+            return terminate_package(package);
+        }
+
         return Err(Error::new(
-            ErrorKind::Other,
+            // Access denied surfaces as PermissionDenied, offering elevation
+            Error::from_raw_os_error(error as i32).kind(),
             format!(
-                "Failed to obtain handle to process {}:{}: {:#x}",
+                "Failed to obtain handle to process {}:{} owned by {}: {:#x}",
                 process.get_name(),
                 process.pid,
+                process_owner(process.pid)
+                    .as_deref()
+                    .unwrap_or("an unknown user"),
                 error
             ),
         ));
     }
 
-    // Terminate the process
-    let result: BOOL = TerminateProcess(handle, 0);
-
-    // Close the handle now that its no longer needed
-    CloseHandle(handle);
+    // Terminate the process, keeping the handle to wait on its exit
+    let handle = ProcessHandle(handle);
+    let result: BOOL = TerminateProcess(handle.0, EXIT_CODE.load(Ordering::Relaxed));
 
     if result == FALSE {
         let error: WIN32_ERROR = GetLastError();
         return Err(Error::new(
-            ErrorKind::Other,
+            Error::from_raw_os_error(error as i32).kind(),
             format!(
                 "Failed to terminate process {}:{}: {:#x}",
                 process.get_name(),
@@ -356,36 +1990,34 @@ unsafe fn kill_process(process: &WindowsProcess) -> Result<()> {
         ));
     }
 
+    *process.handle.borrow_mut() = Some(handle);
+
     Ok(())
 }
 
 /// Reads the extended table of the specified generic [`TableClass`] iterating
-/// the processes in that extended table checking if any bind the provided `port`
-/// those that do will have the process ID inserted into `pids`
+/// the sockets in that extended table checking if any bind the provided `port`
+/// those that do will be added to `entries`
 ///
 /// # Arguments
 ///
 /// * `port` - The port to check for
-/// * `pids` - The output list of process IDs
-unsafe fn use_extended_table<T>(port: u16, pids: &mut HashSet<u32>) -> Result<()>
+/// * `entries` - The output list of sockets
+unsafe fn use_extended_table<T>(port: u16, entries: &mut Vec<SocketEntry>) -> Result<()>
 where
     T: TableClass,
 {
-    // Allocation of initial memory
-    let mut layout: Layout = Layout::new::<T>();
-    let mut buffer: *mut u8 = alloc(layout);
-
-    // Current buffer size later changed by the fn call to be the estimated size
-    // for resizing the buffer
-    let mut size: u32 = layout.size() as u32;
-
-    // Result of asking for the table
-    let mut result: WIN32_ERROR;
+    // The buffer is made of u64s to keep the rows aligned, and starts large
+    // enough for an empty table
+    let mut buffer = vec![0u64; size_of::<T>().div_ceil(size_of::<u64>())];
 
     loop {
+        // Size of the buffer, changed by the call to the size the table needs
+        let mut size = (buffer.len() * size_of::<u64>()) as u32;
+
         // Ask windows for the extended table
-        result = (T::TABLE_FN)(
-            buffer.cast(),
+        let result = (T::TABLE_FN)(
+            buffer.as_mut_ptr().cast(),
             &mut size,
             FALSE,
             T::FAMILY,
@@ -398,36 +2030,28 @@ where
             break;
         }
 
-        // Always deallocate the memory regardless of the error
-        // (Resizing needs to reallocate the memory anyway)
-        dealloc(buffer, layout);
-
-        // Handle buffer too small
+        // Handle buffer too small, growing it by at least a row as the
+        // table may grow between calls
         if result == ERROR_INSUFFICIENT_BUFFER {
-            // Create the new memory layout from the new size and previous alignment
-            layout = Layout::from_size_align_unchecked(size as usize, layout.align());
-            // Allocate the new chunk of memory
-            buffer = alloc(layout);
+            let needed = (size as usize).div_ceil(size_of::<u64>());
+            buffer.resize(needed.max(buffer.len() + 1), 0);
             continue;
         }
 
         // Handle unknown failures
-        return Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "Failed to get size estimate for extended table: {:#x}",
-                result
-            ),
-        ));
+        return Err(Error::other(format!(
+            "Failed to get size estimate for extended table: {:#x}",
+            result
+        )));
     }
 
-    let table: *const T = buffer.cast();
+    let table = slice::from_raw_parts(
+        buffer.as_ptr().cast::<u8>(),
+        buffer.len() * size_of::<u64>(),
+    );
 
-    // Obtain the processes from the table
-    T::get_processes(table, port, pids);
-
-    // Deallocate the buffer memory
-    dealloc(buffer, layout);
+    // Obtain the sockets from the table
+    T::get_entries(table, port, entries);
 
     Ok(())
 }
@@ -454,8 +2078,8 @@ const TCP_TYPE: TableClassType = TCP_TABLE_OWNER_MODULE_ALL;
 const UDP_TYPE: TableClassType = UDP_TABLE_OWNER_MODULE;
 
 /// Trait implemented by extended tables that can
-/// be enumerated for processes that match a
-/// specific PID
+/// be enumerated for the sockets bound to a
+/// specific port
 trait TableClass {
     /// Windows function for loading this table class
     const TABLE_FN: GetExtendedTable;
@@ -466,37 +2090,161 @@ trait TableClass {
     /// Windows table class type
     const TABLE_CLASS: TableClassType;
 
-    /// Iterates the contents of the extended table inserting any
-    /// process entires that match the provided `port` into the
-    /// `pids` set
+    /// Iterates the contents of the extended table adding any
+    /// socket entries that match the provided `port` to
+    /// `entries`
     ///
     /// # Arguments
     ///
-    /// * `table` - The pointer to the table class
+    /// * `table` - The bytes of the table, rows the table does not hold in
+    ///   full are ignored
     /// * `port` - The port to search for
-    /// * `pids` - The process IDs to insert into
-    unsafe fn get_processes(table: *const Self, port: u16, pids: &mut HashSet<u32>);
+    /// * `entries` - The sockets to add to
+    unsafe fn get_entries(table: &[u8], port: u16, entries: &mut Vec<SocketEntry>);
+}
+
+/// Type of the GetOwnerModuleFrom[Tcp/Udp][6]Entry Windows API function
+type GetOwnerModule<T> = unsafe extern "system" fn(
+    *const T,
+    TCPIP_OWNER_MODULE_INFO_CLASS,
+    *mut c_void,
+    *mut u32,
+) -> u32;
+
+/// Trait implemented by the rows of the extended tables
+trait TableRow: Sized {
+    /// Windows function for resolving the module owning a row
+    const OWNER_MODULE_FN: GetOwnerModule<Self>;
+
+    /// Converts the row to a socket entry
+    fn to_entry(&self) -> SocketEntry;
+
+    /// Returns the name of the module owning the socket of the row, which
+    /// is the service name for sockets of a shared service host
+    unsafe fn owner_module(&self) -> Option<String> {
+        // The names are stored after the struct, so the buffer is sized by
+        // the first call
+        let mut size: u32 = 0;
+        let result = (Self::OWNER_MODULE_FN)(
+            self,
+            TCPIP_OWNER_MODULE_INFO_BASIC,
+            std::ptr::null_mut(),
+            &mut size,
+        );
+        if result != ERROR_INSUFFICIENT_BUFFER {
+            return None;
+        }
+
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        let result = (Self::OWNER_MODULE_FN)(
+            self,
+            TCPIP_OWNER_MODULE_INFO_BASIC,
+            buffer.as_mut_ptr().cast(),
+            &mut size,
+        );
+        if result != NO_ERROR {
+            return None;
+        }
+
+        let info = &*buffer.as_ptr().cast::<TCPIP_OWNER_MODULE_BASIC_INFO>();
+        let name = wide_to_string(info.pModuleName);
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+impl TableRow for MIB_TCPROW_OWNER_MODULE {
+    const OWNER_MODULE_FN: GetOwnerModule<Self> = GetOwnerModuleFromTcpEntry;
+
+    fn to_entry(&self) -> SocketEntry {
+        let state = tcp_state(self.dwState);
+        SocketEntry {
+            pid: self.dwOwningPid,
+            protocol: Protocol::Tcp,
+            local: socket_address(
+                Ipv4Addr::from(self.dwLocalAddr.to_ne_bytes()),
+                self.dwLocalPort,
+            ),
+            remote: (state != SocketState::Listen).then(|| {
+                socket_address(
+                    Ipv4Addr::from(self.dwRemoteAddr.to_ne_bytes()),
+                    self.dwRemotePort,
+                )
+            }),
+            state,
+            module: None,
+        }
+    }
+}
+
+impl TableRow for MIB_TCP6ROW_OWNER_MODULE {
+    const OWNER_MODULE_FN: GetOwnerModule<Self> = GetOwnerModuleFromTcp6Entry;
+
+    fn to_entry(&self) -> SocketEntry {
+        let state = tcp_state(self.dwState);
+        SocketEntry {
+            pid: self.dwOwningPid,
+            protocol: Protocol::Tcp,
+            local: socket_address(Ipv6Addr::from(self.ucLocalAddr), self.dwLocalPort),
+            remote: (state != SocketState::Listen)
+                .then(|| socket_address(Ipv6Addr::from(self.ucRemoteAddr), self.dwRemotePort)),
+            state,
+            module: None,
+        }
+    }
+}
+
+impl TableRow for MIB_UDPROW_OWNER_MODULE {
+    const OWNER_MODULE_FN: GetOwnerModule<Self> = GetOwnerModuleFromUdpEntry;
+
+    fn to_entry(&self) -> SocketEntry {
+        SocketEntry {
+            pid: self.dwOwningPid,
+            protocol: Protocol::Udp,
+            local: socket_address(
+                Ipv4Addr::from(self.dwLocalAddr.to_ne_bytes()),
+                self.dwLocalPort,
+            ),
+            remote: None,
+            state: SocketState::Close,
+            module: None,
+        }
+    }
+}
+
+impl TableRow for MIB_UDP6ROW_OWNER_MODULE {
+    const OWNER_MODULE_FN: GetOwnerModule<Self> = GetOwnerModuleFromUdp6Entry;
+
+    fn to_entry(&self) -> SocketEntry {
+        SocketEntry {
+            pid: self.dwOwningPid,
+            protocol: Protocol::Udp,
+            local: socket_address(Ipv6Addr::from(self.ucLocalAddr), self.dwLocalPort),
+            remote: None,
+            state: SocketState::Close,
+            module: None,
+        }
+    }
 }
 
-/// Implementation for get_processes is identical for all of the
+/// Implementation for get_entries is identical for all of the
 /// implementations only difference is the type of row pointer
 /// other than that all the fields accessed are the same to in
 /// order to prevent repeating this its a macro now
-macro_rules! impl_get_processes {
+macro_rules! impl_get_entries {
     ($ty:ty) => {
-        unsafe fn get_processes(table: *const Self, port: u16, pids: &mut HashSet<u32>) {
-            let row_ptr: *const $ty = addr_of!((*table).table).cast();
-            let length: usize = addr_of!((*table).dwNumEntries).read_unaligned() as usize;
-
-            slice::from_raw_parts(row_ptr, length)
-                .iter()
-                .for_each(|element| {
-                    // Convert the port value
-                    let local_port: u16 = (element.dwLocalPort as u16).to_be();
-                    if local_port == port {
-                        pids.insert(element.dwOwningPid);
-                    }
-                });
+        unsafe fn get_entries(table: &[u8], port: u16, entries: &mut Vec<SocketEntry>) {
+            let rows = extended_table_rows(table, offset_of!(Self, table), size_of::<$ty>());
+
+            entries.extend(rows.filter_map(|row| {
+                // Each row is exactly as long as the row type, which holds
+                // plain integers any bytes are valid for
+                let row = row.as_ptr().cast::<$ty>().read_unaligned();
+                let entry = row.to_entry();
+                (entry.local.port() == port).then(|| SocketEntry {
+                    module: row.owner_module(),
+                    ..entry
+                })
+            }));
         }
     };
 }
@@ -507,7 +2255,7 @@ impl TableClass for MIB_TCPTABLE_OWNER_MODULE {
     const FAMILY: AddressFamily = INET;
     const TABLE_CLASS: TableClassType = TCP_TYPE;
 
-    impl_get_processes!(MIB_TCPROW_OWNER_MODULE);
+    impl_get_entries!(MIB_TCPROW_OWNER_MODULE);
 }
 
 /// TCP IPv6 table class
@@ -516,7 +2264,7 @@ impl TableClass for MIB_TCP6TABLE_OWNER_MODULE {
     const FAMILY: AddressFamily = INET6;
     const TABLE_CLASS: TableClassType = TCP_TYPE;
 
-    impl_get_processes!(MIB_TCP6ROW_OWNER_MODULE);
+    impl_get_entries!(MIB_TCP6ROW_OWNER_MODULE);
 }
 
 /// UDP IPv4 table class
@@ -525,7 +2273,7 @@ impl TableClass for MIB_UDPTABLE_OWNER_MODULE {
     const FAMILY: AddressFamily = INET;
     const TABLE_CLASS: TableClassType = UDP_TYPE;
 
-    impl_get_processes!(MIB_UDPROW_OWNER_MODULE);
+    impl_get_entries!(MIB_UDPROW_OWNER_MODULE);
 }
 
 /// UDP IPv6 table class
@@ -534,5 +2282,5 @@ impl TableClass for MIB_UDP6TABLE_OWNER_MODULE {
     const FAMILY: AddressFamily = INET6;
     const TABLE_CLASS: TableClassType = UDP_TYPE;
 
-    impl_get_processes!(MIB_UDP6ROW_OWNER_MODULE);
+    impl_get_entries!(MIB_UDP6ROW_OWNER_MODULE);
 }
@@ -0,0 +1,116 @@
+use killport::action_log::{
+    describe_audit_entry, format_timestamp, read_audit_log, ActionRecord, Outcome,
+};
+use killport::cli::Port;
+use killport::killport::KillableType;
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn timestamps_are_rfc3339_utc() {
+    assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(
+        format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+        "2000-02-29T00:00:00Z"
+    );
+    assert_eq!(
+        format_timestamp(UNIX_EPOCH + Duration::from_secs(1_792_143_000)),
+        "2026-10-16T09:30:00Z"
+    );
+}
+
+#[test]
+fn records_are_lines_of_fields() {
+    let mut record = ActionRecord {
+        timestamp: UNIX_EPOCH + Duration::from_secs(1_792_143_000),
+        port: Port::new(8080).unwrap(),
+        pid: Some(4242),
+        target_type: KillableType::Process,
+        name: "node server.js".to_string(),
+        action: "kill",
+        signal: Some("SIGKILL".to_string()),
+        outcome: Outcome::Done,
+    };
+    assert_eq!(
+        record.to_line(),
+        r#"2026-10-16T09:30:00Z port=8080 pid=4242 type=process name="node server.js" action=kill signal=SIGKILL result=ok"#
+    );
+
+    record.pid = None;
+    record.target_type = KillableType::Container;
+    record.name = "web-1".to_string();
+    record.action = "stop";
+    record.signal = None;
+    record.outcome = Outcome::Failed("container \"web-1\" is paused".to_string());
+    assert_eq!(
+        record.to_line(),
+        r#"2026-10-16T09:30:00Z port=8080 type=container name=web-1 action=stop result=error error="container \"web-1\" is paused""#
+    );
+}
+
+#[test]
+fn journal_entries_carry_the_fields() {
+    let record = ActionRecord {
+        timestamp: UNIX_EPOCH,
+        port: Port::new(8080).unwrap(),
+        pid: Some(4242),
+        target_type: KillableType::Process,
+        name: "node server.js".to_string(),
+        action: "kill",
+        signal: Some("SIGTERM".to_string()),
+        outcome: Outcome::Failed("Operation not permitted\nrun with sudo".to_string()),
+    };
+
+    let entry = record.to_journal_entry(Some("alice"));
+    let text = String::from_utf8_lossy(&entry);
+    assert!(text.starts_with(
+        "MESSAGE\n\x73\0\0\0\0\0\0\0kill of process 'node server.js' (PID 4242) on port 8080 with SIGTERM failed: Operation not permitted\nrun with sudo\nPRIORITY=3\nSYSLOG_IDENTIFIER=killport\n"
+    ));
+    assert!(text.contains("\nKILLPORT_USER=alice\nKILLPORT_PORT=8080\nKILLPORT_PID=4242\n"));
+    assert!(text.ends_with("KILLPORT_RESULT=error\nKILLPORT_ERROR\n\x25\0\0\0\0\0\0\0Operation not permitted\nrun with sudo\n"));
+}
+
+#[test]
+fn audit_entries_can_be_queried_by_port() {
+    let record = ActionRecord {
+        timestamp: UNIX_EPOCH + Duration::from_secs(1_792_143_000),
+        port: Port::new(8080).unwrap(),
+        pid: Some(4242),
+        target_type: KillableType::Process,
+        name: "node server.js".to_string(),
+        action: "kill",
+        signal: Some("SIGKILL".to_string()),
+        outcome: Outcome::Done,
+    };
+    let args = vec!["killport".to_string(), "8080".to_string()];
+    let entry = record.to_audit_entry(Some("root"), Some("alice"), &args);
+    assert_eq!(entry["port"], 8080);
+    assert_eq!(entry["pid"], 4242);
+    assert_eq!(entry["user"], "root");
+    assert_eq!(entry["result"], "ok");
+    assert_eq!(
+        describe_audit_entry(&entry),
+        "2026-10-16T09:30:00Z root (sudo by alice): kill of process 'node server.js' (PID 4242) on port 8080 with SIGKILL succeeded (killport 8080)"
+    );
+
+    let other = ActionRecord {
+        port: Port::new(3000).unwrap(),
+        pid: None,
+        ..record
+    }
+    .to_audit_entry(None, None, &args);
+    let dir = std::env::temp_dir().join(format!("killport-audit-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.jsonl");
+    fs::write(&path, format!("{}\nnot an entry\n{}\n", entry, other)).unwrap();
+
+    assert_eq!(read_audit_log(&path, &[]).unwrap().len(), 2);
+    assert_eq!(
+        read_audit_log(&path, &[Port::new(8080).unwrap()]).unwrap(),
+        vec![entry]
+    );
+    assert!(read_audit_log(&path, &[Port::new(9090).unwrap()])
+        .unwrap()
+        .is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,69 @@
+use clap::Parser;
+use killport::cli::{find_service_port, KillPortArgs, Port};
+use killport::killport::KillOptions;
+use killport::socket::Protocol;
+use std::io::ErrorKind;
+
+const SERVICES: &str = "\
+# Network services, Internet style
+ftp\t\t21/tcp
+domain\t\t53/tcp
+domain\t\t53/udp\t\t# DNS
+http\t\t80/tcp\t\twww\t# WorldWideWeb HTTP
+syslog\t\t514/udp
+";
+
+#[test]
+fn ports_are_parsed_by_number_with_an_optional_protocol() {
+    let port: Port = "8080".parse().unwrap();
+    assert_eq!((port.number(), port.protocol()), (8080, None));
+    assert!(port.carries(Protocol::Tcp) && port.carries(Protocol::Udp));
+    assert_eq!(port.to_string(), "8080");
+
+    let port: Port = "UDP:53".parse().unwrap();
+    assert_eq!((port.number(), port.protocol()), (53, Some(Protocol::Udp)));
+    assert!(port.carries(Protocol::Udp) && !port.carries(Protocol::Tcp));
+    assert_eq!(port, Port::new(53).unwrap().with_protocol(Protocol::Udp));
+}
+
+#[test]
+fn invalid_ports_are_rejected_with_the_valid_range() {
+    for (port, message) in [
+        ("0", "Port 0 cannot be acted on"),
+        (
+            "80000",
+            "Port 80000 is out of range; ports go from 1 to 65535",
+        ),
+        ("99999999999999999999", "out of range"),
+        ("raw:80", "Unknown protocol 'raw'"),
+        ("ftp:21", "Unknown protocol 'ftp'"),
+        ("", "Unknown port ''"),
+    ] {
+        let error = port.parse::<Port>().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(error.to_string().contains(message), "{}: {}", port, error);
+    }
+    assert!(Port::new(0).is_err());
+}
+
+#[test]
+fn services_are_found_by_name_or_alias() {
+    assert_eq!(find_service_port(SERVICES, "http", None), Some(80));
+    assert_eq!(find_service_port(SERVICES, "WWW", None), Some(80));
+    assert_eq!(
+        find_service_port(SERVICES, "domain", Some(Protocol::Udp)),
+        Some(53)
+    );
+    assert_eq!(
+        find_service_port(SERVICES, "syslog", Some(Protocol::Tcp)),
+        None
+    );
+    assert_eq!(find_service_port(SERVICES, "DNS", None), None);
+    assert_eq!(find_service_port(SERVICES, "gopher", None), None);
+}
+
+#[test]
+fn default_options_match_a_bare_command_line() {
+    let args = KillPortArgs::parse_from(["killport", "8080"]);
+    assert_eq!(KillOptions::from(&args), KillOptions::default());
+}
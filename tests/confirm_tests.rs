@@ -0,0 +1,11 @@
+use killport::confirm::is_yes;
+
+#[test]
+fn only_yes_answers_confirm() {
+    for answer in ["y", "Y", "yes", "YES\n", " yes \r\n"] {
+        assert!(is_yes(answer), "{:?}", answer);
+    }
+    for answer in ["", "\n", "n", "no", "yess", "sure"] {
+        assert!(!is_yes(answer), "{:?}", answer);
+    }
+}
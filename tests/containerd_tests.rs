@@ -0,0 +1,26 @@
+use killport::cli::Port;
+use killport::containerd::parse_published_containers;
+
+#[test]
+fn published_ports_are_matched() {
+    let output = "web\t0.0.0.0:8080->80/tcp, [::]:8080->80/tcp\n\
+                  db\t127.0.0.1:5432->5432/tcp\n\
+                  range\t0.0.0.0:9000-9010->9000-9010/udp\n\
+                  internal\t\n";
+    let port = |port: &str| port.parse::<Port>().unwrap();
+
+    assert_eq!(
+        parse_published_containers(output, port("8080")),
+        vec!["web"]
+    );
+    assert_eq!(
+        parse_published_containers(output, port("9005")),
+        vec!["range"]
+    );
+    assert!(parse_published_containers(output, port("80")).is_empty());
+    assert_eq!(
+        parse_published_containers(output, port("udp:9005")),
+        vec!["range"]
+    );
+    assert!(parse_published_containers(output, port("tcp:9005")).is_empty());
+}
@@ -0,0 +1,278 @@
+use bollard::service::{Port, PortTypeEnum};
+#[cfg(unix)]
+use killport::docker::discover_socket;
+use killport::docker::{
+    context_host, current_context, docker_error, format_port_mapping, merge_containers,
+    parse_container_proxy, ContainerProxy, DockerContainer, LabelFilter, TlsFiles,
+};
+use killport::docker_cli::{inspection_from_json, summary_from_inspection};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+#[test]
+fn vm_forwarders_are_recognized() {
+    let docker_desktop = DockerContainer::find_vm_forwarder("com.docker.backend (Docker)");
+    assert_eq!(docker_desktop.map(|f| f.runtime), Some("Docker Desktop"));
+
+    let vpnkit = DockerContainer::find_vm_forwarder("vpnkit");
+    assert_eq!(vpnkit.map(|f| f.runtime), Some("Docker Desktop"));
+
+    let orbstack = DockerContainer::find_vm_forwarder("OrbStack Helper (OrbStack)").unwrap();
+    assert_eq!(orbstack.runtime, "OrbStack");
+    assert!(orbstack.sockets[0].ends_with(".orbstack/run/docker.sock"));
+}
+
+#[test]
+fn regular_processes_are_not_vm_forwarders() {
+    assert!(DockerContainer::find_vm_forwarder("docker-proxy").is_none());
+    assert!(DockerContainer::find_vm_forwarder("nginx").is_none());
+}
+
+#[test]
+fn windows_engines_get_sigkill_or_sigterm() {
+    assert_eq!(
+        DockerContainer::engine_signal("SIGKILL", "windows"),
+        "SIGKILL"
+    );
+    assert_eq!(
+        DockerContainer::engine_signal("SIGINT", "windows"),
+        "SIGTERM"
+    );
+    assert_eq!(DockerContainer::engine_signal("SIGHUP", "linux"), "SIGHUP");
+}
+
+#[test]
+fn docker_contexts_are_resolved_by_name() {
+    let config_dir = tempdir().unwrap();
+    fs::write(
+        config_dir.path().join("config.json"),
+        r#"{"auths": {}, "currentContext": "rootless"}"#,
+    )
+    .unwrap();
+    let meta = config_dir.path().join("contexts/meta/0123abcd");
+    fs::create_dir_all(&meta).unwrap();
+    fs::write(
+        meta.join("meta.json"),
+        r#"{"Name":"rootless","Metadata":{},"Endpoints":{"docker":{"Host":"unix:///run/user/1000/docker.sock","SkipTLSVerify":false}}}"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        current_context(config_dir.path()),
+        Some("rootless".to_string())
+    );
+    assert_eq!(
+        context_host(config_dir.path(), "rootless"),
+        Some("unix:///run/user/1000/docker.sock".to_string())
+    );
+    assert_eq!(context_host(config_dir.path(), "remote"), None);
+}
+
+#[test]
+fn port_mappings_are_formatted_like_docker_ps() {
+    let mapping = |ip: Option<&str>, public_port: Option<u16>| Port {
+        ip: ip.map(str::to_string),
+        private_port: 80,
+        public_port,
+        typ: Some(PortTypeEnum::TCP),
+    };
+
+    assert_eq!(
+        format_port_mapping(&mapping(Some("0.0.0.0"), Some(8080))),
+        "0.0.0.0:8080->80/tcp"
+    );
+    assert_eq!(
+        format_port_mapping(&mapping(Some("::"), Some(8080))),
+        "[::]:8080->80/tcp"
+    );
+    assert_eq!(format_port_mapping(&mapping(None, None)), "80/tcp");
+}
+
+fn container(ports: &[&str]) -> DockerContainer {
+    DockerContainer {
+        name: "web-1".to_string(),
+        id: "3f4e1c5a9b7d2e8f0a1b2c3d4e5f60718293a4b5c6d7e8f9a0b1c2d3e4f50617".to_string(),
+        image: "nginx:1.25".to_string(),
+        ports: ports.iter().map(|port| port.to_string()).collect(),
+        labels: HashMap::new(),
+        node: None,
+        socket: None,
+    }
+}
+
+#[test]
+fn containers_are_described_by_id_image_and_ports() {
+    let container = container(&["0.0.0.0:8080->80/tcp"]);
+
+    assert_eq!(
+        container.describe(),
+        "web-1 (3f4e1c5a9b7d, nginx:1.25, 0.0.0.0:8080->80/tcp)"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn runtime_sockets_are_discovered_without_a_default_socket() {
+    let home = tempdir().unwrap();
+    let default_socket = home.path().join("docker.sock");
+    assert_eq!(discover_socket(home.path(), &default_socket), None);
+
+    fs::create_dir_all(home.path().join(".rd")).unwrap();
+    fs::write(home.path().join(".rd/docker.sock"), "").unwrap();
+    assert_eq!(
+        discover_socket(home.path(), &default_socket),
+        Some(home.path().join(".rd/docker.sock"))
+    );
+
+    fs::write(&default_socket, "").unwrap();
+    assert_eq!(discover_socket(home.path(), &default_socket), None);
+}
+
+#[test]
+fn label_filters_match_by_key_or_value() {
+    let labels = HashMap::from([("com.docker.compose.project".to_string(), "shop".to_string())]);
+
+    let project: LabelFilter = "com.docker.compose.project=shop".parse().unwrap();
+    assert!(project.matches(&labels));
+    let other: LabelFilter = "com.docker.compose.project=blog".parse().unwrap();
+    assert!(!other.matches(&labels));
+    let present: LabelFilter = "com.docker.compose.project".parse().unwrap();
+    assert!(present.matches(&labels));
+    assert!(!present.matches(&HashMap::new()));
+    assert!("=shop".parse::<LabelFilter>().is_err());
+}
+
+#[test]
+fn containers_found_twice_are_merged() {
+    let merged = merge_containers(vec![
+        container(&["0.0.0.0:8080->80/tcp", "[::]:8080->80/tcp"]),
+        container(&["0.0.0.0:8080->80/tcp", "0.0.0.0:8080->80/udp"]),
+    ]);
+
+    assert_eq!(merged.len(), 1);
+    assert_eq!(
+        merged[0].ports,
+        vec![
+            "0.0.0.0:8080->80/tcp",
+            "[::]:8080->80/tcp",
+            "0.0.0.0:8080->80/udp"
+        ]
+    );
+}
+
+#[test]
+fn api_errors_say_what_to_do() {
+    let too_old = docker_error(bollard::errors::Error::DockerResponseServerError {
+        status_code: 400,
+        message: "client version 1.12 is too old. Minimum supported API version is 1.24, please upgrade your client to a newer version".to_string(),
+    });
+    assert_eq!(too_old.kind(), ErrorKind::Unsupported);
+    assert!(too_old
+        .to_string()
+        .starts_with("The Docker daemon requires API version 1.24 or later: "));
+
+    let forbidden = docker_error(bollard::errors::Error::DockerResponseServerError {
+        status_code: 403,
+        message: "authorization denied by plugin".to_string(),
+    });
+    assert_eq!(forbidden.kind(), ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn tls_files_default_to_the_cert_path() {
+    let files = TlsFiles {
+        ca: Some(PathBuf::from("/etc/docker/ca.pem")),
+        ..Default::default()
+    };
+    assert!(!files.is_empty());
+    assert!(TlsFiles::default().is_empty());
+
+    assert_eq!(
+        files.resolve(Path::new("/home/alice/.docker")),
+        (
+            PathBuf::from("/etc/docker/ca.pem"),
+            PathBuf::from("/home/alice/.docker/cert.pem"),
+            PathBuf::from("/home/alice/.docker/key.pem"),
+        )
+    );
+}
+
+#[test]
+fn cli_inspections_are_listed_like_the_api() {
+    let inspection: serde_json::Value = serde_json::from_str(
+        r#"{
+            "Id": "3f4e1c5a9b7d0e2f",
+            "Name": "/web-1",
+            "Config": {
+                "Image": "nginx:1.25",
+                "Labels": {"com.docker.compose.project": "shop"},
+                "ExposedPorts": {"80/tcp": {}, "443/tcp": {}}
+            },
+            "HostConfig": {"NetworkMode": "bridge"},
+            "Mounts": [{"Type": "volume", "Name": "data"}, {"Type": "image"}],
+            "NetworkSettings": {
+                "Ports": {
+                    "80/tcp": [
+                        {"HostIp": "0.0.0.0", "HostPort": "8080"},
+                        {"HostIp": "::", "HostPort": "8080"}
+                    ],
+                    "443/tcp": null
+                },
+                "Networks": {"shop_default": {}}
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let inspection = inspection_from_json(&inspection);
+    assert_eq!(inspection.mounts.as_ref().map(Vec::len), Some(2));
+
+    let summary = summary_from_inspection(inspection);
+    assert_eq!(summary.names, Some(vec!["/web-1".to_string()]));
+    assert_eq!(summary.image.as_deref(), Some("nginx:1.25"));
+    let ports: Vec<String> = summary
+        .ports
+        .iter()
+        .flatten()
+        .map(format_port_mapping)
+        .collect();
+    assert_eq!(
+        ports,
+        vec!["443/tcp", "0.0.0.0:8080->80/tcp", "[::]:8080->80/tcp"]
+    );
+}
+
+#[test]
+fn docker_proxies_and_shims_name_their_container() {
+    let args = |line: &str| line.split(' ').map(str::to_string).collect::<Vec<_>>();
+
+    let proxy = parse_container_proxy(&args(
+        "/usr/bin/docker-proxy -proto tcp -host-ip 0.0.0.0 -host-port 8080 -container-ip 172.17.0.2 -container-port 80",
+    ))
+    .unwrap();
+    assert_eq!(
+        proxy,
+        ContainerProxy::Forward {
+            container_ip: "172.17.0.2".to_string(),
+            container_port: 80,
+        }
+    );
+    assert_eq!(proxy.to_string(), "docker-proxy to 172.17.0.2:80");
+
+    let id = "3f4e1c5a9b7d0e2f3f4e1c5a9b7d0e2f3f4e1c5a9b7d0e2f3f4e1c5a9b7d0e2f";
+    assert_eq!(
+        parse_container_proxy(&args(&format!(
+            "/usr/bin/containerd-shim-runc-v2 -namespace moby -id={} -address /run/containerd/containerd.sock",
+            id
+        ))),
+        Some(ContainerProxy::Shim { id: id.to_string() })
+    );
+
+    assert_eq!(
+        parse_container_proxy(&args("/home/docker-user/app --port 8080")),
+        None
+    );
+}
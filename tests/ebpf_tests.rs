@@ -0,0 +1,42 @@
+#![cfg(all(target_os = "linux", feature = "ebpf"))]
+
+use killport::ebpf::{socket_files, socket_owners};
+use nix::unistd::geteuid;
+use std::collections::HashSet;
+use std::fs;
+use std::net::TcpListener;
+use std::os::fd::AsRawFd;
+
+/// Returns the inode of the socket open as `fd` in this process.
+fn socket_inode(fd: i32) -> u64 {
+    let target = fs::read_link(format!("/proc/self/fd/{}", fd)).unwrap();
+    let target = target.to_string_lossy();
+
+    target
+        .trim_start_matches("socket:[")
+        .trim_end_matches(']')
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn listener_is_found_with_its_owner() {
+    // Loading the iterator needs root
+    if !geteuid().is_root() {
+        return;
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let inode = socket_inode(listener.as_raw_fd());
+    let pid = std::process::id() as i32;
+
+    let files = socket_files().unwrap();
+    assert!(files.iter().any(|file| file.inode == inode
+        && file.pid == pid
+        && file.fd == listener.as_raw_fd() as u32));
+
+    assert_eq!(
+        socket_owners(&HashSet::from([inode])).unwrap(),
+        HashSet::from([pid])
+    );
+}
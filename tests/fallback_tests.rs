@@ -0,0 +1,105 @@
+#![cfg(unix)]
+
+use killport::cli::Port;
+use killport::elevate::find_in_path;
+use killport::fallback::{parse_lsof, parse_netstat, parse_ss, FallbackTool};
+use killport::socket::SocketHolder;
+use std::net::TcpListener;
+
+fn port(port: &str) -> Port {
+    port.parse().unwrap()
+}
+
+fn holder(pid: u32, name: &str, sockets: &[&str]) -> SocketHolder {
+    SocketHolder {
+        pid,
+        name: name.to_string(),
+        sockets: sockets.iter().map(|socket| socket.to_string()).collect(),
+    }
+}
+
+#[test]
+fn lsof_fields_are_parsed() {
+    let output = "p4242\ncnode\nf20\ntIPv4\nPTCP\nn*:8080\nf21\ntIPv6\nPTCP\nn*:8080\n\
+                  f22\ntIPv4\nPTCP\nn127.0.0.1:52345->127.0.0.1:8080\n\
+                  p4343\ncdnsmasq\nf5\ntIPv4\nPUDP\nn127.0.0.1:8080\n";
+
+    assert_eq!(
+        parse_lsof(output, port("8080")),
+        [
+            holder(4242, "node", &["tcp", "tcp6"]),
+            holder(4343, "dnsmasq", &["udp"])
+        ]
+    );
+    assert_eq!(
+        parse_lsof(output, port("udp:8080")),
+        [holder(4242, "node", &[]), holder(4343, "dnsmasq", &["udp"])]
+    );
+}
+
+#[test]
+fn ss_lines_are_parsed() {
+    let output = "\
+tcp LISTEN 0 4096 0.0.0.0:8080 0.0.0.0:* users:((\"nginx\",pid=101,fd=6),(\"nginx\",pid=100,fd=6))
+tcp LISTEN 0 4096 [::]:8080 [::]:* users:((\"nginx\",pid=100,fd=7))
+tcp ESTAB 0 0 127.0.0.1:52345 127.0.0.1:8080 users:((\"curl\",pid=300,fd=5))
+udp UNCONN 0 0 *:8080 *:*
+";
+
+    assert_eq!(
+        parse_ss(output, port("8080")),
+        [
+            holder(101, "nginx", &["tcp"]),
+            holder(100, "nginx", &["tcp", "tcp6"])
+        ]
+    );
+    assert!(parse_ss(output, port("udp:8080")).is_empty());
+}
+
+#[test]
+fn netstat_lines_are_parsed() {
+    let output = "\
+Active Internet connections (servers and established)
+Proto Recv-Q Send-Q Local Address           Foreign Address         State       PID/Program name
+tcp        0      0 0.0.0.0:8080            0.0.0.0:*               LISTEN      4242/node
+tcp6       0      0 :::8080                 :::*                    LISTEN      4242/node
+tcp        0      0 127.0.0.1:52345         127.0.0.1:8080          ESTABLISHED 300/curl
+udp        0      0 0.0.0.0:8080            0.0.0.0:*                           555/Web Content
+udp6       0      0 :::8080                 :::*                                -
+";
+
+    assert_eq!(
+        parse_netstat(output, port("8080")),
+        [
+            holder(4242, "node", &["tcp", "tcp6"]),
+            holder(555, "Web Content", &["udp"])
+        ]
+    );
+    assert_eq!(
+        parse_netstat(output, port("tcp:8080")),
+        [holder(4242, "node", &["tcp", "tcp6"])]
+    );
+}
+
+/// Tests each installed tool against a listener of the test itself. The
+/// netstat of BSDs and macOS lists no processes.
+#[test]
+fn installed_tools_find_own_listener() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = Port::new(listener.local_addr().unwrap().port()).unwrap();
+
+    for tool in FallbackTool::ALL.into_iter().filter(|&tool| {
+        find_in_path(tool.program()).is_some()
+            && (tool != FallbackTool::Netstat || cfg!(target_os = "linux"))
+    }) {
+        let holders = tool.find_holders(port).unwrap();
+        assert!(
+            holders
+                .iter()
+                .any(|holder| holder.pid == std::process::id() && holder.sockets == ["tcp"]),
+            "{} did not find the listener: {:?}",
+            tool,
+            holders
+        );
+    }
+}
@@ -1,31 +1,16 @@
 mod utils;
-use regex::bytes::Regex;
-use utils::start_listener_process;
+use utils::{
+    assert_snapshot, killport, mock_process_line, start_listener_process,
+    start_listener_process_on, MOCK_PROCESS_NAME,
+};
 
-use assert_cmd::Command;
 use tempfile::tempdir;
 
-#[cfg(unix)]
-const MOCK_PROCESS_NAME: &str = "mock_process";
-#[cfg(windows)]
-const MOCK_PROCESS_NAME: &str = "mock_process.exe";
-
-// test helper
-fn assert_match(data: &[u8], msg: &str, port: u16) {
-    let re = Regex::new(&format!(
-        r"{msg} process '(\/tmp\/\.tmp\w+\/)?{MOCK_PROCESS_NAME}' listening on port {port}\n"
-    ))
-    .unwrap();
-    assert!(re.is_match(data));
-}
-
 #[test]
 fn test_basic_kill_no_process() {
-    let mut cmd = Command::cargo_bin("killport").unwrap();
-    cmd.args(&["8080"])
-        .assert()
-        .success()
-        .stdout("No service found using port 8080\n");
+    killport(["8080"])
+        .code(0)
+        .stdout("No service found using port 8080");
 }
 
 /// Tests basic functionality of killing a process on a specified port without any additional options.
@@ -34,9 +19,9 @@ fn test_basic_kill_process() {
     let tempdir = tempdir().unwrap();
     let tempdir_path = tempdir.path();
     let mut child = start_listener_process(tempdir_path, 8180);
-    let mut cmd = Command::cargo_bin("killport").unwrap();
-    let command = cmd.args(&["8180"]).assert().success();
-    assert_match(&command.get_output().stdout, "Successfully killed", 8180);
+    killport(["8180"])
+        .code(0)
+        .stdout(&mock_process_line("Successfully killed", 8180));
     // Clean up
     let _ = child.kill();
     let _ = child.wait();
@@ -50,9 +35,9 @@ fn test_signal_handling() {
 
     for signal in ["sighup", "sigint", "sigkill"].iter() {
         let mut child = start_listener_process(tempdir_path, 8280);
-        let mut cmd = Command::cargo_bin("killport").unwrap();
-        let command = cmd.args(&["8280", "-s", signal]).assert().success();
-        assert_match(&command.get_output().stdout, "Successfully killed", 8280);
+        killport(["8280", "-s", signal])
+            .code(0)
+            .stdout(&mock_process_line("Successfully killed", 8280));
         // Clean up
         let _ = child.kill();
         let _ = child.wait();
@@ -68,34 +53,25 @@ fn test_mode_option() {
     for (i, mode) in ["auto", "process"].iter().enumerate() {
         let port = 8380 + i as u16;
         let mut child = start_listener_process(tempdir_path, port);
-        let mut cmd = Command::cargo_bin("killport").unwrap();
-        let command = cmd
-            .args(&[&port.to_string(), "--mode", mode])
-            .assert()
-            .success();
-        assert_match(&command.get_output().stdout, "Successfully killed", port);
+        killport([&port.to_string(), "--mode", mode])
+            .code(0)
+            .stdout(&mock_process_line("Successfully killed", port));
         // Clean up
         let _ = child.kill();
         let _ = child.wait();
     }
 
-    let mut cmd = Command::cargo_bin("killport").unwrap();
-    cmd.args(&["8383", "--mode", "auto"])
-        .assert()
-        .success()
-        .stdout(format!("No service found using port 8383\n"));
+    killport(["8383", "--mode", "auto"])
+        .code(0)
+        .stdout("No service found using port 8383");
 
-    let mut cmd = Command::cargo_bin("killport").unwrap();
-    cmd.args(&["8383", "--mode", "process"])
-        .assert()
-        .success()
-        .stdout(format!("No process found using port 8383\n"));
+    killport(["8383", "--mode", "process"])
+        .code(0)
+        .stdout("No process found using port 8383");
 
-    let mut cmd = Command::cargo_bin("killport").unwrap();
-    cmd.args(&["8383", "--mode", "container"])
-        .assert()
-        .success()
-        .stdout(format!("No container found using port 8383\n"));
+    killport(["8383", "--mode", "container"])
+        .code(0)
+        .stdout("No container found using port 8383");
 }
 
 /// Tests the `--dry-run` option to ensure no actual killing of the process.
@@ -105,9 +81,367 @@ fn test_dry_run_option() {
     let tempdir_path = tempdir.path();
     let mut child = start_listener_process(tempdir_path, 8480);
 
-    let mut cmd = Command::cargo_bin("killport").unwrap();
-    let command = cmd.args(&["8480", "--dry-run"]).assert().success();
-    assert_match(&command.get_output().stdout, "Would kill", 8480);
+    killport(["8480", "--dry-run"])
+        .code(0)
+        .stdout(&mock_process_line("Would kill", 8480));
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that `--wait` only returns once the port was freed.
+#[test]
+fn test_wait_option() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let mut child = start_listener_process(tempdir_path, 8780);
+    killport(["8780", "--wait", "5"])
+        .code(0)
+        .stdout(&mock_process_line("Successfully killed", 8780));
+    assert!(std::net::TcpListener::bind("127.0.0.1:8780").is_ok());
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that quieting twice turns logging off rather than crashing, and
+/// huge waits are taken as waiting indefinitely.
+#[test]
+fn test_extreme_arguments_do_not_panic() {
+    killport(["8080", "-qq"]).code(0);
+
+    let tempdir = tempdir().unwrap();
+    let mut child = start_listener_process(tempdir.path(), 8890);
+    killport(["8890", "--wait", "18446744073709551615"]).code(0);
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that a port restricted to a protocol leaves the sockets of the
+/// other protocols alone.
+#[test]
+fn test_port_restricted_to_a_protocol() {
+    let tempdir = tempdir().unwrap();
+    let mut child = start_listener_process(tempdir.path(), 8891);
+
+    killport(["udp:8891", "--dry-run"])
+        .code(0)
+        .stdout(r"(?m)^No service found using port 8891\r?$");
+    killport(["tcp:8891", "--dry-run"])
+        .code(0)
+        .stdout(&mock_process_line("Would kill", 8891));
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that `--backend` finds the process with the chosen implementation,
+/// and refuses those not available on the platform.
+#[test]
+fn test_backend_option() {
+    #[cfg(not(windows))]
+    killport(["8892", "--backend", "iphelper"])
+        .code(1)
+        .stderr("The iphelper backend is not available on this platform");
+    #[cfg(windows)]
+    killport(["8892", "--backend", "procfs"])
+        .code(1)
+        .stderr("The procfs backend is not available on this platform");
+
+    let tempdir = tempdir().unwrap();
+    let mut child = start_listener_process(tempdir.path(), 8892);
+
+    killport(["8892", "--dry-run", "--backend", "auto"])
+        .code(0)
+        .stdout(&mock_process_line("Would kill", 8892));
+    #[cfg(unix)]
+    for tool in ["lsof", "ss"] {
+        if killport::elevate::find_in_path(tool).is_some() {
+            killport(["8892", "--dry-run", "--backend", tool])
+                .code(0)
+                .stdout(&mock_process_line("Would kill", 8892));
+        }
+    }
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that `--log-file` records each kill, whatever the verbosity.
+#[test]
+fn test_log_file_option() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let log_file = tempdir_path.join("killport.log");
+    let mut child = start_listener_process(tempdir_path, 8880);
+    killport(["8880", "--quiet", "--log-file", log_file.to_str().unwrap()]).code(0);
+
+    let log = std::fs::read_to_string(&log_file).unwrap();
+    let re = regex::Regex::new(&format!(
+        r"^\d{{4}}-\d{{2}}-\d{{2}}T\d{{2}}:\d{{2}}:\d{{2}}Z port=8880 pid={} type=process name=\S*{} action=kill signal=SIGKILL result=ok\n$",
+        child.id(),
+        MOCK_PROCESS_NAME
+    ))
+    .unwrap();
+    assert!(re.is_match(&log), "unexpected log: {}", log);
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that processes given with --graceful are sent SIGTERM rather than
+/// the default SIGKILL, unless --force or --yes is passed.
+#[cfg(unix)]
+#[test]
+fn test_graceful_option() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let log_file = tempdir_path.join("killport.log");
+    for (force, signal) in [
+        (None, "SIGTERM"),
+        (Some("--force"), "SIGKILL"),
+        (Some("--yes"), "SIGKILL"),
+    ] {
+        let mut child = start_listener_process(tempdir_path, 8886);
+        let log_path = log_file.to_str().unwrap();
+        let mut args = vec![
+            "8886",
+            "--graceful",
+            MOCK_PROCESS_NAME,
+            "--log-file",
+            log_path,
+        ];
+        args.extend(force);
+        killport(args).code(0);
+        let _ = child.wait();
+
+        let log = std::fs::read_to_string(&log_file).unwrap();
+        let last = log.lines().last().unwrap_or_default();
+        assert!(last.contains(&format!("signal={} ", signal)), "{}", log);
+    }
+}
+
+/// Tests that a process listening on the port over both IPv4 and IPv6 is
+/// only reported once.
+#[test]
+fn test_dual_stack_process_is_reported_once() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let mut child = start_listener_process_on(
+        tempdir_path,
+        &["127.0.0.1:8883".to_string(), "[::1]:8883".to_string()],
+    );
+    let run = killport(["8883", "--dry-run"]);
+    run.code(0);
+    assert_eq!(run.stdout.matches("Would kill").count(), 1, "{}", run);
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that a port failing does not keep the following ports from being
+/// handled, while still failing the run.
+#[cfg(unix)]
+#[test]
+fn test_failed_port_does_not_stop_the_others() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let mut child = start_listener_process(tempdir_path, 8884);
+    // The process survives SIGCONT, so waiting for it to exit times out
+    killport(["8884", "8885", "-s", "sigcont", "--wait", "1"])
+        .code(1)
+        .stdout("No service found using port 8885")
+        .stderr("Port 8884: ")
+        .stderr("1 of 2 ports failed: 8884");
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that a port taken over again right after its process is killed,
+/// here by a loop restarting the process, fails the run.
+#[cfg(unix)]
+#[test]
+fn test_port_still_in_use_after_kill_fails() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    // Builds the mock process, which is then restarted once when killed
+    let mut child = start_listener_process(tempdir_path, 8887);
+    let _ = child.kill();
+    let _ = child.wait();
+    let mock_process = tempdir_path.join(MOCK_PROCESS_NAME);
+    let mut supervisor = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "for run in 1 2; do '{}'; done",
+            mock_process.display()
+        ))
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    killport(["8887"])
+        .code(1)
+        .stderr("Port 8887 is still in use by process");
+
+    // Clean up
+    killport(["8887"]).code(0);
+    let _ = supervisor.wait();
+}
+
+#[test]
+fn test_json_summary_option() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let mut child = start_listener_process(tempdir_path, 8881);
+    let run = killport(["8881", "8882", "--summary", "json"]);
+    run.code(0);
+
+    let summary: serde_json::Value = serde_json::from_str(&run.stdout).unwrap();
+    assert_eq!(summary["ports"][0]["port"], 8881);
+    assert_eq!(summary["ports"][0]["status"], "done");
+    assert_eq!(summary["ports"][0]["targets"][0]["action"], "kill");
+    assert_eq!(summary["ports"][1]["status"], "nothing_found");
+    assert_eq!(summary["counts"]["done"], 1);
+    assert_eq!(summary["counts"]["nothing_found"], 1);
+    assert_eq!(summary["exit_code"], 0);
+    // Clean up
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Tests that a Docker host set up with TLS files is not handed to the
+/// docker CLI, which knows nothing of them, when it cannot be reached.
+#[cfg(unix)]
+#[test]
+fn test_tls_docker_host_is_not_reached_through_the_cli() {
+    use std::os::unix::fs::PermissionsExt;
+    use utils::killport_with_env;
+
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let bin = tempdir_path.join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let docker = bin.join("docker");
+    let marker = tempdir_path.join("docker-ran");
+    std::fs::write(
+        &docker,
+        format!("#!/bin/sh\ntouch '{}'\nexit 1\n", marker.display()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&docker, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let tls_file = |name: &str| {
+        let path = tempdir_path.join(name);
+        std::fs::write(&path, "").unwrap();
+        path.to_string_lossy().into_owned()
+    };
+    let path = format!(
+        "{}:{}",
+        bin.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+
+    killport_with_env(
+        [
+            "8980",
+            "--mode",
+            "container",
+            "--docker-tls-ca",
+            &tls_file("ca.pem"),
+            "--docker-tls-cert",
+            &tls_file("cert.pem"),
+            "--docker-tls-key",
+            &tls_file("key.pem"),
+        ],
+        [
+            ("DOCKER_HOST", "tcp://127.0.0.1:1"),
+            ("PATH", path.as_str()),
+        ],
+    )
+    .code(0)
+    .stderr("Containers will not be searched");
+    assert!(!marker.exists(), "the docker CLI was run");
+}
+
+/// Tests that the audit log under sudo is that of the user behind sudo, not
+/// that of root.
+#[cfg(target_os = "linux")]
+#[test]
+fn test_audit_log_under_sudo_is_the_invoking_users() {
+    use utils::killport_with_env;
+
+    if !nix::unistd::geteuid().is_root() {
+        eprintln!("Skipping: the audit log is only moved when running as root");
+        return;
+    }
+
+    let Some(nobody) = nix::unistd::User::from_name("nobody").unwrap() else {
+        eprintln!("Skipping: there is no user named nobody");
+        return;
+    };
+    let path = nobody.dir.join(".local/share/killport/audit.jsonl");
+
+    killport_with_env(
+        ["--show-audit"],
+        [
+            ("SUDO_USER", "nobody"),
+            ("XDG_DATA_HOME", "/root/.local/share"),
+        ],
+    )
+    .code(0)
+    .stdout(&format!(
+        "No actions recorded in {}",
+        regex::escape(&path.display().to_string())
+    ));
+}
+
+/// Tests that the output of each format matches its snapshot, whatever the
+/// paths and process IDs of the run.
+#[test]
+fn test_output_snapshots() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+
+    assert_snapshot("nothing_found.txt", &killport(["8889"]).stdout);
+
+    let mut child = start_listener_process(tempdir_path, 8888);
+    assert_snapshot("dry_run.txt", &killport(["8888", "--dry-run"]).stdout);
+    assert_snapshot("kill.txt", &killport(["8888"]).stdout);
+    let _ = child.wait();
+
+    let mut child = start_listener_process(tempdir_path, 8888);
+    let run = killport(["8888", "8889", "--summary", "json"]);
+    assert_snapshot("summary.json", &run.stdout);
+    let _ = child.wait();
+}
+
+/// Tests that SIGTERM shuts a Windows console process down gracefully.
+#[cfg(windows)]
+#[test]
+fn test_graceful_sigterm_on_windows() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let mut child = start_listener_process(tempdir_path, 8580);
+
+    killport(["8580", "-s", "sigterm"])
+        .code(0)
+        .stdout(&mock_process_line("Successfully killed", 8580));
+    assert!(child.wait().is_ok());
+}
+
+/// Tests that signals without a Windows equivalent are refused rather than
+/// silently turned into a kill.
+#[cfg(windows)]
+#[test]
+fn test_unsupported_signal_on_windows() {
+    let tempdir = tempdir().unwrap();
+    let tempdir_path = tempdir.path();
+    let mut child = start_listener_process(tempdir_path, 8680);
+
+    killport(["8680", "-s", "sigusr1", "--mode", "process"]).code(1);
+    assert!(child.try_wait().unwrap().is_none());
+
     // Clean up
     let _ = child.kill();
     let _ = child.wait();
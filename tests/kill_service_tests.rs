@@ -0,0 +1,583 @@
+use clap::Parser;
+use killport::cli::{KillPortArgs, Port};
+use killport::containerd::{ContainerdClient, ContainerdContainer};
+use killport::docker::{
+    ContainerProxy, DockerClient, DockerContainer, HostNetworkContainer, LabelFilter,
+};
+use killport::killport::{
+    Diagnostics, HandledTarget, KillOptions, Killable, KillableType, Killport, KillportOperations,
+    ProcessDiscovery,
+};
+use killport::signal::KillportSignal;
+use mockall::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+mock! {
+    Target {}
+
+    impl Killable for Target {
+        fn kill(&self, signal: KillportSignal) -> Result<bool, Error>;
+        fn get_type(&self) -> KillableType;
+        fn get_name(&self) -> String;
+        fn get_pid(&self) -> Option<u32>;
+        fn get_owner(&self) -> Option<String>;
+        fn get_sockets(&self) -> Vec<String>;
+    }
+}
+
+/// Discovery finding prepared targets, then nothing once they were acted
+/// on, unless the port is to stay held.
+struct FakeDiscovery {
+    found: RefCell<Vec<Vec<Box<dyn Killable>>>>,
+}
+
+impl FakeDiscovery {
+    fn new(found: Vec<Vec<Box<dyn Killable>>>) -> Self {
+        FakeDiscovery {
+            found: RefCell::new(found),
+        }
+    }
+}
+
+impl KillportOperations for FakeDiscovery {
+    fn find_target_killables(
+        &self,
+        _port: Port,
+        _options: &KillOptions,
+        _diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Box<dyn Killable>>, Error> {
+        let mut found = self.found.borrow_mut();
+        Ok(match found.is_empty() {
+            true => vec![],
+            false => found.remove(0),
+        })
+    }
+}
+
+/// Discovery finding a database, which keeps holding the port until its
+/// shutdown is over.
+struct ShuttingDown {
+    target: RefCell<Option<MockTarget>>,
+    shutdown: Instant,
+}
+
+impl KillportOperations for ShuttingDown {
+    fn find_target_killables(
+        &self,
+        _port: Port,
+        _options: &KillOptions,
+        _diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Box<dyn Killable>>, Error> {
+        if let Some(target) = self.target.borrow_mut().take() {
+            return Ok(vec![Box::new(target)]);
+        }
+        Ok(match Instant::now() < self.shutdown {
+            true => vec![Box::new(process(42, "postgres", None))],
+            false => vec![],
+        })
+    }
+}
+
+/// Returns a process target, for which killing is still to be expected.
+fn process(pid: u32, name: &'static str, owner: Option<&'static str>) -> MockTarget {
+    listener(pid, name, owner, &[])
+}
+
+/// Returns a process target holding `sockets` on the port.
+fn listener(
+    pid: u32,
+    name: &'static str,
+    owner: Option<&'static str>,
+    sockets: &'static [&'static str],
+) -> MockTarget {
+    let mut target = MockTarget::new();
+    target
+        .expect_get_sockets()
+        .returning(move || sockets.iter().map(|socket| socket.to_string()).collect());
+    target.expect_get_type().return_const(KillableType::Process);
+    target.expect_get_name().returning(move || name.to_string());
+    target.expect_get_pid().return_const(Some(pid));
+    target
+        .expect_get_owner()
+        .returning(move || owner.map(str::to_string));
+    target
+}
+
+fn port() -> Port {
+    Port::new(8080).unwrap()
+}
+
+fn options(args: &[&str]) -> KillOptions {
+    let args = KillPortArgs::parse_from(["killport", "8080"].iter().chain(args));
+    KillOptions::from(&args)
+}
+
+#[test]
+fn dry_run_kills_nothing() {
+    let mut target = process(42, "node", None);
+    target.expect_kill().never();
+    let discovery = FakeDiscovery::new(vec![vec![Box::new(target)]]);
+
+    let results = discovery
+        .kill_service_by_port(
+            port(),
+            &options(&["--dry-run"]),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        results,
+        [HandledTarget::new(
+            KillableType::Process,
+            "node".to_string()
+        )]
+    );
+}
+
+#[test]
+fn sockets_held_are_reported_with_their_target() {
+    let mut target = listener(42, "node", None, &["tcp6", "tcp"]);
+    target.expect_kill().times(1).returning(|_| Ok(true));
+    let discovery = FakeDiscovery::new(vec![vec![Box::new(target)]]);
+
+    let results = discovery
+        .kill_service_by_port(port(), &options(&[]), &mut Diagnostics::default())
+        .unwrap();
+
+    assert_eq!(results[0].sockets, ["tcp6", "tcp"]);
+    assert_eq!(results[0].socket_kinds(), ["tcp", "tcp6"]);
+}
+
+#[test]
+fn targets_found_beforehand_are_acted_on() {
+    let mut target = process(42, "node", None);
+    target.expect_kill().times(1).returning(|_| Ok(true));
+    // Only the check that the port was freed looks the port up again
+    let discovery = FakeDiscovery::new(vec![]);
+
+    let results = discovery
+        .act_on_targets(
+            port(),
+            vec![Box::new(target)],
+            &options(&[]),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        results,
+        [HandledTarget::new(
+            KillableType::Process,
+            "node".to_string()
+        )]
+    );
+}
+
+#[test]
+fn process_found_twice_is_killed_once() {
+    let mut first = process(42, "node", None);
+    first.expect_kill().times(1).returning(|_| Ok(true));
+    let mut second = process(42, "node", None);
+    second.expect_kill().never();
+    let discovery = FakeDiscovery::new(vec![vec![Box::new(first), Box::new(second)]]);
+
+    let results = discovery
+        .kill_service_by_port(port(), &options(&[]), &mut Diagnostics::default())
+        .unwrap();
+
+    assert_eq!(
+        results,
+        [HandledTarget::new(
+            KillableType::Process,
+            "node".to_string()
+        )]
+    );
+}
+
+#[test]
+fn databases_are_sent_sigkill_only_when_forced() {
+    for (name, args, signal) in [
+        ("mysqld", vec![], "SIGTERM"),
+        ("mysqld", vec!["--force"], "SIGKILL"),
+        // PostgreSQL waits for its clients to disconnect on SIGTERM
+        ("postgres", vec![], "SIGINT"),
+        ("postgres", vec!["--force"], "SIGKILL"),
+    ] {
+        let mut target = process(42, name, None);
+        target
+            .expect_kill()
+            .with(predicate::eq(signal.parse::<KillportSignal>().unwrap()))
+            .times(1)
+            .returning(|_| Ok(true));
+        let discovery = FakeDiscovery::new(vec![vec![Box::new(target)]]);
+
+        discovery
+            .kill_service_by_port(port(), &options(&args), &mut Diagnostics::default())
+            .unwrap();
+    }
+}
+
+#[test]
+fn processes_of_other_users_are_left_alone() {
+    let mut target = process(42, "node", Some("bob"));
+    target.expect_kill().never();
+    let discovery = FakeDiscovery::new(vec![vec![Box::new(target)]]);
+    let mut diagnostics = Diagnostics::default();
+
+    let results = discovery
+        .kill_service_by_port(port(), &options(&["--user", "alice"]), &mut diagnostics)
+        .unwrap();
+
+    assert!(results.is_empty());
+    assert_eq!(
+        diagnostics.skipped,
+        [(KillableType::Process, "node".to_string())]
+    );
+}
+
+#[test]
+fn failed_kills_fail_the_port() {
+    let mut target = process(42, "node", None);
+    target
+        .expect_kill()
+        .returning(|_| Err(Error::new(ErrorKind::PermissionDenied, "denied")));
+    let discovery = FakeDiscovery::new(vec![vec![Box::new(target)]]);
+
+    let error = discovery
+        .kill_service_by_port(port(), &options(&[]), &mut Diagnostics::default())
+        .unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn databases_are_given_time_to_shut_down() {
+    let mut target = process(42, "postgres", None);
+    target.expect_kill().times(1).returning(|_| Ok(true));
+    // The database holds the port for a while after the signal, which is
+    // longer than anything else is given
+    let shutdown = Instant::now() + Duration::from_secs(3);
+    let discovery = ShuttingDown {
+        target: RefCell::new(Some(target)),
+        shutdown,
+    };
+
+    discovery
+        .kill_service_by_port(port(), &options(&[]), &mut Diagnostics::default())
+        .unwrap();
+
+    assert!(Instant::now() >= shutdown);
+}
+
+#[test]
+fn port_held_again_after_the_kill_fails_the_port() {
+    let mut target = process(42, "node", None);
+    target.expect_kill().times(1).returning(|_| Ok(true));
+    let mut restarted = process(43, "node", None);
+    restarted.expect_kill().never();
+    let discovery = FakeDiscovery::new(vec![vec![Box::new(target)], vec![Box::new(restarted)]]);
+
+    let error = discovery
+        .kill_service_by_port(
+            port(),
+            &options(&["--wait", "1"]),
+            &mut Diagnostics::default(),
+        )
+        .unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::AddrInUse);
+}
+
+/// Processes found on the port, given by name, none of which is to be
+/// killed.
+struct FakeProcesses(Vec<&'static str>);
+
+impl ProcessDiscovery for FakeProcesses {
+    fn find_processes(
+        &self,
+        _port: Port,
+        _options: &KillOptions,
+        _diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Box<dyn Killable>>, Error> {
+        Ok(self
+            .0
+            .iter()
+            .zip(42..)
+            .map(|(&name, pid)| Box::new(process(pid, name, None)) as Box<dyn Killable>)
+            .collect())
+    }
+}
+
+/// A Docker daemon publishing the port from the containers given by name,
+/// or none at all when absent.
+struct FakeDocker {
+    present: bool,
+    containers: Vec<&'static str>,
+    /// Set once the daemon was asked whether it is present.
+    probed: Rc<Cell<bool>>,
+}
+
+impl FakeDocker {
+    fn new(present: bool, containers: Vec<&'static str>) -> Self {
+        FakeDocker {
+            present,
+            containers,
+            probed: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+impl DockerClient for FakeDocker {
+    fn is_present(
+        &self,
+        _timeout: Duration,
+        _diagnostics: &mut Diagnostics,
+    ) -> Result<bool, Error> {
+        self.probed.set(true);
+        Ok(self.present)
+    }
+
+    fn find_target_containers(
+        &self,
+        _port: Port,
+        _socket: Option<&PathBuf>,
+    ) -> Result<Vec<DockerContainer>, Error> {
+        Ok(self
+            .containers
+            .iter()
+            .map(|name| DockerContainer {
+                name: name.to_string(),
+                id: format!("{}-id", name),
+                image: String::new(),
+                ports: vec![],
+                labels: HashMap::new(),
+                node: None,
+                socket: None,
+            })
+            .collect())
+    }
+
+    fn find_containers_by_internal_port(&self, _port: Port) -> Result<Vec<DockerContainer>, Error> {
+        Ok(vec![])
+    }
+
+    fn find_host_network_containers(
+        &self,
+        _port: Port,
+    ) -> Result<Vec<HostNetworkContainer>, Error> {
+        Ok(vec![])
+    }
+
+    fn find_proxied_container(
+        &self,
+        _proxy: &ContainerProxy,
+        _port: Port,
+    ) -> Result<Option<DockerContainer>, Error> {
+        Ok(None)
+    }
+}
+
+/// containerd publishing the port from the containers given by name.
+struct FakeContainerd {
+    containers: Vec<&'static str>,
+    /// Set once the containers were searched.
+    searched: Rc<Cell<bool>>,
+}
+
+impl FakeContainerd {
+    fn new(containers: Vec<&'static str>) -> Self {
+        FakeContainerd {
+            containers,
+            searched: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+impl ContainerdClient for FakeContainerd {
+    fn find_target_containers(
+        &self,
+        _port: Port,
+        _label_filters: &[LabelFilter],
+    ) -> Vec<ContainerdContainer> {
+        self.searched.set(true);
+        self.containers
+            .iter()
+            .map(|name| ContainerdContainer {
+                name: name.to_string(),
+                namespace: "default".to_string(),
+                cli: "nerdctl",
+            })
+            .collect()
+    }
+}
+
+/// Returns the names of the targets found, without the details containers
+/// are described with.
+fn names(found: &[Box<dyn Killable>]) -> Vec<String> {
+    found
+        .iter()
+        .map(|target| {
+            let name = target.get_name();
+            name.split(" (").next().unwrap_or_default().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn process_mode_never_asks_docker() {
+    let docker = FakeDocker::new(true, vec!["web"]);
+    let probed = docker.probed.clone();
+    let containerd = FakeContainerd::new(vec!["api"]);
+    let searched = containerd.searched.clone();
+    let killport = Killport::with(FakeProcesses(vec!["node"]), docker, containerd);
+
+    let found = killport
+        .find_target_killables(
+            port(),
+            &options(&["--mode", "process"]),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+
+    assert_eq!(names(&found), ["node"]);
+    assert!(!probed.get());
+    assert!(!searched.get());
+}
+
+#[test]
+fn container_mode_leaves_processes_alone() {
+    let killport = Killport::with(
+        FakeProcesses(vec!["node"]),
+        FakeDocker::new(true, vec!["web"]),
+        FakeContainerd::new(vec![]),
+    );
+
+    let found = killport
+        .find_target_killables(
+            port(),
+            &options(&["--mode", "container"]),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+
+    assert!(names(&found).contains(&"web".to_string()));
+    assert!(!names(&found).contains(&"node".to_string()));
+}
+
+#[test]
+fn auto_mode_asks_docker_only_without_a_process_on_the_port() {
+    let docker = FakeDocker::new(true, vec!["web"]);
+    let probed = docker.probed.clone();
+    let containerd = FakeContainerd::new(vec!["api"]);
+    let searched = containerd.searched.clone();
+    let found = Killport::with(FakeProcesses(vec!["node"]), docker, containerd)
+        .find_target_killables(port(), &options(&[]), &mut Diagnostics::default())
+        .unwrap();
+    assert_eq!(names(&found), ["node"]);
+    assert!(!probed.get());
+    assert!(!searched.get());
+
+    let docker = FakeDocker::new(true, vec!["web"]);
+    let probed = docker.probed.clone();
+    let found = Killport::with(
+        FakeProcesses(vec![]),
+        docker,
+        FakeContainerd::new(vec!["api"]),
+    )
+    .find_target_killables(port(), &options(&[]), &mut Diagnostics::default())
+    .unwrap();
+    assert!(names(&found).contains(&"web".to_string()));
+    assert!(names(&found).contains(&"api".to_string()));
+    assert!(probed.get());
+}
+
+#[test]
+fn no_docker_searches_no_containers() {
+    let docker = FakeDocker::new(true, vec!["web"]);
+    let probed = docker.probed.clone();
+    let containerd = FakeContainerd::new(vec!["api"]);
+    let searched = containerd.searched.clone();
+    let mut diagnostics = Diagnostics::default();
+
+    let found = Killport::with(FakeProcesses(vec![]), docker, containerd)
+        .find_target_killables(
+            port(),
+            &options(&["--mode", "container", "--no-docker"]),
+            &mut diagnostics,
+        )
+        .unwrap();
+
+    assert!(found.is_empty());
+    assert!(!probed.get());
+    assert!(!searched.get());
+    assert_eq!(
+        diagnostics.unsearched,
+        ["Docker and containerd containers, as --no-docker was given"]
+    );
+}
+
+#[test]
+fn docker_vm_forwarder_is_never_killed_for_its_containers() {
+    let forwarded = Killport::with(
+        FakeProcesses(vec!["com.docker.backend"]),
+        FakeDocker::new(true, vec!["web"]),
+        FakeContainerd::new(vec![]),
+    );
+    let found = forwarded
+        .find_target_killables(
+            port(),
+            &options(&["--dry-run"]),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+    assert!(names(&found).contains(&"web".to_string()));
+    assert!(!names(&found).contains(&"com.docker.backend".to_string()));
+
+    // Without a container to act on instead, the forwarder is left alone
+    let unpublished = Killport::with(
+        FakeProcesses(vec!["com.docker.backend"]),
+        FakeDocker::new(false, vec![]),
+        FakeContainerd::new(vec![]),
+    );
+    let mut diagnostics = Diagnostics::default();
+    let found = unpublished
+        .find_target_killables(port(), &options(&["--dry-run"]), &mut diagnostics)
+        .unwrap();
+    assert!(!names(&found).contains(&"com.docker.backend".to_string()));
+    assert_eq!(
+        diagnostics.skipped,
+        [(KillableType::Process, "com.docker.backend".to_string())]
+    );
+}
+
+#[test]
+fn dry_run_through_discovery_kills_nothing() {
+    // The fake processes expect no kill, so one would fail the test
+    let killport = Killport::with(
+        FakeProcesses(vec!["node", "worker"]),
+        FakeDocker::new(false, vec![]),
+        FakeContainerd::new(vec![]),
+    );
+
+    let results = killport
+        .kill_service_by_port(
+            port(),
+            &options(&["--dry-run", "--mode", "process"]),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        results,
+        [
+            HandledTarget::new(KillableType::Process, "node".to_string()),
+            HandledTarget::new(KillableType::Process, "worker".to_string()),
+        ]
+    );
+}
@@ -1,9 +1,14 @@
 #![cfg(unix)]
 
-use killport::cli::Mode;
-use killport::killport::{Killable, KillableType};
+use killport::cli::{Mode, Port};
+use killport::killport::{
+    descendants, is_owned_by, prefers_graceful, sort_targets, Diagnostics, HandledTarget,
+    KillOptions, Killable, KillableType,
+};
 use killport::signal::KillportSignal;
-use killport::unix::UnixProcess;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use killport::unix::find_parent_processes;
+use killport::unix::{describe_ssh_forward, UnixProcess};
 use mockall::*;
 use nix::sys::signal::Signal;
 use nix::unistd::Pid;
@@ -30,8 +35,8 @@ mock! {
 }
 mock! {
     KillportOperations {
-        fn find_target_killables(&self, port: u16, mode: Mode) -> Result<Vec<Box<dyn Killable>>, Error>;
-        fn kill_service_by_port(&self, port: u16, signal: KillportSignal, mode: Mode, dry_run: bool) -> Result<Vec<(KillableType, String)>, Error>;
+        fn find_target_killables(&self, port: Port, options: &KillOptions, diagnostics: &mut Diagnostics) -> Result<Vec<Box<dyn Killable>>, Error>;
+        fn kill_service_by_port(&self, port: Port, options: &KillOptions, diagnostics: &mut Diagnostics) -> Result<Vec<HandledTarget>, Error>;
     }
 }
 
@@ -45,10 +50,7 @@ fn native_process_kill_succeeds() {
         .times(1) // Ensure the kill method is called exactly once
         .returning(|_| Ok(true)); // Simulate successful kill
 
-    assert_eq!(
-        mock_process.kill(KillportSignal(Signal::SIGKILL)).unwrap(),
-        true
-    );
+    assert!(mock_process.kill(KillportSignal(Signal::SIGKILL)).unwrap());
 }
 
 #[test]
@@ -60,12 +62,9 @@ fn docker_container_kill_succeeds() {
         .times(1)
         .returning(|_| Ok(true));
 
-    assert_eq!(
-        mock_container
-            .kill(KillportSignal(Signal::SIGKILL))
-            .unwrap(),
-        true
-    );
+    assert!(mock_container
+        .kill(KillportSignal(Signal::SIGKILL))
+        .unwrap());
 }
 
 #[test]
@@ -74,8 +73,8 @@ fn find_killables_processes_only() {
 
     mock_killport
         .expect_find_target_killables()
-        .withf(|&port, &mode| port == 8080 && mode == Mode::Process)
-        .returning(|_, _| {
+        .withf(|&port, options, _| port.number() == 8080 && options.mode == Mode::Process)
+        .returning(|_, _, _| {
             let mut mock_process = MockUnixProcess::new();
             mock_process
                 .expect_get_type()
@@ -86,9 +85,14 @@ fn find_killables_processes_only() {
             Ok(vec![Box::new(mock_process)])
         });
 
-    let port = 8080;
-    let mode = Mode::Process;
-    let found_killables = mock_killport.find_target_killables(port, mode).unwrap();
+    let port = Port::new(8080).unwrap();
+    let options = KillOptions {
+        mode: Mode::Process,
+        ..Default::default()
+    };
+    let found_killables = mock_killport
+        .find_target_killables(port, &options, &mut Diagnostics::default())
+        .unwrap();
     assert!(found_killables
         .iter()
         .all(|k| k.get_type() == KillableType::Process));
@@ -109,19 +113,26 @@ fn kill_service_by_port_dry_run() {
 
     mock_killport
         .expect_kill_service_by_port()
-        .returning(|_, _, _, _| Ok(vec![(KillableType::Process, "mock_process".to_string())]));
+        .returning(|_, _, _| {
+            Ok(vec![HandledTarget::new(
+                KillableType::Process,
+                "mock_process".to_string(),
+            )])
+        });
 
-    let port = 8080;
-    let mode = Mode::Process;
-    let dry_run = true;
-    let signal = KillportSignal(Signal::SIGKILL);
+    let port = Port::new(8080).unwrap();
+    let options = KillOptions {
+        mode: Mode::Process,
+        dry_run: true,
+        ..Default::default()
+    };
 
     let results = mock_killport
-        .kill_service_by_port(port, signal, mode, dry_run)
+        .kill_service_by_port(port, &options, &mut Diagnostics::default())
         .unwrap();
     assert_eq!(results.len(), 1);
-    assert_eq!(results[0].0, KillableType::Process);
-    assert_eq!(results[0].1, "mock_process");
+    assert_eq!(results[0].target_type, KillableType::Process);
+    assert_eq!(results[0].name, "mock_process");
 }
 
 #[test]
@@ -147,3 +158,198 @@ fn check_docker_container_type_and_name() {
     assert_eq!(mock_container.get_type(), KillableType::Container);
     assert_eq!(mock_container.get_name(), "docker_container");
 }
+
+#[test]
+fn ssh_local_forward_is_described_as_tunnel() {
+    let args: Vec<String> = ["ssh", "-fNL", "8080:db.internal:5432", "bastion"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+    assert_eq!(
+        describe_ssh_forward(&args, 8080),
+        Some("ssh tunnel to db.internal:5432".to_string())
+    );
+    assert_eq!(describe_ssh_forward(&args, 5432), None);
+}
+
+#[test]
+fn sshd_session_is_described_as_remote_forward_but_not_the_daemon() {
+    let session = vec!["sshd: alice@pts/0".to_string()];
+    assert_eq!(
+        describe_ssh_forward(&session, 8080),
+        Some("ssh remote forward of session 'alice@pts/0'".to_string())
+    );
+
+    let listener = vec!["sshd: /usr/sbin/sshd -D [listener] 0 of 10-100 startups".to_string()];
+    assert_eq!(describe_ssh_forward(&listener, 22), None);
+}
+
+#[test]
+fn ssh_forward_with_bind_address_and_dynamic_forward() {
+    let args: Vec<String> = [
+        "/usr/bin/ssh",
+        "-p",
+        "2222",
+        "-L[::1]:9000:localhost:80",
+        "-D",
+        "1080",
+        "host",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+
+    assert_eq!(
+        describe_ssh_forward(&args, 9000),
+        Some("ssh tunnel to localhost:80".to_string())
+    );
+    assert_eq!(
+        describe_ssh_forward(&args, 1080),
+        Some("ssh SOCKS proxy (dynamic forward)".to_string())
+    );
+    assert_eq!(describe_ssh_forward(&args, 2222), None);
+}
+
+#[test]
+fn non_ssh_processes_are_not_tunnels() {
+    let args = vec![
+        "node".to_string(),
+        "-L".to_string(),
+        "8080:x:80".to_string(),
+    ];
+
+    assert_eq!(describe_ssh_forward(&args, 8080), None);
+}
+
+#[test]
+fn descendants_are_listed_parents_first() {
+    let parents = vec![(10, 1), (11, 10), (12, 10), (13, 11), (20, 1), (14, 13)];
+
+    assert_eq!(descendants(10, &parents), vec![11, 12, 13, 14]);
+    assert!(descendants(20, &parents).is_empty());
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn parent_walk_stops_at_own_process_tree() {
+    // The shell is the parent of the sleep, and is itself spawned by the test
+    let mut shell = std::process::Command::new("sh")
+        .args(["-c", "sleep 5 & echo $!; wait"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn the shell");
+    let mut line = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(shell.stdout.take().unwrap()),
+        &mut line,
+    )
+    .unwrap();
+    let sleep = Pid::from_raw(line.trim().parse().unwrap());
+
+    let parents = find_parent_processes(sleep, 5);
+
+    let _ = nix::sys::signal::kill(sleep, Signal::SIGKILL);
+    let _ = shell.wait();
+    assert_eq!(parents.len(), 1);
+    assert_eq!(parents[0].get_name(), "sh");
+}
+
+#[test]
+fn unix_owners_match_exactly() {
+    assert!(is_owned_by("alice", "alice"));
+    assert!(!is_owned_by("alice", "Alice"));
+    assert!(!is_owned_by("alice", "bob"));
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn process_owner_is_the_running_user() {
+    let user = nix::unistd::User::from_uid(nix::unistd::getuid())
+        .unwrap()
+        .unwrap();
+    let process = UnixProcess::new(nix::unistd::getpid(), "killport_unix_tests".to_string());
+
+    assert_eq!(process.get_owner(), Some(user.name));
+}
+
+#[test]
+fn explanations_name_what_stood_in_the_way() {
+    let mut diagnostics = Diagnostics {
+        searched: vec!["processes with a socket bound to port 8080".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        diagnostics.explain(Port::new(8080).unwrap()),
+        vec![
+            "Searched for targets on port 8080:",
+            "  - processes with a socket bound to port 8080",
+            "Likely reasons:",
+            "  - No socket is bound to port 8080, so it is free unless another network namespace or host is meant",
+        ]
+    );
+
+    diagnostics.uninspected = vec![41, 42];
+    diagnostics
+        .unsearched
+        .push("Docker containers: the daemon did not answer within 1000 ms".to_string());
+    let lines = diagnostics.explain(Port::new(8080).unwrap());
+    assert_eq!(lines.len(), 5);
+    assert!(lines[3].starts_with("  - 2 processes could not be inspected"));
+    assert!(lines[3].contains("(PIDs 41, 42)"));
+    assert_eq!(
+        lines[4],
+        "  - Not searched: Docker containers: the daemon did not answer within 1000 ms"
+    );
+}
+
+#[test]
+fn escalation_hints_depend_on_running_as_root() {
+    let hint = killport::elevate::escalation_hint();
+    assert_eq!(
+        hint.starts_with("Run killport with sudo"),
+        !killport::elevate::is_elevated(),
+        "{}",
+        hint
+    );
+}
+
+#[test]
+fn databases_prefer_a_graceful_shutdown() {
+    let graceful: Vec<String> = killport::killport::GRACEFUL_PROCESSES
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+
+    assert!(prefers_graceful(
+        "/usr/lib/postgresql/16/bin/postgres -D /var/lib/postgresql/16/main",
+        &graceful
+    ));
+    assert!(prefers_graceful("postgres: checkpointer", &graceful));
+    assert!(prefers_graceful("redis-server *:6379", &graceful));
+    assert!(prefers_graceful("mongod.exe", &graceful));
+    assert!(!prefers_graceful("node server.js --db postgres", &graceful));
+    assert!(!prefers_graceful("postgres-exporter", &graceful));
+}
+
+#[test]
+fn targets_are_sorted_by_pid_then_name() {
+    let container = |name: &'static str| {
+        let mut container = MockDockerContainer::new();
+        container
+            .expect_get_name()
+            .returning(move || name.to_string());
+        Box::new(container) as Box<dyn Killable>
+    };
+    let mut targets: Vec<Box<dyn Killable>> = vec![
+        container("web"),
+        Box::new(UnixProcess::new(Pid::from_raw(300), "node".to_string())),
+        container("db"),
+        Box::new(UnixProcess::new(Pid::from_raw(20), "nginx".to_string())),
+    ];
+
+    sort_targets(&mut targets);
+
+    let names: Vec<String> = targets.iter().map(|target| target.get_name()).collect();
+    assert_eq!(names, ["nginx", "node", "db", "web"]);
+}
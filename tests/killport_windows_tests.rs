@@ -1,12 +1,43 @@
 #![cfg(windows)]
 
-use killport::cli::Mode;
-use killport::killport::{Killable, KillableType};
-use killport::signal::KillportSignal;
-use killport::windows::WindowsProcess;
+use killport::cli::{Mode, Port};
+use killport::elevate::quote_argument;
+use killport::killport::{
+    is_owned_by, Diagnostics, HandledTarget, KillOptions, Killable, KillableType,
+};
+use killport::signal::{KillportSignal, Signal};
+use killport::socket::SocketState;
+use killport::windows::{
+    console_ctrl_event, console_processes, has_window, parse_excluded_port_ranges,
+    parse_portproxy_rules, parse_ss_processes, services_by_pid, set_exit_code, stop_service,
+    tcp_state, PortProxyRule, ProcessLookupTable, WindowsProcess,
+};
 use mockall::*;
 
-use std::io::Error;
+use std::io::{Error, ErrorKind};
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exit code given to the processes terminated by the tests, telling them
+/// apart from those that shut down on their own
+const TERMINATED: u32 = 57;
+
+/// Starts ping for a minute, in a console of its own when `own_console` is
+/// set, else in the console of the tests if they have one
+fn start_ping(own_console: bool) -> std::process::Child {
+    // CREATE_NEW_CONSOLE
+    let flags = if own_console { 0x10 } else { 0 };
+    let child = Command::new("ping")
+        .args(["-n", "60", "127.0.0.1"])
+        .creation_flags(flags)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+    child
+}
 
 // Setup Mocks
 mock! {
@@ -30,8 +61,8 @@ mock! {
 }
 mock! {
     KillportOperations {
-        fn find_target_killables(&self, port: u16, mode: Mode) -> Result<Vec<Box<dyn Killable>>, Error>;
-        fn kill_service_by_port(&self, port: u16, signal: KillportSignal, mode: Mode, dry_run: bool) -> Result<Vec<(KillableType, String)>, Error>;
+        fn find_target_killables(&self, port: Port, options: &KillOptions, diagnostics: &mut Diagnostics) -> Result<Vec<Box<dyn Killable>>, Error>;
+        fn kill_service_by_port(&self, port: Port, options: &KillOptions, diagnostics: &mut Diagnostics) -> Result<Vec<HandledTarget>, Error>;
     }
 }
 
@@ -41,15 +72,11 @@ fn native_process_kill_succeeds() {
     // Setup the expectation for the mock
     mock_process
         .expect_kill()
-        .with(mockall::predicate::eq(KillportSignal(
-            "SIGKILL".to_string(),
-        )))
+        .with(mockall::predicate::eq(KillportSignal(Signal::SIGKILL)))
         .times(1) // Ensure the kill method is called exactly once
         .returning(|_| Ok(true)); // Simulate successful kill
 
-    assert!(mock_process
-        .kill(KillportSignal("SIGKILL".to_string()))
-        .unwrap());
+    assert!(mock_process.kill(KillportSignal(Signal::SIGKILL)).unwrap());
 }
 
 #[test]
@@ -57,14 +84,12 @@ fn docker_container_kill_succeeds() {
     let mut mock_container = MockDockerContainer::new();
     mock_container
         .expect_kill()
-        .with(mockall::predicate::eq(KillportSignal(
-            "SIGKILL".to_string(),
-        )))
+        .with(mockall::predicate::eq(KillportSignal(Signal::SIGKILL)))
         .times(1)
         .returning(|_| Ok(true));
 
     assert!(mock_container
-        .kill(KillportSignal("SIGKILL".to_string()))
+        .kill(KillportSignal(Signal::SIGKILL))
         .unwrap());
 }
 
@@ -74,8 +99,8 @@ fn find_killables_processes_only() {
 
     mock_killport
         .expect_find_target_killables()
-        .withf(|&port, &mode| port == 8080 && mode == Mode::Process)
-        .returning(|_, _| {
+        .withf(|&port, options, _| port.number() == 8080 && options.mode == Mode::Process)
+        .returning(|_, _, _| {
             let mut mock_process = MockWindowsProcess::new();
             mock_process
                 .expect_get_type()
@@ -86,9 +111,14 @@ fn find_killables_processes_only() {
             Ok(vec![Box::new(mock_process)])
         });
 
-    let port = 8080;
-    let mode = Mode::Process;
-    let found_killables = mock_killport.find_target_killables(port, mode).unwrap();
+    let port = Port::new(8080).unwrap();
+    let options = KillOptions {
+        mode: Mode::Process,
+        ..Default::default()
+    };
+    let found_killables = mock_killport
+        .find_target_killables(port, &options, &mut Diagnostics::default())
+        .unwrap();
     assert!(found_killables
         .iter()
         .all(|k| k.get_type() == KillableType::Process));
@@ -109,19 +139,26 @@ fn kill_service_by_port_dry_run() {
 
     mock_killport
         .expect_kill_service_by_port()
-        .returning(|_, _, _, _| Ok(vec![(KillableType::Process, "mock_process".to_string())]));
+        .returning(|_, _, _| {
+            Ok(vec![HandledTarget::new(
+                KillableType::Process,
+                "mock_process".to_string(),
+            )])
+        });
 
-    let port = 8080;
-    let mode = Mode::Process;
-    let dry_run = true;
-    let signal = KillportSignal("SIGKILL".to_string());
+    let port = Port::new(8080).unwrap();
+    let options = KillOptions {
+        mode: Mode::Process,
+        dry_run: true,
+        ..Default::default()
+    };
 
     let results = mock_killport
-        .kill_service_by_port(port, signal, mode, dry_run)
+        .kill_service_by_port(port, &options, &mut Diagnostics::default())
         .unwrap();
     assert_eq!(results.len(), 1);
-    assert_eq!(results[0].0, KillableType::Process);
-    assert_eq!(results[0].1, "mock_process");
+    assert_eq!(results[0].target_type, KillableType::Process);
+    assert_eq!(results[0].name, "mock_process");
 }
 
 #[test]
@@ -147,3 +184,239 @@ fn check_docker_container_type_and_name() {
     assert_eq!(mock_container.get_type(), KillableType::Container);
     assert_eq!(mock_container.get_name(), "docker_container");
 }
+
+#[test]
+fn parse_ss_processes_lists_each_process_once() {
+    let output = concat!(
+        "tcp LISTEN 0 511 0.0.0.0:3000 0.0.0.0:* users:((\"node\",pid=812,fd=21),(\"node\",pid=812,fd=22))\n",
+        "tcp LISTEN 0 511 [::]:3000 [::]:* users:((\"node\",pid=812,fd=23),(\"worker\",pid=830,fd=3))\n",
+    );
+
+    assert_eq!(
+        parse_ss_processes(output),
+        vec![(812, "node".to_string()), (830, "worker".to_string())]
+    );
+}
+
+#[test]
+fn parse_portproxy_rules_reads_every_kind() {
+    let output = concat!(
+        "\r\n",
+        "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\PortProxy\\v4tov4\r\n",
+        "\r\n",
+        "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\PortProxy\\v4tov4\\tcp\r\n",
+        "    0.0.0.0/8080    REG_SZ    172.20.1.2/80\r\n",
+        "\r\n",
+        "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\PortProxy\\v6tov4\\tcp\r\n",
+        "    ::/3000    REG_SZ    127.0.0.1/3000\r\n",
+    );
+
+    let rules = parse_portproxy_rules(output);
+
+    assert_eq!(
+        rules,
+        vec![
+            PortProxyRule {
+                kind: "v4tov4".to_string(),
+                listen_address: "0.0.0.0".to_string(),
+                listen_port: 8080,
+                connect: "172.20.1.2:80".to_string(),
+            },
+            PortProxyRule {
+                kind: "v6tov4".to_string(),
+                listen_address: "::".to_string(),
+                listen_port: 3000,
+                connect: "127.0.0.1:3000".to_string(),
+            },
+        ]
+    );
+    assert_eq!(rules[1].name(), "v6tov4 [::]:3000");
+}
+
+#[test]
+fn tcp_state_maps_table_states() {
+    assert_eq!(tcp_state(2), SocketState::Listen);
+    assert_eq!(tcp_state(5), SocketState::Established);
+    assert_eq!(tcp_state(11), SocketState::TimeWait);
+    assert_eq!(tcp_state(12), SocketState::Close);
+}
+
+#[test]
+fn quote_argument_round_trips_through_the_c_runtime() {
+    assert_eq!(quote_argument("8080"), "8080");
+    assert_eq!(quote_argument(""), "\"\"");
+    assert_eq!(quote_argument("a b"), "\"a b\"");
+    assert_eq!(quote_argument("say \"hi\""), "\"say \\\"hi\\\"\"");
+    assert_eq!(
+        quote_argument("C:\\dir with space\\"),
+        "\"C:\\dir with space\\\\\""
+    );
+}
+
+#[test]
+fn parse_excluded_port_ranges_skips_headers() {
+    let output = concat!(
+        "\r\n",
+        "Protocol tcp Port Exclusion Ranges\r\n",
+        "\r\n",
+        "Start Port    End Port\r\n",
+        "----------    --------\r\n",
+        "      5357        5357\r\n",
+        "     50000       50059     *\r\n",
+        "\r\n",
+        "* - Administered port exclusions.\r\n",
+    );
+
+    assert_eq!(
+        parse_excluded_port_ranges(output),
+        vec![(5357, 5357), (50000, 50059)]
+    );
+}
+
+#[test]
+fn windows_accounts_match_with_or_without_domain() {
+    assert!(is_owned_by(r"DESKTOP-1\alice", "alice"));
+    assert!(is_owned_by(r"DESKTOP-1\Alice", r"desktop-1\alice"));
+    assert!(is_owned_by(r"NT AUTHORITY\SYSTEM", "system"));
+    assert!(!is_owned_by(r"DESKTOP-1\alice", "bob"));
+}
+
+#[test]
+fn signals_map_to_console_control_events() {
+    // CTRL_C_EVENT and CTRL_BREAK_EVENT
+    assert_eq!(
+        console_ctrl_event(&KillportSignal(Signal::SIGINT)).unwrap(),
+        Some(0)
+    );
+    for signal in [
+        Signal::SIGTERM,
+        Signal::SIGBREAK,
+        Signal::SIGQUIT,
+        Signal::SIGHUP,
+    ] {
+        assert_eq!(
+            console_ctrl_event(&KillportSignal(signal)).unwrap(),
+            Some(1)
+        );
+    }
+    assert_eq!(
+        console_ctrl_event(&KillportSignal(Signal::SIGKILL)).unwrap(),
+        None
+    );
+    assert_eq!(
+        console_ctrl_event(&KillportSignal(Signal::SIGUSR1))
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Unsupported
+    );
+}
+
+#[test]
+fn sigint_shuts_down_a_process_in_its_own_console() {
+    set_exit_code(TERMINATED);
+    let mut child = start_ping(true);
+
+    // The tests keep running, as killport ignores the event it sends
+    let process = WindowsProcess::new(child.id(), "PING.EXE".to_string());
+    assert!(process.kill(KillportSignal(Signal::SIGINT)).unwrap());
+
+    assert_ne!(child.wait().unwrap().code(), Some(TERMINATED as i32));
+}
+
+#[test]
+fn process_sharing_the_console_of_killport_is_terminated() {
+    set_exit_code(TERMINATED);
+    let mut child = start_ping(false);
+    // Without a console of their own, ping was given a new one
+    if !console_processes().contains(&child.id()) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return;
+    }
+
+    // Ctrl+C would reach the terminal of the tests as well, so it is not sent
+    let process = WindowsProcess::new(child.id(), "PING.EXE".to_string());
+    assert!(process.kill(KillportSignal(Signal::SIGINT)).unwrap());
+
+    assert_eq!(child.wait().unwrap().code(), Some(TERMINATED as i32));
+}
+
+#[test]
+fn console_processes_have_no_window_to_close() {
+    let mut child = start_ping(true);
+
+    // The console window belongs to the console host rather than to ping
+    let windowless = !has_window(child.id());
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(windowless);
+}
+
+#[test]
+fn sigterm_closes_the_window_of_a_gui_process() {
+    set_exit_code(TERMINATED);
+    let mut child = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "Add-Type -AssemblyName System.Windows.Forms; [System.Windows.Forms.Application]::Run((New-Object System.Windows.Forms.Form))",
+        ])
+        .spawn()
+        .unwrap();
+
+    // Sessions without a desktop, as some services run in, show no window
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while !has_window(child.id()) {
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return;
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+
+    let process = WindowsProcess::new(child.id(), "powershell.exe".to_string());
+    assert!(process.kill(KillportSignal(Signal::SIGTERM)).unwrap());
+
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+}
+
+#[test]
+fn services_are_listed_by_their_hosting_process() {
+    let services = services_by_pid();
+
+    // The RPC service runs on every Windows system, the tests never are one
+    assert!(services
+        .values()
+        .flatten()
+        .any(|name| name.eq_ignore_ascii_case("RpcSs")));
+    assert!(!services.contains_key(&std::process::id()));
+}
+
+#[test]
+fn stopping_an_unknown_service_fails_as_not_found() {
+    let error = stop_service("killport-test-no-such-service").unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn own_tree_holds_the_tests_and_what_started_them() {
+    let tree = ProcessLookupTable::create().unwrap().own_tree();
+
+    assert!(tree.contains(&std::process::id()));
+    // Cargo, or the shell the tests were started from
+    assert!(tree.len() > 1);
+}
+
+#[test]
+fn parents_never_include_the_terminal_killport_runs_in() {
+    // A server started from the same terminal as killport
+    let mut child = start_ping(false);
+
+    let parents = WindowsProcess::new(child.id(), "PING.EXE".to_string()).get_parents(10);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(parents.is_empty());
+}
@@ -0,0 +1,88 @@
+use killport::kubernetes::{find_service_by_port, parse_port_forward, ClusterNode, Pod};
+use std::collections::HashMap;
+
+fn args(command_line: &str) -> Vec<String> {
+    command_line
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn pod_port_forwards_are_recognized() {
+    let forward = parse_port_forward(
+        &args("/usr/local/bin/kubectl -n prod port-forward pod/web-0 8080:80"),
+        8080,
+    )
+    .unwrap();
+
+    assert_eq!(
+        forward.describe(),
+        "kubectl port-forward to pod/web-0 in namespace prod"
+    );
+    assert_eq!(
+        forward.pod(),
+        Some(Pod {
+            name: "web-0".to_string(),
+            namespace: Some("prod".to_string()),
+            context: None,
+            forwarded: true,
+        })
+    );
+}
+
+#[test]
+fn service_port_forwards_have_no_known_pod() {
+    let forward = parse_port_forward(
+        &args("kubectl port-forward --context=kind-dev svc/web 9000 :443"),
+        9000,
+    )
+    .unwrap();
+
+    assert_eq!(forward.context.as_deref(), Some("kind-dev"));
+    assert_eq!(forward.describe(), "kubectl port-forward to svc/web");
+    assert!(forward.pod().is_none());
+}
+
+#[test]
+fn other_commands_are_not_port_forwards() {
+    assert!(parse_port_forward(&args("kubectl port-forward web-0 8080:80"), 9090).is_none());
+    assert!(parse_port_forward(&args("kubectl proxy --port 8001"), 8001).is_none());
+    assert!(parse_port_forward(&args("ssh -L 8080:localhost:80 host"), 8080).is_none());
+}
+
+#[test]
+fn cluster_node_containers_are_recognized_by_label() {
+    let labels = HashMap::from([("io.x-k8s.kind.cluster".to_string(), "dev".to_string())]);
+    let node = ClusterNode::from_labels(&labels, Some(30080)).unwrap();
+
+    assert_eq!(node.to_string(), "kind cluster 'dev'");
+    assert_eq!(node.context(), "kind-dev");
+    assert!(ClusterNode::from_labels(&HashMap::new(), None).is_none());
+}
+
+#[test]
+fn node_ports_lead_to_their_service() {
+    let services = serde_json::json!({
+        "items": [
+            {
+                "metadata": {"namespace": "prod", "name": "web"},
+                "spec": {"type": "NodePort", "ports": [{"port": 80, "nodePort": 30080}]}
+            },
+            {
+                "metadata": {"namespace": "kube-system", "name": "traefik"},
+                "spec": {"type": "LoadBalancer", "ports": [{"port": 443, "nodePort": 31443}]}
+            }
+        ]
+    });
+
+    assert_eq!(
+        find_service_by_port(&services, 30080).as_deref(),
+        Some("NodePort service prod/web")
+    );
+    assert_eq!(
+        find_service_by_port(&services, 443).as_deref(),
+        Some("LoadBalancer service kube-system/traefik")
+    );
+    assert!(find_service_by_port(&services, 8080).is_none());
+}
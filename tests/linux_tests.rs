@@ -0,0 +1,169 @@
+#![cfg(target_os = "linux")]
+
+use killport::cli::Port;
+use killport::killport::{Diagnostics, Killable, KillableType};
+use killport::linux::{
+    find_container_id, find_sandbox_unit, find_systemd_unit, find_target_processes,
+    parse_sctp_table, parse_wireguard_listen_ports, skip_systemd_manager,
+};
+use killport::service::{Service, ServiceKind};
+use nix::sys::socket::{socket, AddressFamily, SockFlag, SockProtocol, SockType};
+
+#[test]
+fn systemd_system_unit() {
+    assert_eq!(
+        find_systemd_unit("/system.slice/nginx.service"),
+        Some(Service::new(
+            ServiceKind::Systemd,
+            "nginx.service".to_string()
+        ))
+    );
+}
+
+#[test]
+fn systemd_user_unit() {
+    assert_eq!(
+        find_systemd_unit(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/dev-server.service"
+        ),
+        Some(Service::new(
+            ServiceKind::SystemdUser(1000),
+            "dev-server.service".to_string()
+        ))
+    );
+}
+
+#[test]
+fn systemd_scopes_are_not_services() {
+    assert_eq!(
+        find_systemd_unit("/user.slice/user-1000.slice/session-2.scope"),
+        None
+    );
+    assert_eq!(
+        find_systemd_unit("/user.slice/user-1000.slice/user@1000.service/init.scope"),
+        None
+    );
+}
+
+#[test]
+fn snap_services_are_stopped_through_snapd() {
+    assert_eq!(
+        find_sandbox_unit("/system.slice/snap.lxd.daemon.service"),
+        Some(Service::new(ServiceKind::Snap, "lxd.daemon".to_string()))
+    );
+}
+
+#[test]
+fn snap_app_scopes_are_not_services() {
+    assert_eq!(
+        find_sandbox_unit(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/snap.firefox.firefox-2b5c1f0e-7d0b-4f3a-9e1e-1c3b6a2d4f5e.scope"
+        ),
+        None
+    );
+}
+
+#[test]
+fn flatpak_apps_are_found_by_scope() {
+    assert_eq!(
+        find_sandbox_unit(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-flatpak-org.example.Server-12345.scope"
+        ),
+        Some(Service::new(
+            ServiceKind::Flatpak,
+            "org.example.Server".to_string()
+        ))
+    );
+}
+
+#[test]
+fn skipped_systemd_manager_keeps_the_port_from_being_reported_free() {
+    let port: Port = "8080".parse().unwrap();
+    let mut diagnostics = Diagnostics::default();
+
+    skip_systemd_manager(port, 1, "/sbin/init".to_string(), &mut diagnostics);
+
+    assert_eq!(
+        diagnostics.skipped,
+        vec![(KillableType::Process, "/sbin/init".to_string())]
+    );
+    assert!(diagnostics
+        .explain(port)
+        .iter()
+        .any(|line| line.contains("process '/sbin/init' was found but left alone")));
+}
+
+#[test]
+fn wireguard_listen_ports_are_read_per_interface() {
+    assert_eq!(
+        parse_wireguard_listen_ports("wg0\t51820\nwg-office\t41641\ndown\t0\n"),
+        vec![51820, 41641]
+    );
+    assert_eq!(parse_wireguard_listen_ports(""), Vec::<u16>::new());
+}
+
+#[test]
+fn container_ids_are_found_in_cgroups() {
+    let id = "3f4e1c5a9b7d2e8f0a1b2c3d4e5f60718293a4b5c6d7e8f9a0b1c2d3e4f5a6b7";
+
+    assert_eq!(
+        find_container_id(&format!("/system.slice/docker-{}.scope", id)),
+        Some(id.to_string())
+    );
+    assert_eq!(
+        find_container_id(&format!("/docker/{}", id)),
+        Some(id.to_string())
+    );
+    assert_eq!(
+        find_container_id("/user.slice/user-1000.slice/session-2.scope"),
+        None
+    );
+}
+
+#[test]
+fn sctp_sockets_are_found_by_local_port() {
+    let endpoints = "\
+ ENDPT     SOCK   STY SST HBKT LPORT   UID INODE LADDRS
+ffff88017e8f1000 ffff88017e6d4400 2   10  29   38412     0 103405 10.0.0.1
+ffff88017e8f2000 ffff88017e6d4800 2   10  30   2905     0 103406 10.0.0.1
+";
+    let associations = "\
+ ASSOC     SOCK   STY SST ST HBKT ASSOC-ID TX_QUEUE RX_QUEUE UID INODE LPORT RPORT LADDRS <-> RADDRS
+ffff8800a5f0e000 ffff88009b6a5400 2   1   4  1009    3        0        0       0 62372 38412 38413  10.0.0.1 <-> *10.0.0.2
+ffff8800a5f0f000 ffff88009b6a5800 2   1   4  1010    4        0        0       0 0 38412 38414  10.0.0.1 <-> *10.0.0.3
+";
+
+    assert_eq!(
+        parse_sctp_table("/proc/net/sctp/eps", endpoints, 38412),
+        vec![103405]
+    );
+    assert_eq!(
+        parse_sctp_table("/proc/net/sctp/assocs", associations, 38412),
+        vec![62372]
+    );
+    assert_eq!(
+        parse_sctp_table("/proc/net/sctp/eps", endpoints, 38413),
+        Vec::<u64>::new()
+    );
+}
+
+#[test]
+fn raw_sockets_are_not_found_by_protocol_number() {
+    // Raw sockets need CAP_NET_RAW
+    let Ok(_raw) = socket(
+        AddressFamily::Inet,
+        SockType::Raw,
+        SockFlag::empty(),
+        SockProtocol::Udp,
+    ) else {
+        return;
+    };
+
+    // The raw socket table reports 17, the protocol number of UDP, in place
+    // of the local port
+    let processes =
+        find_target_processes(Port::new(17).unwrap(), &mut Diagnostics::default()).unwrap();
+    assert!(!processes
+        .iter()
+        .any(|process| process.get_pid() == Some(std::process::id())));
+}
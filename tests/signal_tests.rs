@@ -0,0 +1,64 @@
+use killport::signal::{suggest_signals, KillportSignal};
+
+#[test]
+fn signals_parse_by_name_or_number() {
+    let term: KillportSignal = "SIGTERM".parse().unwrap();
+
+    assert_eq!("term".parse::<KillportSignal>().unwrap(), term);
+    assert_eq!("sigterm".parse::<KillportSignal>().unwrap(), term);
+    assert_eq!("15".parse::<KillportSignal>().unwrap(), term);
+    assert_eq!("-TERM".parse::<KillportSignal>().unwrap(), term);
+    assert_eq!("-15".parse::<KillportSignal>().unwrap(), term);
+    assert_eq!(
+        "9".parse::<KillportSignal>().unwrap().to_string(),
+        "SIGKILL"
+    );
+}
+
+#[test]
+fn unknown_signals_are_rejected() {
+    let error = "nonsense".parse::<KillportSignal>().unwrap_err();
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(error.to_string().contains("Unknown signal nonsense"));
+    assert!("1000".parse::<KillportSignal>().is_err());
+}
+
+#[test]
+fn container_signals_are_linux_names() {
+    let hup: KillportSignal = "hup".parse().unwrap();
+
+    assert_eq!(hup.container_signal().unwrap(), "SIGHUP");
+}
+
+#[test]
+fn misspelled_signals_get_suggestions() {
+    assert_eq!(suggest_signals("SIGKIL"), vec!["SIGKILL"]);
+    assert_eq!(suggest_signals("SIGTREM"), vec!["SIGTERM"]);
+    assert_eq!(suggest_signals("SIGUSR"), vec!["SIGUSR1", "SIGUSR2"]);
+    assert!(suggest_signals("SIGNONSENSE").is_empty());
+
+    let error = "sigkil".parse::<KillportSignal>().unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Unknown signal sigkil, did you mean SIGKILL? "));
+    assert!(error.to_string().contains("SIGHUP, SIGINT"));
+}
+
+#[test]
+fn only_ending_signals_end_processes() {
+    for signal in ["sigkill", "term", "-INT", "quit"] {
+        assert!(
+            signal.parse::<KillportSignal>().unwrap().ends_process(),
+            "{}",
+            signal
+        );
+    }
+    for signal in ["sighup", "usr1", "stop", "cont"] {
+        assert!(
+            !signal.parse::<KillportSignal>().unwrap().ends_process(),
+            "{}",
+            signal
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use killport::cli::Port;
+use killport::socket::{
+    describe_kernel_sockets, describe_orphans, extended_table_rows, parse_netstat_time_wait,
+    parse_procfiles, KernelSocket, OrphanSocket, Protocol, SocketHolder, SocketState,
+};
+use std::time::Duration;
+
+#[test]
+fn describe_orphans_groups_by_protocol_and_state() {
+    let orphans = vec![
+        OrphanSocket {
+            protocol: Protocol::Tcp,
+            state: SocketState::TimeWait,
+            remaining: Some(Duration::from_millis(12_300)),
+        },
+        OrphanSocket {
+            protocol: Protocol::Tcp,
+            state: SocketState::TimeWait,
+            remaining: Some(Duration::from_secs(40)),
+        },
+        OrphanSocket {
+            protocol: Protocol::Tcp,
+            state: SocketState::FinWait2,
+            remaining: None,
+        },
+    ];
+
+    let lines = describe_orphans(Port::new(8080).unwrap(), &orphans);
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with(
+        "Port 8080 has 2 tcp sockets in TIME_WAIT with no owning process, released automatically in 40s: "
+    ));
+    assert!(
+        lines[1].starts_with("Port 8080 has 1 tcp socket in FIN_WAIT2 with no owning process: ")
+    );
+}
+
+#[test]
+fn describe_orphans_without_sockets() {
+    assert!(describe_orphans(Port::new(8080).unwrap(), &[]).is_empty());
+}
+
+#[test]
+fn describe_kernel_sockets_names_the_subsystem() {
+    let sockets = vec![
+        KernelSocket {
+            protocol: Protocol::Tcp,
+            subsystem: Some("NFS server (nfsd)"),
+        },
+        KernelSocket {
+            protocol: Protocol::Tcp,
+            subsystem: Some("NFS server (nfsd)"),
+        },
+        KernelSocket {
+            protocol: Protocol::Udp,
+            subsystem: None,
+        },
+    ];
+
+    let lines = describe_kernel_sockets(Port::new(2049).unwrap(), &sockets);
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("Port 2049 (tcp) is held by the kernel (NFS server (nfsd))"));
+    assert!(lines[1].starts_with("Port 2049 (udp) is not held by any visible process"));
+}
+
+#[test]
+fn netstat_time_wait_connections_are_orphans() {
+    let output = "\
+Active Internet connections (including servers)
+Proto Recv-Q Send-Q  Local Address          Foreign Address        (state)
+tcp4       0      0  127.0.0.1.8080         127.0.0.1.52345        TIME_WAIT
+tcp6       0      0  ::1.8080               ::1.52346              TIME_WAIT
+tcp4       0      0  127.0.0.1.52347        127.0.0.1.8080         TIME_WAIT
+tcp4       0      0  *.8080                 *.*                    LISTEN
+tcp4       0      0  127.0.0.1.18080        127.0.0.1.52348        TIME_WAIT
+";
+
+    let orphans = parse_netstat_time_wait(output, 8080);
+
+    assert_eq!(orphans.len(), 2);
+    assert!(orphans
+        .iter()
+        .all(|orphan| orphan.protocol == Protocol::Tcp && orphan.state == SocketState::TimeWait));
+    let lines = describe_orphans(Port::new(8080).unwrap(), &orphans);
+    assert!(
+        lines[0].starts_with("Port 8080 has 2 tcp sockets in TIME_WAIT with no owning process: ")
+    );
+    assert!(lines[0].contains("SO_REUSEADDR"));
+}
+
+/// Builds an extended table claiming `count` rows, holding `rows` rows of
+/// `row_size` bytes from `rows_offset`, each filled with its index.
+fn extended_table(count: u32, rows_offset: usize, row_size: usize, rows: usize) -> Vec<u8> {
+    let mut table = vec![0u8; rows_offset + rows * row_size];
+    table[..4].copy_from_slice(&count.to_ne_bytes());
+    for (index, row) in table[rows_offset..].chunks_exact_mut(row_size).enumerate() {
+        row.fill(index as u8);
+    }
+    table
+}
+
+#[test]
+fn extended_table_rows_are_split_by_row_size() {
+    let table = extended_table(3, 8, 24, 3);
+
+    let rows: Vec<&[u8]> = extended_table_rows(&table, 8, 24).collect();
+
+    assert_eq!(rows.len(), 3);
+    for (index, row) in rows.iter().enumerate() {
+        assert_eq!(*row, [index as u8; 24]);
+    }
+}
+
+#[test]
+fn extended_table_rows_stop_at_the_end_of_the_buffer() {
+    // The count claims more rows than the buffer holds, with the last one cut short
+    let mut table = extended_table(10, 8, 24, 2);
+    table.extend([0; 10]);
+
+    assert_eq!(extended_table_rows(&table, 8, 24).count(), 2);
+    assert_eq!(extended_table_rows(&table[..3], 8, 24).count(), 0);
+    assert_eq!(extended_table_rows(&[], 8, 24).count(), 0);
+    assert_eq!(extended_table_rows(&table, 8, 0).count(), 0);
+}
+
+/// Checks on buffers of random sizes, counts and row layouts that the rows
+/// read are whole, within the buffer, and never more than the table claims.
+#[test]
+fn extended_table_rows_never_read_past_the_buffer() {
+    let mut rng = fastrand::Rng::with_seed(0x6b696c6c706f7274);
+
+    for _ in 0..10_000 {
+        let row_size = rng.usize(0..64);
+        let rows_offset = rng.usize(0..16);
+        let count = match rng.u8(..) {
+            0..=31 => rng.u32(..),
+            _ => rng.u32(0..32),
+        };
+        let mut table = vec![0u8; rng.usize(0..512)];
+        rng.fill(&mut table);
+        if table.len() >= 4 {
+            table[..4].copy_from_slice(&count.to_ne_bytes());
+        }
+
+        let rows: Vec<&[u8]> = extended_table_rows(&table, rows_offset, row_size).collect();
+
+        let held = table
+            .len()
+            .saturating_sub(rows_offset)
+            .checked_div(row_size)
+            .unwrap_or(0);
+        let claimed = if table.len() >= 4 { count as usize } else { 0 };
+        assert_eq!(rows.len(), claimed.min(held));
+        assert!(rows.iter().all(|row| row.len() == row_size));
+        assert!(rows_offset + rows.len() * row_size <= table.len() || rows.is_empty());
+    }
+}
+
+#[test]
+fn procfiles_lists_the_sockets_on_the_port() {
+    let output = "\
+  3932192 : /usr/sbin/sshd
+  Current rlimit: 2000 file descriptors
+   0: S_IFCHR mode:0666 dev:10,3 ino:4232 uid:0 gid:0 rdev:2,2
+      O_RDWR name:/dev/null
+   3: S_IFSOCK mode:0666 dev:-1,-1 ino:0 uid:0 gid:0 rdev:0,0
+      O_RDWR | O_NONBLOCK
+      SOCK_STREAM
+      sockname: AF_INET 0.0.0.0  port: 22
+   4: S_IFSOCK mode:0666 dev:-1,-1 ino:0 uid:0 gid:0 rdev:0,0
+      O_RDWR | O_NONBLOCK
+      SOCK_STREAM
+      sockname: AF_INET6 ::  port: 22
+  4128770 : syslogd
+   5: S_IFSOCK mode:0666 dev:-1,-1 ino:0 uid:0 gid:0 rdev:0,0
+      O_RDWR
+      SOCK_DGRAM
+      sockname: AF_INET 0.0.0.0  port: 514
+";
+    let sshd = |sockets: &[&str]| SocketHolder {
+        pid: 3932192,
+        name: "/usr/sbin/sshd".to_string(),
+        sockets: sockets.iter().map(|socket| socket.to_string()).collect(),
+    };
+    let syslogd = |sockets: &[&str]| SocketHolder {
+        pid: 4128770,
+        name: "syslogd".to_string(),
+        sockets: sockets.iter().map(|socket| socket.to_string()).collect(),
+    };
+    let port = |port: &str| port.parse::<Port>().unwrap();
+
+    assert_eq!(
+        parse_procfiles(output, port("22")),
+        [sshd(&["tcp", "tcp6"]), syslogd(&[])]
+    );
+    assert_eq!(
+        parse_procfiles(output, port("udp:22")),
+        [sshd(&[]), syslogd(&[])]
+    );
+    assert_eq!(
+        parse_procfiles(output, port("514")),
+        [sshd(&[]), syslogd(&["udp"])]
+    );
+}
@@ -0,0 +1,58 @@
+use killport::killport::KillableType;
+use killport::summary::{PortSummary, RunSummary, TargetSummary};
+
+#[test]
+fn failed_ports_explain_the_exit_code() {
+    let mut summary = RunSummary {
+        dry_run: false,
+        ports: vec![
+            PortSummary {
+                port: 8080,
+                targets: vec![TargetSummary {
+                    target_type: KillableType::Container,
+                    name: "web-1".to_string(),
+                    action: "stop",
+                    sockets: vec!["tcp".to_string(), "tcp6".to_string()],
+                }],
+                ..Default::default()
+            },
+            PortSummary {
+                port: 3000,
+                ..Default::default()
+            },
+        ],
+    };
+    assert_eq!(summary.exit_code(), 0);
+    assert_eq!(summary.exit_reason(), "every port was handled");
+
+    summary.ports[1].error = Some("Permission denied".to_string());
+    let json = summary.to_json();
+    assert_eq!(json["exit_code"], 1);
+    assert_eq!(json["exit_reason"], "port 3000 failed: Permission denied");
+    assert_eq!(json["counts"]["targets"], 1);
+    assert_eq!(json["counts"]["failed"], 1);
+    assert_eq!(json["ports"][0]["status"], "done");
+    assert_eq!(json["ports"][0]["targets"][0]["type"], "container");
+    assert_eq!(
+        json["ports"][0]["targets"][0]["sockets"],
+        serde_json::json!(["tcp", "tcp6"])
+    );
+    assert_eq!(json["ports"][1]["status"], "error");
+}
+
+#[test]
+fn ports_with_only_skipped_targets_are_not_reported_free() {
+    let summary = RunSummary {
+        dry_run: false,
+        ports: vec![PortSummary {
+            port: 8080,
+            skipped: vec![(KillableType::Process, "/sbin/init".to_string())],
+            ..Default::default()
+        }],
+    };
+
+    let json = summary.to_json();
+    assert_eq!(json["ports"][0]["status"], "skipped");
+    assert_eq!(json["counts"]["skipped"], 1);
+    assert_eq!(json["counts"]["nothing_found"], 0);
+}
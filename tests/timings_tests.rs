@@ -0,0 +1,43 @@
+use killport::cli::Port;
+use killport::timings::{self, describe_timings, Timing};
+use std::time::Duration;
+
+#[test]
+fn phases_add_up_per_port() {
+    let (port, other_port) = (Port::new(8080).unwrap(), Port::new(8081).unwrap());
+    timings::record(port, "find processes", Duration::from_millis(1));
+    assert!(timings::timings().is_empty());
+
+    timings::enable();
+    timings::record(port, "find processes", Duration::from_millis(2));
+    timings::record(port, "probe docker", Duration::from_millis(10));
+    timings::record(port, "find processes", Duration::from_millis(3));
+    assert_eq!(timings::time(other_port, "find processes", || 42), 42);
+
+    let timings = timings::timings();
+    assert_eq!(
+        timings[..2],
+        [
+            Timing {
+                port,
+                phase: "find processes",
+                elapsed: Duration::from_millis(5),
+                runs: 2,
+            },
+            Timing {
+                port,
+                phase: "probe docker",
+                elapsed: Duration::from_millis(10),
+                runs: 1,
+            },
+        ]
+    );
+    assert_eq!((timings[2].port, timings[2].runs), (other_port, 1));
+    assert_eq!(
+        describe_timings(&timings[..2]),
+        [
+            "port 8080: find processes took 5.0ms over 2 runs",
+            "port 8080: probe docker took 10.0ms",
+        ]
+    );
+}
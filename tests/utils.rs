@@ -1,8 +1,27 @@
-use std::process::{Child, Command as SystemCommand};
-use std::{fs::File, io::Write, path::Path, thread, time::Duration};
+use regex::Regex;
+use std::ffi::OsStr;
+use std::process::{Child, Command as SystemCommand, Stdio};
+use std::{env::consts::EXE_SUFFIX, fs::File, io::Write, path::Path, thread, time::Duration};
+
+/// File name of the mock process binary.
+#[cfg(unix)]
+pub const MOCK_PROCESS_NAME: &str = "mock_process";
+#[cfg(windows)]
+pub const MOCK_PROCESS_NAME: &str = "mock_process.exe";
+
+/// Process creation flag giving the mock process a console of its own, so
+/// the console control events killport sends it do not reach the test harness.
+#[cfg(windows)]
+const CREATE_NEW_CONSOLE: u32 = 0x0000_0010;
 
 /// Generates and starts a mock Rust application that listens on a given port.
 pub fn start_listener_process(tempdir_path: &Path, port: u16) -> Child {
+    start_listener_process_on(tempdir_path, &[format!("127.0.0.1:{}", port)])
+}
+
+/// Generates and starts a mock Rust application that listens on each of the
+/// given addresses, e.g. "[::1]:8080".
+pub fn start_listener_process_on(tempdir_path: &Path, addresses: &[String]) -> Child {
     let mock_process_code = format!(
         r#"
         use std::net::TcpListener;
@@ -10,22 +29,26 @@ pub fn start_listener_process(tempdir_path: &Path, port: u16) -> Child {
         use std::thread;
 
         fn main() {{
-            let mut listener = None;
-            for _ in 0..5 {{
-                match TcpListener::bind("127.0.0.1:{}") {{
-                    Ok(l) => {{
-                        listener = Some(l);
-                        break;
-                    }},
-                    Err(_) => thread::sleep(Duration::from_millis(500)),
+            let addresses = {:?};
+            let mut listeners = vec![];
+            for address in addresses {{
+                let mut listener = None;
+                for _ in 0..5 {{
+                    match TcpListener::bind(address) {{
+                        Ok(l) => {{
+                            listener = Some(l);
+                            break;
+                        }},
+                        Err(_) => thread::sleep(Duration::from_millis(500)),
+                    }}
                 }}
+                listeners.push(listener.expect("Failed to bind to port after several attempts"));
             }}
-            let listener = listener.expect("Failed to bind to port after several attempts");
-            println!("Listening on port {}");
-            loop {{ let _ = listener.accept(); }}
+            println!("Listening on {{:?}}", addresses);
+            loop {{ let _ = listeners[0].accept(); }}
         }}
     "#,
-        port, port
+        addresses
     );
 
     let mock_process_path = tempdir_path.join("mock_process.rs");
@@ -44,12 +67,173 @@ pub fn start_listener_process(tempdir_path: &Path, port: u16) -> Child {
 
     assert!(status.success(), "Compilation of mock process failed");
 
-    let mock_binary_path = tempdir_path.join("mock_process");
-    let child = SystemCommand::new(mock_binary_path)
-        .spawn()
-        .expect("Failed to start the mock process");
+    let mock_binary_path = tempdir_path.join(format!("mock_process{}", EXE_SUFFIX));
+    let mut command = SystemCommand::new(mock_binary_path);
+
+    #[cfg(windows)]
+    std::os::windows::process::CommandExt::creation_flags(&mut command, CREATE_NEW_CONSOLE);
+
+    let child = command.spawn().expect("Failed to start the mock process");
 
     thread::sleep(Duration::from_secs(1));
 
     child
 }
+
+/// A finished run of killport, checked against patterns and exit codes
+/// rather than exact output, as paths and wording differ between platforms
+/// and package builds.
+pub struct KillportRun {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl KillportRun {
+    /// Asserts that killport exited with `code`.
+    pub fn code(&self, code: i32) -> &Self {
+        assert_eq!(self.code, Some(code), "unexpected exit code\n{}", self);
+        self
+    }
+
+    /// Asserts that stdout matches the regex `pattern`.
+    pub fn stdout(&self, pattern: &str) -> &Self {
+        let re = Regex::new(pattern).unwrap();
+        assert!(
+            re.is_match(&self.stdout),
+            "stdout does not match {:?}\n{}",
+            pattern,
+            self
+        );
+        self
+    }
+
+    /// Asserts that stderr matches the regex `pattern`.
+    pub fn stderr(&self, pattern: &str) -> &Self {
+        let re = Regex::new(pattern).unwrap();
+        assert!(
+            re.is_match(&self.stderr),
+            "stderr does not match {:?}\n{}",
+            pattern,
+            self
+        );
+        self
+    }
+}
+
+impl std::fmt::Display for KillportRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "code: {:?}\nstdout:\n{}\nstderr:\n{}",
+            self.code, self.stdout, self.stderr
+        )
+    }
+}
+
+/// Runs killport with `args` and waits for it to exit. Its stdin is not a
+/// terminal, so it never waits on a question.
+pub fn killport<I, S>(args: I) -> KillportRun
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    killport_with_env(args, Vec::<(&str, &str)>::new())
+}
+
+/// Runs killport with `args` and the environment variables `envs` added to
+/// its environment, and waits for it to exit.
+pub fn killport_with_env<I, S, E, K, V>(args: I, envs: E) -> KillportRun
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+    E: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    let output = SystemCommand::new(assert_cmd::cargo::cargo_bin("killport"))
+        .args(args)
+        .envs(envs)
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run killport");
+
+    KillportRun {
+        code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }
+}
+
+/// Returns a pattern matching the line killport reports the mock process on
+/// `port` with, wherever the mock process was built, e.g. "Successfully
+/// killed process '/tmp/.tmpa1B2/mock_process' listening on port 8080".
+///
+/// # Arguments
+///
+/// * `msg` - The start of the line, e.g. "Would kill".
+/// * `port` - The port the mock process listens on.
+pub fn mock_process_line(msg: &str, port: u16) -> String {
+    format!(
+        r"(?m)^{} process '([^']*[/\\])?{}' listening on port {}\r?$",
+        regex::escape(msg),
+        regex::escape(MOCK_PROCESS_NAME),
+        port
+    )
+}
+
+/// Replaces what differs between runs and machines in killport's output
+/// with placeholders: the path of the mock process, process IDs, counts of
+/// uninspected processes and the version, as well as line endings.
+pub fn normalize(output: &str) -> String {
+    let replacements = [
+        (
+            r#"(?:[A-Za-z]:)?[^\s'"]*[/\\]mock_process(?:\.exe)?|mock_process\.exe"#,
+            "<mock_process>",
+        ),
+        (r"\bPID \d+", "PID <pid>"),
+        (r#""pid": \d+"#, r#""pid": "<pid>""#),
+        (r#""uninspected": \d+"#, r#""uninspected": "<count>""#),
+        (r#""version": "[^"]*""#, r#""version": "<version>""#),
+    ];
+
+    let mut output = output.replace("\r\n", "\n");
+    for (pattern, replacement) in replacements {
+        output = Regex::new(pattern)
+            .unwrap()
+            .replace_all(&output, replacement)
+            .into_owned();
+    }
+    output
+}
+
+/// Compares `output`, normalized, with the snapshot `name` kept under
+/// `tests/snapshots`, so that output formats other tools parse cannot change
+/// unnoticed. Run with `UPDATE_SNAPSHOTS=1` to write the snapshots instead.
+///
+/// # Arguments
+///
+/// * `name` - The file name of the snapshot, e.g. "kill.txt".
+/// * `output` - The output to compare.
+pub fn assert_snapshot(name: &str, output: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("snapshots")
+        .join(name);
+    let output = normalize(output);
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &output).unwrap();
+        return;
+    }
+
+    let snapshot = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read snapshot {}: {}", path.display(), e))
+        .replace("\r\n", "\n");
+    assert_eq!(
+        output, snapshot,
+        "output differs from snapshot {}; rerun with UPDATE_SNAPSHOTS=1 if the change is intended",
+        name
+    );
+}